@@ -1,20 +1,45 @@
 use anyhow::{Context, Result};
 use blake3::Hasher;
 use chrono::Utc;
-use clap::{Parser, Subcommand, ValueHint};
+use clap::{Args, Parser, Subcommand, ValueEnum, ValueHint};
+use comfy_table::{CellAlignment, ContentArrangement, Table};
 use directories::ProjectDirs;
-use html_escape::decode_html_entities;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use html_escape::{decode_html_entities, encode_text};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fs;
-use std::io::{BufRead, BufReader, Read};
+use std::io::{self, BufRead, BufReader, IsTerminal, Read, Seek, SeekFrom, Write};
+use std::net::{TcpListener, TcpStream};
+use std::ops::{Bound, Range};
+use std::panic::{self, AssertUnwindSafe};
 use std::path::{Path, PathBuf};
-use std::time::{Duration, UNIX_EPOCH};
+use std::process::{self, ExitCode};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant, UNIX_EPOCH};
 use tantivy::collector::TopDocs;
-use tantivy::query::QueryParser;
-use tantivy::schema::{Schema, SchemaBuilder, TantivyDocument, Value, STORED, STRING, TEXT};
-use tantivy::snippet::SnippetGenerator;
-use tantivy::{doc, Index, Term};
+use tantivy::directory::error::OpenReadError;
+use tantivy::merge_policy::{LogMergePolicy, MergePolicy, NoMergePolicy};
+use tantivy::query::{
+    AllQuery, BooleanQuery, BoostQuery, ConstScoreQuery, Occur, PhraseQuery, Query, QueryParser,
+    RangeQuery, TermQuery,
+};
+use tantivy::schema::{
+    Field, FieldType, IndexRecordOption, OwnedValue, Schema, SchemaBuilder, TantivyDocument, Value,
+    FAST, INDEXED, STORED, STRING, TEXT,
+};
+use tantivy::snippet::{collapse_overlapped_ranges, SnippetGenerator};
+use tantivy::tokenizer::{
+    Language, LowerCaser, RawTokenizer, RemoveLongFilter, SimpleTokenizer, Stemmer, StopWordFilter,
+    TextAnalyzer, TokenizerManager,
+};
+use tantivy::{
+    doc, DateTime, DocId, DocSet, Document, Index, IndexReader, IndexWriter, ReloadPolicy, Score,
+    Searcher, SegmentReader, Term, TERMINATED,
+};
 
 /// Local file search tool (offline, private).
 #[derive(Parser, Debug)]
@@ -23,9 +48,19 @@ use tantivy::{doc, Index, Term};
     version,
     about = "Vault: local, offline file search",
     author = "You",
-    arg_required_else_help = true
+    arg_required_else_help = true,
+    after_help = "Exit codes: 0 success, 1 other error, 2 invalid usage, 3 config missing or invalid, \
+                  4 index missing, 5 query parse error, 6 index corrupt."
 )]
 struct Cli {
+    /// Config file to use instead of $VAULTSEARCH_CONFIG or the platform config directory
+    #[arg(long, global = true, value_name = "PATH", value_hint = ValueHint::FilePath)]
+    config: Option<PathBuf>,
+
+    /// When to highlight matches with terminal escapes (`auto` = only on a terminal, unless $NO_COLOR is set)
+    #[arg(long, global = true, value_name = "WHEN", value_enum, default_value_t = ColorChoice::Auto)]
+    color: ColorChoice,
+
     #[command(subcommand)]
     command: Command,
 }
@@ -35,71 +70,1303 @@ enum Command {
     /// Initialize config and index for a root folder
     Init {
         /// Root directory to index (e.g. C:\Users\You\Documents)
-        #[arg(long, value_hint = ValueHint::DirPath)]
-        root: String,
+        #[arg(long, value_hint = ValueHint::DirPath, required_unless_present = "print_only")]
+        root: Option<String>,
         /// Recreate the index directory if it already exists
         #[arg(long)]
         force: bool,
+        /// Maximum directory depth to descend below the root (0 = only files directly in the root)
+        #[arg(long, value_name = "N")]
+        max_depth: Option<usize>,
+        /// Store the index in DIR instead of the platform data directory (saved to config)
+        #[arg(long, value_name = "DIR", value_hint = ValueHint::DirPath)]
+        index_dir: Option<String>,
+        /// Drop the words listed in PATH (one per line) from file contents (saved to config)
+        #[arg(long, value_name = "PATH", value_hint = ValueHint::FilePath)]
+        stop_words_file: Option<PathBuf>,
+        /// Skip words longer than N bytes in file contents, such as base64 blobs (default: 39; saved to config)
+        #[arg(long, value_name = "N", value_parser = clap::value_parser!(u16).range(1..))]
+        max_token_length: Option<u16>,
+        /// Print where the config and index would be stored, without creating anything
+        #[arg(long, conflicts_with_all = ["force", "stop_words_file", "max_token_length"])]
+        print_only: bool,
     },
 
     /// Re-scan the filesystem and update the index
-    Index,
+    Index(IndexOptions),
 
     /// Search the index for a query string
-    Search {
-        /// Search query (e.g. "tax report 2023")
+    Search(Box<SearchArgs>),
+
+    /// Show the next page of results for the last search
+    Next,
+
+    /// Show the previous page of results for the last search
+    Prev,
+
+    /// Run every query in a file against one opened index, grouping results per query
+    Batch(BatchArgs),
+
+    /// Show the terms, fields, and boolean structure a query parses into, without searching
+    ExplainQuery {
+        /// Query to explain, written as for `search`
+        query: String,
+        /// Expand `*` wildcards as `search --wildcard` would
+        #[arg(long)]
+        wildcard: bool,
+        /// Expand word forms as `search --stem-language LANG` would
+        #[arg(long, value_enum, value_name = "LANG", conflicts_with = "wildcard")]
+        stem_language: Option<StemLanguage>,
+        /// Expand configured synonyms as `search --synonyms` would
+        #[arg(long, conflicts_with_all = ["wildcard", "stem_language"])]
+        synonyms: bool,
+    },
+
+    /// Check that a query parses against the index, without searching (exit code 5 if not)
+    ValidateQuery {
+        /// Query to check, written as for `search`
         query: String,
+        /// Print a confirmation when the query is valid instead of staying silent
+        #[arg(long)]
+        verbose: bool,
+    },
+
+    /// Merge index segments and purge deleted documents to speed up searches
+    Compact,
+
+    /// Print a JSON manifest of the index directory's files with sizes and hashes, or verify one
+    Manifest {
+        /// Compare the index directory with a manifest saved earlier (exit code 6 on any difference)
+        #[arg(long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+        verify: Option<PathBuf>,
+    },
+
+    /// Manage the search result cache enabled by `cache_results`
+    Cache {
+        #[command(subcommand)]
+        action: CacheAction,
     },
+
+    /// Keep the index open and answer `search --daemon` queries over a Unix socket
+    Daemon,
+
+    /// Serve read-only searches over TCP for `search --server` clients
+    Serve {
+        /// Address to listen on; use 0.0.0.0:PORT to accept connections from other hosts
+        #[arg(long, value_name = "ADDR", default_value = DEFAULT_SERVE_ADDR)]
+        listen: String,
+    },
+
+    /// Describe the index schema (fields, types, and indexing options)
+    Schema {
+        /// Output format
+        #[arg(long, value_enum, default_value_t = SchemaFormat::Table)]
+        format: SchemaFormat,
+    },
+
+    /// List file extensions, or the terms of an indexed text field, by document count
+    Tags {
+        /// `extension`, or the name of an indexed text field such as `path`
+        #[arg(long, value_name = "FIELD", default_value = TAGS_BY_EXTENSION)]
+        by: String,
+        /// Show only the N most common values
+        #[arg(long, value_name = "N")]
+        limit: Option<usize>,
+    },
+
+    /// Remove indexed documents whose path matches a glob, without re-indexing
+    Forget {
+        /// Glob matched against absolute paths and paths relative to the root (e.g. `archive/**`)
+        pattern: String,
+        /// Allow a glob that matches every indexed document
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// List the files the last indexing run skipped, with the reason for each
+    LastErrors {
+        /// Only list files skipped for this reason
+        #[arg(long, value_enum)]
+        reason: Option<SkipReason>,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum ColorChoice {
+    Always,
+    Auto,
+    Never,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum SchemaFormat {
+    Table,
+    Json,
+}
+
+/// Search options; also saved as the last search for `next` and `prev`.
+#[derive(Args, Debug, Clone, Serialize, Deserialize)]
+struct SearchArgs {
+    /// Search query (e.g. "tax report 2023"); use `-` to read it from stdin
+    #[arg(required_unless_present_any = ["query_file", "near_file", "any_of"])]
+    query: Option<String>,
+    /// Read the search query from a file instead of the command line
+    #[arg(long, value_name = "PATH", value_hint = ValueHint::FilePath, conflicts_with = "query")]
+    query_file: Option<PathBuf>,
+    /// Match files whose contents mention any term listed in PATH (one per line; `#` starts a comment)
+    #[arg(long, value_name = "PATH", value_hint = ValueHint::FilePath, conflicts_with = "near_file")]
+    any_of: Option<PathBuf>,
+    /// Find files similar to this one, using its most distinctive terms as the query
+    #[arg(long, value_name = "PATH", value_hint = ValueHint::FilePath, conflicts_with_all = ["query", "query_file", "wildcard", "stem_language", "synonyms"])]
+    near_file: Option<PathBuf>,
+    /// After printing results, prompt for a result number and open it in $EDITOR
+    #[arg(long)]
+    interactive_open: bool,
+    /// Write results to FILE instead of stdout, without highlighting (parent directories are created)
+    #[arg(long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+    output: Option<PathBuf>,
+    /// Maximum number of results to return
+    #[arg(long, value_name = "N", default_value_t = TOP_RESULTS)]
+    limit: usize,
+    /// Skip the first N results, for paging through long result lists
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    offset: usize,
+    /// Output format for results
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    format: OutputFormat,
+    /// Also print the last N lines of each matching file (read live from disk)
+    #[arg(long, value_name = "N")]
+    preview_lines: Option<usize>,
+    /// Treat `*` in bare terms as a wildcard (`config*`, `*report`); slower on large indexes
+    #[arg(long)]
+    wildcard: bool,
+    /// Also match other word forms of bare query terms, stemmed for LANG (e.g. `german`)
+    #[arg(long, value_enum, value_name = "LANG", conflicts_with = "wildcard")]
+    stem_language: Option<StemLanguage>,
+    /// Expand words listed in the config's `[synonyms]` table into OR groups with their synonyms
+    #[arg(long, conflicts_with_all = ["wildcard", "stem_language"])]
+    synonyms: bool,
+    /// Match query words only as whole indexed words, rejecting anything that matches word parts
+    #[arg(long, visible_alias = "match-whole-word", conflicts_with_all = ["wildcard", "stem_language"])]
+    whole_word: bool,
+    /// Field searched by bare terms; repeat for several (overrides `search_fields` in config)
+    #[arg(long = "search-field", value_name = "FIELD")]
+    search_fields: Vec<String>,
+    /// Search only comments and docstrings extracted from source files (same as `--search-field comments`)
+    #[arg(long, conflicts_with = "search_fields")]
+    comments: bool,
+    /// Multiply scores from FIELD by WEIGHT, e.g. `path=3`; repeatable (overrides `field_boosts` in config)
+    #[arg(long = "field-boost", value_name = "FIELD=WEIGHT", value_parser = parse_field_boost)]
+    field_boosts: Vec<(String, f32)>,
+    /// Rank with the preset NAME from `[relevance_profiles]` (default: `default_relevance_profile`)
+    #[arg(long, value_name = "NAME")]
+    relevance: Option<String>,
+    /// Result ordering; score ties are always broken by path so output is reproducible
+    #[arg(long, value_enum, default_value_t = SortOrder::Score)]
+    sort: SortOrder,
+    /// Rank recently modified files higher, decaying scores with age (see `[recency_boost]`)
+    #[arg(long)]
+    recency_boost: bool,
+    /// Age in days at which `--recency-boost` halves a score, overriding the configured value
+    #[arg(long, value_name = "DAYS", requires = "recency_boost")]
+    recency_half_life: Option<f64>,
+    /// Only match files whose indexed version was written by indexing run N or earlier
+    #[arg(long, value_name = "N")]
+    as_of_generation: Option<u64>,
+    /// Drop query words shorter than N characters, with a warning (1 keeps every word)
+    #[arg(long, value_name = "N", default_value_t = 1)]
+    min_term_length: usize,
+    /// Include every stored field of each result as a JSON object (long text is truncated)
+    #[arg(long)]
+    fields_json: bool,
+    /// Report scores relative to the best result (0.0–1.0) instead of raw BM25 scores
+    #[arg(long)]
+    score_normalize: bool,
+    /// Skip snippet generation and print only ranks, scores, and paths (faster on broad queries)
+    #[arg(long)]
+    no_snippet: bool,
+    /// Text written before each highlighted match instead of terminal bold, in every output format
+    #[arg(long, value_name = "STR", allow_hyphen_values = true)]
+    highlight_open: Option<String>,
+    /// Text written after each highlighted match (see `--highlight-open`)
+    #[arg(long, value_name = "STR", allow_hyphen_values = true)]
+    highlight_close: Option<String>,
+    /// Instead of snippets, print each result's lines that contain a query term, like grep (text format)
+    #[arg(long, conflicts_with_all = ["no_snippet", "near_file"])]
+    highlight_only: bool,
+    /// With --highlight-only, print at most N matching lines per file
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 5,
+        requires = "highlight_only"
+    )]
+    max_matches_per_file: usize,
+    /// Hide results whose path matches GLOB (e.g. `**/archive/**`); repeatable
+    #[arg(long, value_name = "GLOB")]
+    exclude_path: Vec<String>,
+    /// Return at most N results from any one directory, promoting hits from other directories
+    #[arg(long, value_name = "N")]
+    limit_per_dir: Option<usize>,
+    /// Only return files detected as language CODE: ISO 639-3 such as `eng`, `fra`, `spa`, or `unknown`
+    #[arg(long, value_name = "CODE", value_parser = parse_language_code)]
+    language: Option<String>,
+    /// Only return files with at least N lines
+    #[arg(long, value_name = "N")]
+    min_lines: Option<u64>,
+    /// Only return files with at most N lines
+    #[arg(long, value_name = "N")]
+    max_lines: Option<u64>,
+    /// Only return files modified within DUR of now: a number with `s`, `m`, `h`, `d`, or `w` (e.g. `24h`, `7d`, `2w`)
+    #[arg(long, value_name = "DUR", value_parser = parse_duration_secs)]
+    modified_within: Option<u64>,
+    /// Only return files whose modification time on disk is at or after TIME (RFC 3339 or YYYY-MM-DD, UTC); stats each candidate
+    #[arg(long, value_name = "TIME", value_parser = parse_since)]
+    since: Option<i64>,
+    /// Only return files owned by this numeric user id (Unix only)
+    #[arg(long, value_name = "UID")]
+    owner: Option<u64>,
+    /// Only return files with these permission bits: `644` matches exactly, `-002` requires all listed bits (Unix only)
+    #[arg(long, value_name = "OCTAL", allow_hyphen_values = true)]
+    mode: Option<String>,
+    /// Show result paths relative to DIR instead of the index root (absolute when outside DIR)
+    #[arg(long, value_name = "DIR", value_hint = ValueHint::DirPath, conflicts_with = "cwd_relative")]
+    relative_to: Option<PathBuf>,
+    /// Show result paths relative to the current directory (absolute when outside it)
+    #[arg(long)]
+    cwd_relative: bool,
+    /// Add the line where each file first mentions a query term, as `path:LINE` (read live from disk)
+    #[arg(long, conflicts_with = "near_file")]
+    line_numbers: bool,
+    /// Omit the `Results for query:` header line (text format)
+    #[arg(long)]
+    no_header: bool,
+    /// Print details such as the detected language under each result (text format), and cache hits
+    #[arg(long)]
+    verbose: bool,
+    /// Search the index even when `cache_results` has these results cached
+    #[arg(long)]
+    no_cache: bool,
+    /// Line printed between results in text format; pass an empty string for none
+    #[arg(long, value_name = "STR", allow_hyphen_values = true)]
+    separator: Option<String>,
+    /// Query a `vaultsearch serve` instance at ADDR (host:port) instead of the local index
+    #[arg(long, value_name = "ADDR")]
+    server: Option<String>,
+    /// Print absolute paths separated by NUL bytes, like `find -print0` (same as `--format null`)
+    #[arg(long, conflicts_with = "format")]
+    print0: bool,
+    /// Ask a running `vaultsearch daemon` first, opening the index directly if none is running
+    #[arg(long, conflicts_with = "server")]
+    daemon: bool,
+    /// Verify the index opens, reads, and answers a query before searching (exit code 6 if not)
+    #[arg(long, conflicts_with = "server")]
+    check: bool,
+    /// Search a hard-linked copy of the current commit, isolated from concurrent `index` runs
+    #[arg(long, conflicts_with_all = ["server", "daemon"])]
+    snapshot: bool,
+    /// Search this index directory instead of the configured one, read-only (repeatable; results are merged by score)
+    #[arg(
+        long = "index",
+        value_name = "DIR",
+        value_hint = ValueHint::DirPath,
+        conflicts_with_all = ["server", "daemon", "snapshot", "check", "relative_to", "cwd_relative"]
+    )]
+    indexes: Vec<PathBuf>,
+    /// Search the root NAME from the config's `[roots]` table instead of the top-level root
+    #[arg(long, value_name = "NAME", conflicts_with_all = ["server", "daemon", "indexes"])]
+    root_name: Option<String>,
+    /// Search the top-level root and every `[roots]` entry, merging results by score
+    #[arg(
+        long,
+        conflicts_with_all = ["server", "daemon", "indexes", "root_name", "snapshot", "relative_to", "cwd_relative"]
+    )]
+    all_roots: bool,
+    /// Print which config file is in effect and its key settings to stderr before searching
+    #[arg(long, conflicts_with_all = ["server", "indexes"])]
+    show_config_source: bool,
+    /// Set on the saved last search when its page came back short, so `next` stops there
+    #[arg(skip)]
+    #[serde(default)]
+    last_page: bool,
+    /// Config file the saved last search ran under, which `next` and `prev` search again
+    #[arg(skip)]
+    #[serde(default)]
+    config_file: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+struct BatchArgs {
+    /// File with one query per line; blank lines and lines starting with `#` are ignored
+    #[arg(long, value_name = "PATH", value_hint = ValueHint::FilePath)]
+    queries_file: PathBuf,
+    /// Maximum number of results per query
+    #[arg(long, value_name = "N", default_value_t = TOP_RESULTS)]
+    limit: usize,
+    /// Output format: `text` groups results under each query, `json` maps each query to its results
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    format: OutputFormat,
+    /// Result ordering within each query
+    #[arg(long, value_enum, default_value_t = SortOrder::Score)]
+    sort: SortOrder,
+    /// Skip snippet generation and print only ranks, scores, and paths
+    #[arg(long)]
+    no_snippet: bool,
+}
+
+/// Search options that run against the index, shared by local searches and `serve` requests.
+/// Output-only options (format, separators, previews) stay on the client.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SearchRequest {
+    query: String,
+    #[serde(default = "default_result_limit")]
+    limit: usize,
+    #[serde(default)]
+    offset: usize,
+    #[serde(default)]
+    wildcard: bool,
+    #[serde(default)]
+    stem_language: Option<StemLanguage>,
+    #[serde(default)]
+    synonyms: bool,
+    #[serde(default)]
+    whole_word: bool,
+    /// Replaces the configured `search_fields` when not empty
+    #[serde(default)]
+    search_fields: Vec<String>,
+    /// Merged over the configured `field_boosts`
+    #[serde(default)]
+    field_boosts: BTreeMap<String, f32>,
+    /// Name of a `[relevance_profiles]` preset, resolved against the searching side's config
+    #[serde(default)]
+    relevance: Option<String>,
+    #[serde(default)]
+    sort: SortOrder,
+    #[serde(default)]
+    recency_boost: bool,
+    /// Overrides the configured `recency_boost.half_life_days`
+    #[serde(default)]
+    recency_half_life: Option<f64>,
+    #[serde(default)]
+    owner: Option<u64>,
+    #[serde(default)]
+    mode: Option<String>,
+    #[serde(default)]
+    exclude_path: Vec<String>,
+    #[serde(default)]
+    limit_per_dir: Option<usize>,
+    #[serde(default)]
+    language: Option<String>,
+    #[serde(default)]
+    min_lines: Option<u64>,
+    #[serde(default)]
+    max_lines: Option<u64>,
+    /// Seconds before now, from `--modified-within`
+    #[serde(default)]
+    modified_within: Option<u64>,
+    /// Unix seconds from `--since`, compared with each result's modification time on disk
+    #[serde(default)]
+    since: Option<i64>,
+    #[serde(default)]
+    as_of_generation: Option<u64>,
+    #[serde(default)]
+    no_snippet: bool,
+    #[serde(default)]
+    fields_json: bool,
+    /// Fill in each hit's `size` and `modified`, for `--format table`
+    #[serde(default)]
+    file_details: bool,
+    /// Fill in each hit's `match_spans`, for `--format json`
+    #[serde(default)]
+    match_spans: bool,
+    /// Reference file for `--near-file`; local only, since the file lives on the client
+    #[serde(skip)]
+    near_file: Option<PathBuf>,
 }
 
+/// One line of the `serve` protocol sent back for each request line.
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum SearchResponse {
+    Results(Vec<SearchHit>),
+    Error(String),
+}
+
+fn default_result_limit() -> usize {
+    TOP_RESULTS
+}
+
+/// Permission filter for `search --mode`, modeled on `find -perm`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ModeFilter {
+    /// Permission bits must be exactly these
+    Exact(u32),
+    /// Every listed bit must be set (written with a leading `-`)
+    AllOf(u32),
+}
+
+impl ModeFilter {
+    fn matches(self, mode: u32) -> bool {
+        match self {
+            ModeFilter::Exact(bits) => mode == bits,
+            ModeFilter::AllOf(bits) => mode & bits == bits,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum OutputFormat {
+    /// Human-readable list with highlighted snippets
+    Text,
+    /// JSON array of result objects
+    Json,
+    /// CSV with a `rank,score,path,relative_path,snippet` header row
+    Csv,
+    /// Absolute paths, each terminated by a NUL byte (for `xargs -0`)
+    Null,
+    /// Aligned rank, score, size, modified, and path columns fitted to the terminal width
+    Table,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum SortOrder {
+    /// Highest score first
+    #[default]
+    Score,
+    /// Alphabetical by path (applied to the top-scoring results)
+    Path,
+    /// Most lines first (applied to the top-scoring results)
+    Lines,
+}
+
+/// Stemming languages for `search --stem-language`.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum StemLanguage {
+    Arabic,
+    Danish,
+    Dutch,
+    English,
+    Finnish,
+    French,
+    German,
+    Greek,
+    Hungarian,
+    Italian,
+    Norwegian,
+    Portuguese,
+    Romanian,
+    Russian,
+    Spanish,
+    Swedish,
+    Tamil,
+    Turkish,
+}
+
+impl StemLanguage {
+    fn tantivy_language(self) -> Language {
+        match self {
+            StemLanguage::Arabic => Language::Arabic,
+            StemLanguage::Danish => Language::Danish,
+            StemLanguage::Dutch => Language::Dutch,
+            StemLanguage::English => Language::English,
+            StemLanguage::Finnish => Language::Finnish,
+            StemLanguage::French => Language::French,
+            StemLanguage::German => Language::German,
+            StemLanguage::Greek => Language::Greek,
+            StemLanguage::Hungarian => Language::Hungarian,
+            StemLanguage::Italian => Language::Italian,
+            StemLanguage::Norwegian => Language::Norwegian,
+            StemLanguage::Portuguese => Language::Portuguese,
+            StemLanguage::Romanian => Language::Romanian,
+            StemLanguage::Russian => Language::Russian,
+            StemLanguage::Spanish => Language::Spanish,
+            StemLanguage::Swedish => Language::Swedish,
+            StemLanguage::Tamil => Language::Tamil,
+            StemLanguage::Turkish => Language::Turkish,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct AppConfig {
-    /// Root directory that will be indexed
+    /// Root directory that will be indexed (may reference `~` and `$VARS`)
     root: String,
     /// Directory where the Tantivy index is stored
     index_dir: String,
     /// Timestamp of last successful indexing run
     #[serde(default)]
     last_indexed: Option<String>,
+    /// Number of the last indexing run, recorded on every document it added or updated
+    #[serde(default, skip_serializing_if = "is_zero")]
+    index_generation: u64,
+    /// Maximum directory depth below the root (0 = only files directly in the root)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_depth: Option<usize>,
+    /// `false` indexes only files directly in the root, like `max_depth = 0`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    recursive: Option<bool>,
+    /// Files smaller than this many bytes are skipped as stubs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    min_file_size: Option<u64>,
+    /// Print a progress line every N indexed files (0 disables intermediate progress)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    progress_every: Option<usize>,
+    /// Directory names whose subtrees are never indexed (e.g. `node_modules`)
+    #[serde(default = "default_excluded_dirs")]
+    default_excluded_dirs: Vec<String>,
+    /// Maximum file sizes to index, with optional per-extension overrides
+    #[serde(default, skip_serializing_if = "SizeLimits::is_default")]
+    size_limits: SizeLimits,
+    /// How `search --recency-boost` decays scores with file age
+    #[serde(default, skip_serializing_if = "RecencyBoost::is_default")]
+    recency_boost: RecencyBoost,
+    /// Suffix of sidecar files whose text is indexed with the file they describe (empty disables)
+    #[serde(default = "default_sidecar_suffix")]
+    sidecar_suffix: String,
+    /// Most skipped files recorded for `last-errors` per indexing run (0 disables recording)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_recorded_skips: Option<usize>,
+    /// Words that `search --synonyms` expands into OR groups (e.g. `car = ["automobile", "vehicle"]`)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    synonyms: BTreeMap<String, Vec<String>>,
+    /// Fields searched by bare query terms (default: `path` and `contents`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    search_fields: Vec<String>,
+    /// Score multipliers per field for bare query terms (e.g. `path = 3.0`)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    field_boosts: BTreeMap<String, f32>,
+    /// Profile used when `search --relevance` is not given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    default_relevance_profile: Option<String>,
+    /// Named ranking presets for `search --relevance`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    relevance_profiles: BTreeMap<String, RelevanceProfile>,
+    /// Further roots, each with its own index, for `--root-name` and `--all-roots`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    roots: BTreeMap<String, NamedRoot>,
+    /// Words dropped from `contents` when indexing and searching; changing them needs a rebuild
+    #[serde(default, skip_serializing_if = "StopWords::is_empty_list")]
+    stop_words: StopWords,
+    /// Longest token kept in `contents`, in bytes; changing it needs a rebuild
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_token_length: Option<usize>,
+    /// Extensions whose text is indexed into a field other than `contents`; changing it needs a rebuild
+    #[serde(default, skip_serializing_if = "FieldRouting::is_empty")]
+    field_routing: FieldRouting,
+    /// Store paths relative to `root` so the index can move between machines; changing it re-adds every file
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    store_relative_paths: bool,
+    /// When `serve` and `daemon` readers pick up new commits (`on-commit` or `manual`)
+    #[serde(default, skip_serializing_if = "ReaderReload::is_on_commit")]
+    reader_reload: ReaderReload,
+    /// Reuse results of identical searches until the next `index` run
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    cache_results: bool,
+    /// How `index` merges segments (`log` or `no-merge`)
+    #[serde(default, skip_serializing_if = "MergePolicyKind::is_log")]
+    merge_policy: MergePolicyKind,
+    /// Segment count that triggers a `log` merge
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    merge_min_segments: Option<u16>,
+    /// How `contents` is tokenized, resolved from `stop_words`, `max_token_length`, and `field_routing`
+    #[serde(skip)]
+    analysis: ContentsAnalysis,
+    /// `root` as written in the config file, kept so saving does not bake in expanded variables
+    #[serde(skip)]
+    raw_root: Option<String>,
+    /// Project-local config merged over this one, if any was discovered
+    #[serde(skip)]
+    project_config: Option<PathBuf>,
+    /// `[roots]` entry this config was narrowed to by `--root-name`, whose run state saving updates
+    #[serde(skip)]
+    root_name: Option<String>,
+    /// Whether the project config moved the root and with it the index, whose run state is then
+    /// kept in the index directory instead of the config file
+    #[serde(skip)]
+    project_index: bool,
+    /// File this config was read from and why that file was chosen (unset until loaded)
+    #[serde(skip)]
+    loaded_from: Option<(PathBuf, ConfigSource)>,
+}
+
+/// Overrides read from a project-local `.vaultsearch.toml`; unset fields keep the global value.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProjectConfig {
+    /// Root to index; relative paths are resolved against the directory holding the file
+    root: Option<String>,
+    max_depth: Option<usize>,
+    recursive: Option<bool>,
+    min_file_size: Option<u64>,
+    progress_every: Option<usize>,
+    default_excluded_dirs: Option<Vec<String>>,
+    size_limits: Option<SizeLimits>,
+    sidecar_suffix: Option<String>,
+    synonyms: Option<BTreeMap<String, Vec<String>>>,
+    search_fields: Option<Vec<String>>,
+    field_boosts: Option<BTreeMap<String, f32>>,
+}
+
+/// Settings of the `contents` and `code` tokenizers, which the index schema records by name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ContentsAnalysis {
+    /// Lowercased, de-duplicated, and sorted
+    stop_words: Vec<String>,
+    max_token_length: usize,
+    /// Extensions routed to the `code` field, as from `FieldRouting::code_extensions`
+    code_extensions: Vec<String>,
+}
+
+impl Default for ContentsAnalysis {
+    fn default() -> Self {
+        Self {
+            stop_words: Vec::new(),
+            max_token_length: DEFAULT_MAX_TOKEN_LENGTH,
+            code_extensions: Vec::new(),
+        }
+    }
+}
+
+impl ContentsAnalysis {
+    /// Whether `contents` uses Tantivy's built-in `default` tokenizer, which needs no registration.
+    fn is_default(&self) -> bool {
+        self.stop_words.is_empty() && self.max_token_length == DEFAULT_MAX_TOKEN_LENGTH
+    }
+
+    /// Whether files at `path` are indexed into `code` instead of `contents`.
+    fn routes_to_code(&self, path: &Path) -> bool {
+        path.extension()
+            .and_then(|s| s.to_str())
+            .is_some_and(|ext| {
+                self.code_extensions
+                    .binary_search(&ext.to_ascii_lowercase())
+                    .is_ok()
+            })
+    }
+}
+
+/// Extensions indexed into a field other than `contents`, configured under `[field_routing]`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FieldRouting {
+    /// Extensions indexed into `code`, which keeps stop words (e.g. `["rs", "py"]`)
+    #[serde(default)]
+    code: Vec<String>,
+}
+
+impl FieldRouting {
+    fn is_empty(&self) -> bool {
+        self.code.is_empty()
+    }
+
+    /// Lowercased, without a leading `.`, de-duplicated, and sorted, so the same set of
+    /// extensions always yields the same tokenizer name.
+    fn code_extensions(&self) -> Vec<String> {
+        let extensions: BTreeSet<String> = self
+            .code
+            .iter()
+            .map(|ext| ext.trim().trim_start_matches('.').to_ascii_lowercase())
+            .filter(|ext| !ext.is_empty())
+            .collect();
+        extensions.into_iter().collect()
+    }
+}
+
+/// A named ranking preset under `[relevance_profiles.<name>]`, picked with `search --relevance`.
+/// Command-line flags still win over the profile, and the profile over the top-level settings.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RelevanceProfile {
+    /// Replaces `search_fields` when not empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    search_fields: Vec<String>,
+    /// Merged over `field_boosts`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    field_boosts: BTreeMap<String, f32>,
+    /// Rank recently modified files higher, as with `--recency-boost`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    recency_boost: bool,
+    /// Overrides `[recency_boost] half_life_days`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    recency_half_life_days: Option<f64>,
+}
+
+/// A `[roots.NAME]` entry: a root indexed into its own directory with the other settings of
+/// the config. Its run state is kept here instead of in the top-level keys.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NamedRoot {
+    /// Directory to index (may reference `~` and `$VARS`)
+    root: String,
+    /// Directory of this root's Tantivy index
+    index_dir: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    last_indexed: Option<String>,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    index_generation: u64,
+}
+
+/// The `reader_reload` config value: how long-lived index readers see new commits.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum ReaderReload {
+    /// Tantivy watches `meta.json` on a background thread and reloads shortly after each commit
+    #[default]
+    OnCommit,
+    /// No watcher thread; `serve` and `daemon` reload before each request instead
+    Manual,
+}
+
+impl ReaderReload {
+    fn is_on_commit(&self) -> bool {
+        *self == ReaderReload::OnCommit
+    }
+
+    fn policy(self) -> ReloadPolicy {
+        match self {
+            ReaderReload::OnCommit => ReloadPolicy::OnCommitWithDelay,
+            ReaderReload::Manual => ReloadPolicy::Manual,
+        }
+    }
+}
+
+/// The `merge_policy` config value and `index --merge-policy`: how Tantivy merges segments in the background.
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum MergePolicyKind {
+    /// Merge groups of similar-sized segments while indexing (Tantivy's default)
+    #[default]
+    Log,
+    /// Never merge while indexing; run `vaultsearch compact` afterwards
+    NoMerge,
+}
+
+impl MergePolicyKind {
+    fn is_log(&self) -> bool {
+        *self == MergePolicyKind::Log
+    }
+
+    fn policy(self, min_segments: Option<u16>) -> Box<dyn MergePolicy> {
+        match self {
+            MergePolicyKind::Log => {
+                let mut policy = LogMergePolicy::default();
+                if let Some(min) = min_segments {
+                    policy.set_min_num_segments(usize::from(min));
+                }
+                Box::new(policy)
+            }
+            MergePolicyKind::NoMerge => Box::new(NoMergePolicy),
+        }
+    }
+}
+
+/// The `stop_words` config value: either the words themselves or a file listing them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+enum StopWords {
+    /// Words listed inline, e.g. `stop_words = ["system", "data"]`
+    List(Vec<String>),
+    /// Path to a file with one word per line; blank lines and `#` comments are ignored
+    File(String),
+}
+
+impl Default for StopWords {
+    fn default() -> Self {
+        StopWords::List(Vec::new())
+    }
+}
+
+impl StopWords {
+    fn is_empty_list(&self) -> bool {
+        matches!(self, StopWords::List(words) if words.is_empty())
+    }
+
+    /// Returns the words lowercased (as the tokenizer sees them), de-duplicated, and
+    /// sorted, so the same set of words always yields the same tokenizer name.
+    fn resolve(&self) -> Result<Vec<String>> {
+        let words: BTreeSet<String> = match self {
+            StopWords::List(words) => words
+                .iter()
+                .map(|word| word.trim().to_lowercase())
+                .collect(),
+            StopWords::File(path) => {
+                let path = shellexpand::tilde(path);
+                let text = fs::read_to_string(path.as_ref())
+                    .with_context(|| format!("Failed to read stop-words file {path}"))?;
+                text.lines()
+                    .map(str::trim)
+                    .filter(|line| !line.starts_with('#'))
+                    .map(str::to_lowercase)
+                    .collect()
+            }
+        };
+        Ok(words.into_iter().filter(|word| !word.is_empty()).collect())
+    }
+}
+
+/// File size caps applied during indexing, configured under `[size_limits]`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct SizeLimits {
+    /// Limit in bytes for extensions without an override
+    #[serde(default = "default_max_file_size")]
+    default: u64,
+    /// Limits in bytes keyed by lowercase extension (e.g. `log = 50000000`)
+    #[serde(default)]
+    per_extension: HashMap<String, u64>,
+}
+
+impl Default for SizeLimits {
+    fn default() -> Self {
+        Self {
+            default: MAX_FILE_SIZE_BYTES,
+            per_extension: HashMap::new(),
+        }
+    }
+}
+
+impl SizeLimits {
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Returns the override for the file's extension, falling back to the default limit.
+    fn limit_for(&self, path: &Path) -> u64 {
+        path.extension()
+            .and_then(|s| s.to_str())
+            .and_then(|ext| self.per_extension.get(&ext.to_ascii_lowercase()))
+            .copied()
+            .unwrap_or(self.default)
+    }
+}
+
+/// Score decay for `search --recency-boost`, configured under `[recency_boost]`. Each score
+/// is multiplied by `min_weight + (1 - min_weight) * 0.5^(age / half_life_days)`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+struct RecencyBoost {
+    /// Age in days at which the recency part of the weight halves
+    #[serde(default = "default_recency_half_life_days")]
+    half_life_days: f64,
+    /// Weight that even the oldest files keep, from 0 (none) to 1 (no decay)
+    #[serde(default)]
+    min_weight: f64,
+}
+
+impl Default for RecencyBoost {
+    fn default() -> Self {
+        Self {
+            half_life_days: DEFAULT_RECENCY_HALF_LIFE_DAYS,
+            min_weight: 0.0,
+        }
+    }
+}
+
+impl RecencyBoost {
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    fn validate(&self) -> Result<()> {
+        if !(self.half_life_days > 0.0 && self.half_life_days.is_finite()) {
+            anyhow::bail!(
+                "recency half-life must be a positive number of days, not {}",
+                self.half_life_days
+            );
+        }
+        if !(0.0..=1.0).contains(&self.min_weight) {
+            anyhow::bail!(
+                "recency_boost.min_weight must be between 0 and 1, not {}",
+                self.min_weight
+            );
+        }
+        Ok(())
+    }
+
+    /// Score multiplier for a file last modified `age_secs` ago; future times count as new.
+    fn weight(&self, age_secs: i64) -> f32 {
+        let age_days = age_secs.max(0) as f64 / 86_400.0;
+        let decay = 0.5_f64.powf(age_days / self.half_life_days);
+        (self.min_weight + (1.0 - self.min_weight) * decay) as f32
+    }
+}
+
+fn default_recency_half_life_days() -> f64 {
+    DEFAULT_RECENCY_HALF_LIFE_DAYS
+}
+
+fn is_zero(value: &u64) -> bool {
+    *value == 0
+}
+
+fn default_excluded_dirs() -> Vec<String> {
+    DEFAULT_EXCLUDED_DIRS
+        .iter()
+        .map(|d| d.to_string())
+        .collect()
+}
+
+fn default_sidecar_suffix() -> String {
+    DEFAULT_SIDECAR_SUFFIX.to_string()
+}
+
+fn default_max_file_size() -> u64 {
+    MAX_FILE_SIZE_BYTES
+}
+
+/// Per-run overrides for `perform_indexing` that are not persisted to config.
+#[derive(Args, Debug, Default)]
+struct IndexOptions {
+    /// Maximum directory depth for this run, overriding the configured value
+    #[arg(long, value_name = "N")]
+    max_depth: Option<usize>,
+    /// Index only files directly in the root for this run (same as `--max-depth 0`)
+    #[arg(long, conflicts_with = "max_depth")]
+    no_recursive: bool,
+    /// Skip files smaller than SIZE (e.g. `200`, `1KB`), overriding the configured value
+    #[arg(long, value_name = "SIZE", value_parser = parse_size_arg)]
+    min_file_size: Option<u64>,
+    /// Run OCR on image files and index the recognized text (requires the `ocr` feature)
+    #[arg(long)]
+    ocr: bool,
+    /// Print a progress line every N indexed files (0 disables), overriding the configured value
+    #[arg(long, value_name = "N")]
+    progress_every: Option<usize>,
+    /// Descend into directories listed in `default_excluded_dirs` for this run
+    #[arg(long)]
+    no_default_excludes: bool,
+    /// Fail (after printing the summary) if more than PCT percent of files were skipped
+    #[arg(long, value_name = "PCT")]
+    fail_if_skip_ratio: Option<f64>,
+    /// Fail (after printing the summary) if the committed index holds no documents
+    #[arg(long)]
+    fail_on_empty_index: bool,
+    /// Descend into symlinked directories and record where symlinked files point
+    #[arg(long)]
+    follow_symlinks: bool,
+    /// Index only files tracked by git (`git ls-files`) when the root is inside a git repository
+    #[arg(long)]
+    git_tracked_only: bool,
+    /// Index binary files and unsupported extensions by path alone (empty contents) instead of skipping them
+    #[arg(long)]
+    index_binary_paths: bool,
+    /// Threads reading and decoding files (default: twice the CPU count, at most 16)
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u16).range(1..=256))]
+    read_threads: Option<u16>,
+    /// Threads Tantivy uses to index documents (default: chosen by Tantivy, at most 8)
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u16).range(1..=MAX_INDEX_THREADS as i64))]
+    index_threads: Option<u16>,
+    /// Verify the index opens, reads, and answers a query before indexing (exit code 6 if not)
+    #[arg(long)]
+    check: bool,
+    /// Print how long each indexing phase took
+    #[arg(long)]
+    profile_stats: bool,
+    /// Print which config file is in effect and its key settings to stderr before indexing
+    #[arg(long)]
+    show_config_source: bool,
+    /// Skip paths matching the gitignore-style patterns in PATH, relative to the root (repeatable)
+    #[arg(long = "ignore-file", value_name = "PATH", value_hint = ValueHint::FilePath)]
+    ignore_files: Vec<PathBuf>,
+    /// Index the root NAME from the config's `[roots]` table into its own index directory
+    #[arg(long, value_name = "NAME")]
+    root_name: Option<String>,
+    /// Index the top-level root and then every `[roots]` entry
+    #[arg(long, conflicts_with_all = ["root_name", "explain_file", "resume"])]
+    all_roots: bool,
+    /// Continue an interrupted run, keeping the files it already committed
+    #[arg(long)]
+    resume: bool,
+    /// Commit and save progress every N added or updated files so a killed run can resume (0 disables)
+    #[arg(long, value_name = "N")]
+    checkpoint_every: Option<usize>,
+    /// Report which indexing checks PATH passes or fails, without indexing anything
+    #[arg(long, value_name = "PATH", value_hint = ValueHint::FilePath)]
+    explain_file: Option<PathBuf>,
+    /// How segments are merged while indexing (default: `merge_policy` from config, else `log`)
+    #[arg(long, value_enum)]
+    merge_policy: Option<MergePolicyKind>,
+    /// With the `log` policy, merge once this many similar-sized segments exist (default: 8)
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u16).range(2..))]
+    merge_min_segments: Option<u16>,
 }
 
 const INDEX_WRITER_HEAP_BYTES: usize = 50_000_000;
+/// Tantivy's minimum writer heap per indexing thread and its thread cap.
+const INDEX_WRITER_MIN_HEAP_PER_THREAD: usize = 15_000_000;
+const MAX_INDEX_THREADS: usize = 8;
+const DEFAULT_RECENCY_HALF_LIFE_DAYS: f64 = 30.0;
+/// Longest `contents` token kept, in bytes; Tantivy's `default` tokenizer drops tokens of 40 or more
+const DEFAULT_MAX_TOKEN_LENGTH: usize = 39;
+const MAX_DEFAULT_READ_THREADS: usize = 16;
 const INDEX_PROGRESS_CHUNK: usize = 100;
+const INDEX_CHECKPOINT_FILES: usize = 5_000;
 const TOP_RESULTS: usize = 20;
+const SNIPPET_MAX_CHARS: usize = 200;
+const FIELDS_JSON_MAX_CHARS: usize = 500;
+/// Longest line printed by `search --highlight-only` before it is cut off with `…`.
+const HIGHLIGHT_LINE_MAX_CHARS: usize = 300;
+/// Most `match_spans` reported per result in `--format json`.
+const MAX_MATCH_SPANS: usize = 100;
+const FILENAME_MATCH_LABEL: &str = "[matched filename]";
+/// Why file contents are not read for a hit that `--index` found stored relative to its root
+const UNKNOWN_ROOT_NOTE: &str = "path is stored relative to an unknown root";
+const PREVIEW_TAIL_MAX_BYTES: u64 = 64 * 1024;
+const WILDCARD_MAX_EXPANSIONS: usize = 256;
+const STEM_MAX_EXPANSIONS: usize = 64;
+const NEAR_FILE_MAX_TERMS: usize = 25;
+const DEFAULT_SERVE_ADDR: &str = "127.0.0.1:7878";
+const REMOTE_TIMEOUT_SECS: u64 = 30;
+/// Deepest result a search may reach (`offset + limit`); tantivy allocates for every slot up front.
+const MAX_RESULT_WINDOW: usize = 10_000;
+/// Longest request line `serve`/`daemon` will buffer before dropping the connection.
+const MAX_REQUEST_LINE_BYTES: u64 = 64 * 1024;
+/// Daemon sockets are `daemon-<hash of the index directory>.sock` in the data directory.
+const DAEMON_SOCKET_PREFIX: &str = "daemon-";
+/// `tags --by` value that groups files by extension instead of listing a field's terms
+const TAGS_BY_EXTENSION: &str = "extension";
+/// Names the config file when `--config` is not given
+const CONFIG_ENV_VAR: &str = "VAULTSEARCH_CONFIG";
+/// RFC 3339 time recorded as `last_indexed` instead of the clock, for reproducible output
+const NOW_ENV_VAR: &str = "VAULTSEARCH_NOW";
+
+/// The global `--config` flag, recorded in `run` before any command reads the config.
+static CONFIG_FLAG: OnceLock<PathBuf> = OnceLock::new();
+/// Whether text output to stdout highlights matches, decided in `run` from `--color`.
+static USE_COLOR: OnceLock<bool> = OnceLock::new();
+/// Last successful search, kept next to the config for `next` and `prev`
+const LAST_SEARCH_FILE: &str = "last_search.json";
+/// Label of the top-level root among `search --all-roots` results
+const MAIN_ROOT_LABEL: &str = "main";
+/// Results of recent searches, kept next to the config when `cache_results` is on in
+/// `result_cache-<hash of the index directory>.json`, one file per index
+const RESULT_CACHE_PREFIX: &str = "result_cache";
+/// Searches kept in the result cache; the oldest is dropped first
+const RESULT_CACHE_ENTRIES: usize = 50;
+/// Files skipped by the last indexing run, kept next to the config for `last-errors`
+const SKIPS_FILE: &str = "skips.jsonl";
+const DEFAULT_MAX_RECORDED_SKIPS: usize = 1_000;
+const DEFAULT_SIDECAR_SUFFIX: &str = ".meta.json";
+const NEAR_FILE_MIN_TERM_CHARS: usize = 3;
+/// Language stored for files too short or mixed for whatlang to call reliably
+const UNKNOWN_LANGUAGE: &str = "unknown";
+/// Characters from the start of a file used to detect its language
+const LANGUAGE_SAMPLE_CHARS: usize = 4_096;
 const MAX_FILE_SIZE_BYTES: u64 = 5_000_000;
+const OCR_MAX_FILE_SIZE_BYTES: u64 = 20_000_000;
 const BINARY_SNIFF_BYTES: usize = 4_096;
+const PROJECT_CONFIG_FILE: &str = ".vaultsearch.toml";
 const METADATA_FILE: &str = "file_metadata.json";
+/// Written next to the metadata while a run is in progress; removed when it completes
+const UNFINISHED_RUN_FILE: &str = "unfinished_run.json";
+/// Under the data directory; holds one index per root set by a project config
+const PROJECT_INDEXES_DIR: &str = "projects";
+/// `last_indexed` and `index_generation` of a project index, kept inside it
+const PROJECT_RUN_STATE_FILE: &str = "run_state.json";
 const TEXT_LIKE_EXTENSIONS: &[&str] = &[
     "txt", "md", "rst", "log", "json", "toml", "yaml", "yml", "ini", "cfg", "rs", "lock", "c",
     "cpp", "h", "hpp", "cs", "java", "py", "go", "rb", "php", "js", "ts", "tsx", "jsx", "html",
     "htm", "css", "sh", "bash", "ps1", "bat", "tex", "csv",
 ];
+const DEFAULT_EXCLUDED_DIRS: &[&str] = &[
+    ".git",
+    ".hg",
+    ".svn",
+    "node_modules",
+    "target",
+    "venv",
+    ".venv",
+    "__pycache__",
+];
+const OCR_IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "tif", "tiff", "bmp", "gif", "webp"];
 
 // ---- Entry point ----
 
-fn main() -> Result<()> {
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {err:?}");
+            ExitCode::from(exit_code_for(&err))
+        }
+    }
+}
+
+fn run() -> Result<()> {
     let cli = Cli::parse();
+    if let Some(path) = cli.config {
+        CONFIG_FLAG
+            .set(path)
+            .expect("--config is recorded once, before any command runs");
+    }
+    let no_color = std::env::var_os("NO_COLOR");
+    let _ = USE_COLOR.set(use_color(
+        cli.color,
+        no_color.as_deref(),
+        io::stdout().is_terminal(),
+    ));
 
     match cli.command {
-        Command::Init { root, force } => {
-            cmd_init(&root, force)?;
+        Command::Init {
+            root,
+            force,
+            max_depth,
+            index_dir,
+            stop_words_file,
+            max_token_length,
+            print_only,
+        } => {
+            if print_only {
+                cmd_init_print_only(root.as_deref(), index_dir.as_deref())?;
+            } else {
+                let root = root.expect("clap requires --root without --print-only");
+                cmd_init(
+                    &root,
+                    force,
+                    max_depth,
+                    index_dir.as_deref(),
+                    stop_words_file.as_deref(),
+                    max_token_length.map(usize::from),
+                )?;
+            }
+        }
+        Command::Index(options) => {
+            cmd_index(options)?;
+        }
+        Command::Batch(args) => {
+            cmd_batch(&args)?;
+        }
+        Command::ExplainQuery {
+            query,
+            wildcard,
+            stem_language,
+            synonyms,
+        } => {
+            cmd_explain_query(&query, wildcard, stem_language, synonyms)?;
+        }
+        Command::ValidateQuery { query, verbose } => {
+            cmd_validate_query(&query, verbose)?;
+        }
+        Command::Search(mut args) => {
+            if args.print0 {
+                args.format = OutputFormat::Null;
+            }
+            cmd_search(&args)?;
+        }
+        Command::Next => {
+            cmd_page(PageStep::Next)?;
+        }
+        Command::Prev => {
+            cmd_page(PageStep::Prev)?;
+        }
+        Command::Compact => {
+            cmd_compact()?;
         }
-        Command::Index => {
-            cmd_index()?;
+        Command::Manifest { verify } => {
+            cmd_manifest(verify.as_deref())?;
         }
-        Command::Search { query } => {
-            cmd_search(&query)?;
+        Command::Cache {
+            action: CacheAction::Clear,
+        } => {
+            cmd_cache_clear()?;
+        }
+        Command::Forget { pattern, force } => {
+            cmd_forget(&pattern, force)?;
+        }
+        Command::Daemon => {
+            cmd_daemon()?;
+        }
+        Command::Serve { listen } => {
+            cmd_serve(&listen)?;
+        }
+        Command::Schema { format } => {
+            cmd_schema(format)?;
+        }
+        Command::Tags { by, limit } => {
+            cmd_tags(&by, limit)?;
+        }
+        Command::LastErrors { reason } => {
+            cmd_last_errors(reason)?;
         }
     }
 
     Ok(())
 }
 
+// ---- Errors ----
+
+/// Failure categories that map to distinct process exit codes for scripting.
+///
+/// Uncategorized errors exit with 1 and invalid command-line usage exits with 2.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FailureKind {
+    /// Config file is missing, unreadable, or invalid (exit code 3)
+    Config,
+    /// Index directory is missing (exit code 4)
+    IndexMissing,
+    /// Search query could not be parsed (exit code 5)
+    QueryParse,
+    /// `--check` found the index unreadable, or `manifest --verify` found differences (exit code 6)
+    IndexCorrupt,
+    /// Indexing was stopped with Ctrl-C after committing partial progress (exit code 130)
+    Interrupted,
+}
+
+impl FailureKind {
+    fn exit_code(self) -> u8 {
+        match self {
+            FailureKind::Config => 3,
+            FailureKind::IndexMissing => 4,
+            FailureKind::QueryParse => 5,
+            FailureKind::IndexCorrupt => 6,
+            FailureKind::Interrupted => 130,
+        }
+    }
+}
+
+/// Error message tagged with a `FailureKind`, attached via `anyhow` context.
+#[derive(Debug)]
+struct CategorizedError {
+    kind: FailureKind,
+    message: String,
+}
+
+impl std::fmt::Display for CategorizedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for CategorizedError {}
+
+fn categorized(kind: FailureKind, message: impl Into<String>) -> CategorizedError {
+    CategorizedError {
+        kind,
+        message: message.into(),
+    }
+}
+
+fn exit_code_for(err: &anyhow::Error) -> u8 {
+    // `downcast_ref` looks through every layer of `anyhow` context, unlike `chain()`.
+    err.downcast_ref::<CategorizedError>()
+        .map(|categorized| categorized.kind.exit_code())
+        .unwrap_or(1)
+}
+
 // ---- Commands ----
 
-fn cmd_init(root: &str, force: bool) -> Result<()> {
+fn cmd_init(
+    root: &str,
+    force: bool,
+    max_depth: Option<usize>,
+    index_dir_override: Option<&str>,
+    stop_words_file: Option<&Path>,
+    max_token_length: Option<usize>,
+) -> Result<()> {
     // 1) Check the root directory exists.
-    let root_path = fs::canonicalize(root)
+    let root = expand_root(root)?;
+    let root_path = fs::canonicalize(&root)
         .with_context(|| format!("Root path does not exist or is invalid: {root}"))?;
     if !root_path.is_dir() {
         anyhow::bail!("Root path is not a directory: {}", root_path.display());
@@ -108,7 +1375,36 @@ fn cmd_init(root: &str, force: bool) -> Result<()> {
     // 2) Work out where to put config and index.
     let proj_dirs = get_project_dirs()?;
     let config_path = config_file_path(&proj_dirs)?;
-    let index_dir = index_dir_path(&proj_dirs)?;
+    let index_dir = resolve_index_dir(&proj_dirs, index_dir_override)?;
+
+    // Tokenizer settings edited into an existing config carry over, so `init --force`
+    // applies them.
+    let existing = fs::read_to_string(&config_path)
+        .ok()
+        .and_then(|data| toml::from_str::<AppConfig>(&data).ok());
+    let stop_words = match stop_words_file {
+        Some(path) => {
+            let path = fs::canonicalize(path)
+                .with_context(|| format!("Stop-words file does not exist: {}", path.display()))?;
+            StopWords::File(path.to_string_lossy().to_string())
+        }
+        None => existing
+            .as_ref()
+            .map(|cfg| cfg.stop_words.clone())
+            .unwrap_or_default(),
+    };
+    let max_token_length = max_token_length
+        .or_else(|| existing.as_ref().and_then(|cfg| cfg.max_token_length))
+        .filter(|&length| length != DEFAULT_MAX_TOKEN_LENGTH);
+    let field_routing = existing
+        .as_ref()
+        .map(|cfg| cfg.field_routing.clone())
+        .unwrap_or_default();
+    let analysis = ContentsAnalysis {
+        stop_words: stop_words.resolve()?,
+        max_token_length: max_token_length.unwrap_or(DEFAULT_MAX_TOKEN_LENGTH),
+        code_extensions: field_routing.code_extensions(),
+    };
 
     // Ensure directories exist.
     if let Some(parent) = config_path.parent() {
@@ -117,6 +1413,13 @@ fn cmd_init(root: &str, force: bool) -> Result<()> {
     }
 
     let index_already_present = tantivy_index_exists(&index_dir);
+    // `--force` deletes the index directory, so never adopt a directory holding other files.
+    if index_dir_override.is_some() && !index_already_present && dir_has_entries(&index_dir) {
+        anyhow::bail!(
+            "--index-dir {} is not empty and does not contain a vaultsearch index. Choose an empty or new directory.",
+            index_dir.display()
+        );
+    }
 
     if index_already_present && force {
         println!(
@@ -133,10 +1436,11 @@ fn cmd_init(root: &str, force: bool) -> Result<()> {
 
     fs::create_dir_all(&index_dir)
         .with_context(|| format!("Failed to create index directory: {}", index_dir.display()))?;
+    ensure_dir_writable(&index_dir)?;
 
     // 3) Create or validate the Tantivy index (schema + empty index).
     let index_status = if index_already_present && !force {
-        let existing_index = open_index(&index_dir).with_context(|| {
+        let existing_index = open_index(&index_dir, &analysis).with_context(|| {
             format!(
                 "Failed to open existing index at {}. Re-run with --force to recreate it.",
                 index_dir.display()
@@ -144,7 +1448,7 @@ fn cmd_init(root: &str, force: bool) -> Result<()> {
         })?;
 
         let existing_schema = existing_index.schema();
-        let expected_schema = build_schema();
+        let expected_schema = build_schema(&analysis);
 
         if existing_schema != expected_schema {
             anyhow::bail!(
@@ -154,7 +1458,7 @@ fn cmd_init(root: &str, force: bool) -> Result<()> {
 
         "Reused existing Tantivy index."
     } else {
-        create_empty_index(&index_dir)?;
+        create_empty_index(&index_dir, &analysis)?;
         "Created new Tantivy index."
     };
 
@@ -163,6 +1467,52 @@ fn cmd_init(root: &str, force: bool) -> Result<()> {
         root: root_path.to_string_lossy().to_string(),
         index_dir: index_dir.to_string_lossy().to_string(),
         last_indexed: None,
+        // A reused index keeps its documents, so keep counting generations where it left off.
+        index_generation: if index_already_present && !force {
+            existing.as_ref().map_or(0, |cfg| cfg.index_generation)
+        } else {
+            0
+        },
+        max_depth,
+        recursive: None,
+        min_file_size: None,
+        progress_every: None,
+        default_excluded_dirs: default_excluded_dirs(),
+        sidecar_suffix: default_sidecar_suffix(),
+        max_recorded_skips: None,
+        synonyms: BTreeMap::new(),
+        search_fields: Vec::new(),
+        field_boosts: BTreeMap::new(),
+        default_relevance_profile: None,
+        relevance_profiles: BTreeMap::new(),
+        roots: existing
+            .as_ref()
+            .map(|cfg| cfg.roots.clone())
+            .unwrap_or_default(),
+        size_limits: SizeLimits::default(),
+        recency_boost: RecencyBoost::default(),
+        stop_words,
+        max_token_length,
+        field_routing,
+        store_relative_paths: existing
+            .as_ref()
+            .is_some_and(|cfg| cfg.store_relative_paths),
+        cache_results: existing.as_ref().is_some_and(|cfg| cfg.cache_results),
+        reader_reload: existing
+            .as_ref()
+            .map(|cfg| cfg.reader_reload)
+            .unwrap_or_default(),
+        merge_policy: existing
+            .as_ref()
+            .map(|cfg| cfg.merge_policy)
+            .unwrap_or_default(),
+        merge_min_segments: existing.as_ref().and_then(|cfg| cfg.merge_min_segments),
+        analysis,
+        raw_root: None,
+        project_config: None,
+        root_name: None,
+        project_index: false,
+        loaded_from: None,
     };
 
     write_config(&cfg, &config_path)?;
@@ -172,542 +1522,8346 @@ fn cmd_init(root: &str, force: bool) -> Result<()> {
     println!("  Index directory: {}", cfg.index_dir);
     println!("  Index status   : {index_status}");
     println!("  Config file    : {}", config_path.display());
+    if let Some(depth) = cfg.max_depth {
+        println!("  Max depth      : {depth}");
+    }
+    match &cfg.stop_words {
+        StopWords::File(path) => println!(
+            "  Stop words     : {} from {path}",
+            cfg.analysis.stop_words.len()
+        ),
+        StopWords::List(_) if !cfg.analysis.stop_words.is_empty() => {
+            println!("  Stop words     : {}", cfg.analysis.stop_words.len())
+        }
+        StopWords::List(_) => {}
+    }
+    if let Some(length) = cfg.max_token_length {
+        println!("  Max token size : {length} bytes");
+    }
 
     println!("\nStarting initial indexing run...");
-    perform_indexing(&mut cfg)?;
+    perform_indexing(&mut cfg, &IndexOptions::default())?;
 
     Ok(())
 }
 
-fn cmd_index() -> Result<()> {
-    let mut cfg = load_config()?;
-    perform_indexing(&mut cfg)
-}
-
-fn cmd_search(query: &str) -> Result<()> {
-    let cfg = load_config()?;
-    let index_dir = Path::new(&cfg.index_dir);
+/// Shows the paths `init` would use on this platform without touching the filesystem.
+fn cmd_init_print_only(root: Option<&str>, index_dir_override: Option<&str>) -> Result<()> {
+    let proj_dirs = get_project_dirs()?;
+    let (config_path, source) = resolve_config_path(&proj_dirs);
+    let index_dir = resolve_index_dir(&proj_dirs, index_dir_override)?;
 
-    if cfg.last_indexed.is_none() {
-        println!(
-            "Index has not been built yet for {}. Run `vaultsearch index` to scan your files.",
-            cfg.root
-        );
-        return Ok(());
+    println!("vaultsearch would use:");
+    if let Some(root) = root {
+        let root = expand_root(root)?;
+        let root_path = fs::canonicalize(&root)
+            .with_context(|| format!("Root path does not exist or is invalid: {root}"))?;
+        println!("  Root directory : {}", root_path.display());
     }
-
-    if !tantivy_index_exists(index_dir) {
-        println!(
-            "Index directory missing at {}. Re-run `vaultsearch init` followed by `vaultsearch index`.",
-            index_dir.display()
-        );
-        return Ok(());
+    let origin = match source {
+        ConfigSource::Default => String::new(),
+        _ => format!(", {}", source.describe()),
+    };
+    println!(
+        "  Config file    : {} ({}{origin})",
+        config_path.display(),
+        existence_label(config_path.is_file())
+    );
+    println!(
+        "  Index directory: {} ({})",
+        index_dir.display(),
+        existence_label(tantivy_index_exists(&index_dir))
+    );
+    println!("  Config dir     : {}", proj_dirs.config_dir().display());
+    println!(
+        "  Data dir       : {}",
+        proj_dirs.data_local_dir().display()
+    );
+    println!(
+        "  Daemon socket  : {}",
+        daemon_socket_path(&index_dir)?.display()
+    );
+    println!("Nothing was created (--print-only).");
+
+    Ok(())
+}
+
+fn existence_label(exists: bool) -> &'static str {
+    if exists {
+        "exists"
+    } else {
+        "not created yet"
     }
+}
 
-    let index = open_index(index_dir)?;
-    let schema = index.schema();
+fn cmd_index(options: IndexOptions) -> Result<()> {
+    if let Some(pct) = options.fail_if_skip_ratio {
+        if !(0.0..=100.0).contains(&pct) {
+            anyhow::bail!("--fail-if-skip-ratio must be between 0 and 100, got {pct}");
+        }
+    }
+    let mut cfg = load_config()?;
+    if let Some(name) = &options.root_name {
+        cfg = select_root(cfg, name)?;
+    }
+    if options.show_config_source {
+        print_config_source(&cfg);
+    }
+    if let Some(path) = &options.explain_file {
+        return explain_file(&cfg, &options, path);
+    }
+    if !options.all_roots {
+        ensure_own_index(&cfg)?;
+        return perform_indexing(&mut cfg, &options);
+    }
 
-    let path_field = schema.get_field("path").expect("path field");
-    let contents_field = schema.get_field("contents").expect("contents field");
+    warn_overlapping_roots(&cfg);
+    let names: Vec<String> = cfg.roots.keys().cloned().collect();
+    perform_indexing(&mut cfg, &options)?;
+    for name in names {
+        println!();
+        println!("Root `{name}`:");
+        // Reloaded so each run sees the run state the previous one saved.
+        let mut named = select_root(load_config()?, &name)?;
+        ensure_own_index(&named)?;
+        perform_indexing(&mut named, &options)?;
+    }
+    Ok(())
+}
 
-    let reader = index.reader().context("Failed to create index reader")?;
-    let searcher = reader.searcher();
+/// Narrows `cfg` to the `[roots]` entry `name`: its root, index directory, and run state,
+/// with every other setting shared.
+fn select_root(cfg: AppConfig, name: &str) -> Result<AppConfig> {
+    let Some(named) = cfg.roots.get(name).cloned() else {
+        let names: Vec<&str> = cfg.roots.keys().map(String::as_str).collect();
+        let known = if names.is_empty() {
+            "the config has no [roots] table".to_string()
+        } else {
+            format!("configured roots: {}", names.join(", "))
+        };
+        return Err(categorized(
+            FailureKind::Config,
+            format!("Unknown root `{name}` ({known})"),
+        )
+        .into());
+    };
+    let root = expand_root(&named.root).map_err(|e| {
+        e.context(categorized(
+            FailureKind::Config,
+            format!("Invalid root for `{name}` in config"),
+        ))
+    })?;
+    Ok(AppConfig {
+        root,
+        index_dir: named.index_dir,
+        last_indexed: named.last_indexed,
+        index_generation: named.index_generation,
+        raw_root: None,
+        root_name: Some(name.to_string()),
+        ..cfg
+    })
+}
 
-    if searcher.num_docs() == 0 {
-        println!(
-            "Index is empty. Run `vaultsearch index` to index files under {}.",
-            cfg.root
-        );
-        return Ok(());
+/// Warns about configured roots that contain one another: their shared files are indexed
+/// once per root, and `--all-roots` keeps only the best-scoring copy of each.
+fn warn_overlapping_roots(cfg: &AppConfig) {
+    let mut roots = vec![(MAIN_ROOT_LABEL.to_string(), cfg.root.clone())];
+    for (name, named) in &cfg.roots {
+        if let Ok(root) = expand_root(&named.root) {
+            roots.push((name.clone(), root));
+        }
+    }
+    let roots: Vec<(String, PathBuf)> = roots
+        .into_iter()
+        .map(|(name, root)| {
+            let path = fs::canonicalize(&root).unwrap_or_else(|_| PathBuf::from(root));
+            (name, path)
+        })
+        .collect();
+    for (i, (outer_name, outer)) in roots.iter().enumerate() {
+        for (j, (inner_name, inner)) in roots.iter().enumerate() {
+            // Identical roots contain each other; report the pair once.
+            if i == j || !inner.starts_with(outer) || (inner == outer && j < i) {
+                continue;
+            }
+            eprintln!(
+                "  [warn] Root `{inner_name}` ({}) is inside root `{outer_name}` ({}); its files are indexed twice",
+                inner.display(),
+                outer.display()
+            );
+        }
     }
+}
 
-    let query_parser = QueryParser::for_index(&index, vec![path_field, contents_field]);
+/// Creates the index of a `[roots]` entry, or of a root set by a project config, on its
+/// first `index` run; `init` only creates the top-level one.
+fn ensure_own_index(cfg: &AppConfig) -> Result<()> {
+    let label = match &cfg.root_name {
+        Some(name) => format!("root `{name}`"),
+        None if cfg.project_index => format!("project root {}", cfg.root),
+        None => return Ok(()),
+    };
+    let index_dir = Path::new(&cfg.index_dir);
+    if tantivy_index_exists(index_dir) {
+        return Ok(());
+    }
+    fs::create_dir_all(index_dir)
+        .with_context(|| format!("Failed to create index directory {}", index_dir.display()))?;
+    create_empty_index(index_dir, &cfg.analysis)?;
+    println!("Created index for {label} at {}", index_dir.display());
+    Ok(())
+}
 
-    let tantivy_query = query_parser
-        .parse_query(query)
-        .with_context(|| format!("Failed to parse query: {query}"))?;
+/// Deepest directory level below the root that indexing descends into, if limited.
+fn index_max_depth(cfg: &AppConfig, options: &IndexOptions) -> Option<usize> {
+    // `--no-recursive` and `recursive = false` are shorthands for depth 0.
+    let flat =
+        options.no_recursive || (options.max_depth.is_none() && cfg.recursive == Some(false));
+    if flat {
+        Some(0)
+    } else {
+        options.max_depth.or(cfg.max_depth)
+    }
+}
 
-    let mut snippet_generator = SnippetGenerator::create(&searcher, &tantivy_query, contents_field)
-        .context("Failed to create snippet generator")?;
-    snippet_generator.set_max_num_chars(200);
+/// Runs the checks `perform_indexing` applies to each file against `path` alone and prints
+/// a pass/fail line for each, followed by the verdict. All checks run even after a failure.
+fn explain_file(cfg: &AppConfig, options: &IndexOptions, path: &Path) -> Result<()> {
+    let path =
+        fs::canonicalize(path).with_context(|| format!("File not found: {}", path.display()))?;
+    let root = fs::canonicalize(&cfg.root)
+        .with_context(|| format!("Root directory not found: {}", cfg.root))?;
+    let mut failed: Vec<&str> = Vec::new();
+    let mut check = |passed: bool, name: &'static str, detail: String| {
+        println!(
+            "  [{}] {name:<14}: {detail}",
+            if passed { "pass" } else { "fail" }
+        );
+        if !passed {
+            failed.push(name);
+        }
+    };
 
-    let top_docs = searcher
-        .search(&tantivy_query, &TopDocs::with_limit(TOP_RESULTS))
-        .context("Search failed")?;
+    println!("Checking {}", path.display());
+    let metadata = fs::metadata(&path)
+        .with_context(|| format!("Failed to read metadata for {}", path.display()))?;
+    check(
+        metadata.is_file(),
+        "regular file",
+        if metadata.is_file() {
+            "yes".to_string()
+        } else {
+            "not a regular file".to_string()
+        },
+    );
 
-    if top_docs.is_empty() {
-        println!("No results found for query: {query}");
-        return Ok(());
+    match path.strip_prefix(&root) {
+        Ok(relative) => {
+            check(true, "under root", relative.display().to_string());
+            let excluded_dirs: HashSet<&str> = if options.no_default_excludes {
+                HashSet::new()
+            } else {
+                cfg.default_excluded_dirs
+                    .iter()
+                    .map(String::as_str)
+                    .collect()
+            };
+            let not_excluded = tracked_file_included(relative, None, &excluded_dirs);
+            check(
+                not_excluded,
+                "excluded dirs",
+                if not_excluded {
+                    "no parent directory is in default_excluded_dirs".to_string()
+                } else {
+                    "a parent directory is in default_excluded_dirs (--no-default-excludes)"
+                        .to_string()
+                },
+            );
+            let max_depth = index_max_depth(cfg, options);
+            let depth = relative.components().count().saturating_sub(1);
+            check(
+                tracked_file_included(relative, max_depth, &HashSet::new()),
+                "max depth",
+                match max_depth {
+                    Some(limit) => format!("{depth} director(ies) deep, limit {limit}"),
+                    None => format!("{depth} director(ies) deep, no limit"),
+                },
+            );
+            let ignore_rules = IgnoreRules::load(&options.ignore_files)?;
+            let ignored = ignore_rules.is_ignored_with_parents(relative);
+            check(
+                !ignored,
+                "ignore files",
+                if ignored {
+                    "matched by an --ignore-file pattern".to_string()
+                } else {
+                    format!("no match among {} pattern(s)", ignore_rules.len())
+                },
+            );
+            if options.git_tracked_only {
+                let tracked = git_tracked_files(&root)?
+                    .iter()
+                    .any(|file| file == relative);
+                check(
+                    tracked,
+                    "git tracked",
+                    if tracked {
+                        "tracked by git".to_string()
+                    } else {
+                        "not tracked by git (--git-tracked-only)".to_string()
+                    },
+                );
+            }
+        }
+        Err(_) => check(false, "under root", format!("outside {}", root.display())),
     }
 
-    println!("Results for query: {query}");
-    for (rank, (score, doc_address)) in top_docs.into_iter().enumerate() {
-        let retrieved_doc: TantivyDocument = searcher
-            .doc(doc_address)
-            .context("Failed to load document")?;
-
-        let path_value = retrieved_doc
-            .get_first(path_field)
-            .and_then(|v| v.as_str())
-            .unwrap_or("<unknown path>");
+    let ocr_candidate = options.ocr && is_ocr_image(&path);
+    let sidecar = find_sidecar(&path, &cfg.sidecar_suffix);
+    let extension = path
+        .extension()
+        .map(|ext| format!("`.{}`", ext.to_string_lossy()))
+        .unwrap_or_else(|| "(no extension)".to_string());
+    let (supported, detail) = if ocr_candidate {
+        (true, format!("{extension} is an image read with --ocr"))
+    } else if is_text_like(&path) {
+        (true, format!("{extension} is a supported text extension"))
+    } else if let Some(sidecar) = &sidecar {
+        let sidecar = sidecar.path.display();
+        (
+            true,
+            format!("{extension} is unsupported; indexed through sidecar {sidecar}"),
+        )
+    } else if options.index_binary_paths {
+        (
+            true,
+            format!("{extension} is unsupported; indexed by path (--index-binary-paths)"),
+        )
+    } else {
+        (
+            false,
+            format!("{extension}: not a supported text extension"),
+        )
+    };
+    check(supported, "extension", detail);
 
-        let snippet_html = snippet_generator.snippet_from_doc(&retrieved_doc).to_html();
-        let snippet = highlight_snippet(&snippet_html);
-        let relative_path = Path::new(path_value)
-            .strip_prefix(&cfg.root)
-            .map(|p| p.to_string_lossy().to_string())
-            .unwrap_or_else(|_| path_value.to_string());
+    let size = metadata.len();
+    let min_file_size = options.min_file_size.or(cfg.min_file_size).unwrap_or(0);
+    check(
+        size >= min_file_size,
+        "min size",
+        format!("{size} bytes, minimum {min_file_size}"),
+    );
+    let size_limit = if ocr_candidate {
+        OCR_MAX_FILE_SIZE_BYTES
+    } else {
+        cfg.size_limits.limit_for(&path)
+    };
+    check(
+        size <= size_limit || sidecar.is_some(),
+        "max size",
+        format!("{size} bytes, limit {size_limit}"),
+    );
 
-        println!("{:>2}. [score: {:.3}] {}", rank + 1, score, relative_path);
-        println!("      {snippet}");
-        println!();
+    if !ocr_candidate {
+        match is_probably_binary(&path) {
+            Ok(binary) => check(
+                !binary || sidecar.is_some() || options.index_binary_paths,
+                "binary sniff",
+                if binary {
+                    "content looks binary".to_string()
+                } else {
+                    "content looks like text".to_string()
+                },
+            ),
+            Err(e) => check(false, "binary sniff", format!("failed to read: {e}")),
+        }
     }
 
+    if failed.is_empty() {
+        println!("Result: would be indexed.");
+    } else {
+        println!("Result: would be skipped ({}).", failed.join(", "));
+    }
     Ok(())
 }
 
-fn highlight_snippet(snippet_html: &str) -> String {
-    let decoded = decode_html_entities(snippet_html);
-    let with_bold = decoded.replace("<b>", "\x1b[1m").replace("</b>", "\x1b[0m");
-    with_bold
-}
-
-fn perform_indexing(cfg: &mut AppConfig) -> Result<()> {
-    let root = Path::new(&cfg.root);
-    let index_dir = Path::new(&cfg.index_dir);
+fn cmd_search(args: &SearchArgs) -> Result<()> {
+    let query = match (&args.near_file, &args.any_of) {
+        (Some(reference), _) => format!("files like {}", reference.display()),
+        (None, Some(keywords)) => {
+            let any_of = any_of_query(keywords)?;
+            if args.query.is_some() || args.query_file.is_some() {
+                let query = resolve_query(args.query.as_deref(), args.query_file.as_deref())?;
+                format!("({query}) AND {any_of}")
+            } else {
+                any_of
+            }
+        }
+        (None, None) => resolve_query(args.query.as_deref(), args.query_file.as_deref())?,
+    };
+    let shown = run_search(args, &query)?;
 
-    if !tantivy_index_exists(index_dir) {
-        anyhow::bail!(
-            "Index missing at {}. Re-run `vaultsearch init` to recreate it.",
-            index_dir.display()
-        );
+    // Save the query text itself so paging does not re-read stdin or a changed query file.
+    let mut last = args.clone();
+    last.last_page = shown < args.limit;
+    last.config_file = Some(absolute_config_path()?);
+    if last.near_file.is_none() {
+        last.query = Some(query);
+        last.query_file = None;
+        last.any_of = None;
     }
+    save_last_search(&last)
+}
 
-    println!("Indexing...");
-    println!("  Root directory : {}", root.display());
-    println!("  Index directory: {}", index_dir.display());
+/// Actions of the `cache` command.
+#[derive(Subcommand, Debug)]
+enum CacheAction {
+    /// Delete every cached search result
+    Clear,
+}
 
-    let index = open_index(index_dir)?;
-    let schema = index.schema();
+/// Which way `next` and `prev` move through the last search's results.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PageStep {
+    Next,
+    Prev,
+}
 
-    let path_field = schema.get_field("path").expect("path field");
-    let path_exact_field = schema.get_field("path_exact").expect("path_exact field");
-    let contents_field = schema.get_field("contents").expect("contents field");
+/// Re-runs the last search one page (`--limit` results) further on or back.
+fn cmd_page(step: PageStep) -> Result<()> {
+    let mut args = load_last_search()?;
+    if let Some(saved) = &args.config_file {
+        let (_, source) = resolve_config_path(&get_project_dirs()?);
+        let current = absolute_config_path()?;
+        if source != ConfigSource::Default && current != *saved {
+            return Err(categorized(
+                FailureKind::Config,
+                format!(
+                    "The last search used config {}, not {} ({}). Page without choosing a config, or run a new search.",
+                    saved.display(),
+                    current.display(),
+                    source.describe()
+                ),
+            )
+            .into());
+        }
+        if current != *saved {
+            // Only the default location was in effect, so `--config` was never recorded.
+            let _ = CONFIG_FLAG.set(saved.clone());
+        }
+    }
+    args.offset = match step {
+        // Past a short page there is nothing left, and the result window ends at
+        // `MAX_RESULT_WINDOW`.
+        PageStep::Next
+            if args.last_page
+                || args.offset.saturating_add(args.limit.saturating_mul(2)) > MAX_RESULT_WINDOW =>
+        {
+            eprintln!("Already on the last page.");
+            args.offset
+        }
+        PageStep::Next => args.offset.saturating_add(args.limit),
+        PageStep::Prev if args.offset == 0 => {
+            eprintln!("Already on the first page.");
+            0
+        }
+        PageStep::Prev => args.offset.saturating_sub(args.limit),
+    };
+    cmd_search(&args)
+}
 
-    // Tantivy index writer: 50 MB heap
-    let mut writer = index
-        .writer(INDEX_WRITER_HEAP_BYTES)
-        .context("Failed to create Tantivy index writer")?;
+fn last_search_path() -> Result<PathBuf> {
+    let proj_dirs = get_project_dirs()?;
+    Ok(proj_dirs.config_dir().join(LAST_SEARCH_FILE))
+}
 
-    let previous_metadata = load_file_metadata(index_dir).unwrap_or_else(|e| {
-        eprintln!(
-            "  [warn] Failed to load previous metadata ({}). Starting fresh.",
-            e
-        );
-        HashMap::new()
-    });
+fn skips_path() -> Result<PathBuf> {
+    let proj_dirs = get_project_dirs()?;
+    Ok(proj_dirs.config_dir().join(SKIPS_FILE))
+}
 
-    let mut new_metadata: HashMap<String, FileMetadata> = HashMap::new();
-    let mut skip_stats = SkipStats::default();
-    let mut stats = IndexingStats::default();
+/// Replaces `skips.jsonl` with this run's skipped files, one JSON object per line.
+fn save_skipped_files(skipped: &[SkippedFile]) -> Result<PathBuf> {
+    let path = skips_path()?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create config directory {}", parent.display()))?;
+    }
+    let mut lines = String::new();
+    for file in skipped {
+        lines.push_str(&serde_json::to_string(file).context("Failed to serialize skipped file")?);
+        lines.push('\n');
+    }
+    write_atomically(&path, lines.as_bytes())
+        .with_context(|| format!("Failed to write skip log {}", path.display()))?;
+    Ok(path)
+}
 
-    for entry in walkdir::WalkDir::new(root)
-        .into_iter()
-        .filter_map(|e| e.ok())
-    {
-        let path = entry.path();
+fn save_last_search(args: &SearchArgs) -> Result<()> {
+    let path = last_search_path()?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create config directory {}", parent.display()))?;
+    }
+    let json = serde_json::to_string_pretty(args).context("Failed to serialize last search")?;
+    fs::write(&path, json)
+        .with_context(|| format!("Failed to save last search to {}", path.display()))
+}
 
-        if !path.is_file() {
-            continue;
+fn load_last_search() -> Result<SearchArgs> {
+    let path = last_search_path()?;
+    let json = match fs::read_to_string(&path) {
+        Ok(json) => json,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            anyhow::bail!("No previous search to page through. Run `vaultsearch search` first.")
         }
-
-        let path_display = path.display();
-
-        if !is_text_like(path) {
-            eprintln!("  [skip] Unsupported extension: {path_display}");
-            skip_stats.unsupported_extension += 1;
-            continue;
+        Err(err) => {
+            return Err(err)
+                .with_context(|| format!("Failed to read last search from {}", path.display()))
         }
+    };
+    serde_json::from_str(&json).with_context(|| {
+        format!(
+            "Last search in {} is unreadable; run `vaultsearch search` again",
+            path.display()
+        )
+    })
+}
 
-        let metadata = match fs::metadata(path) {
-            Ok(meta) => meta,
-            Err(e) => {
-                eprintln!("  [skip] Failed to read metadata for {path_display}: {e}");
-                skip_stats.read_errors += 1;
-                continue;
-            }
-        };
+/// Searches with `query` (already resolved from the arguments), prints the results, and
+/// returns how many were printed.
+fn run_search(args: &SearchArgs, query: &str) -> Result<usize> {
+    if args.limit == 0 {
+        anyhow::bail!("--limit must be at least 1");
+    }
+    if args.limit > MAX_RESULT_WINDOW {
+        anyhow::bail!("--limit must be at most {MAX_RESULT_WINDOW}");
+    }
+    if args.offset.saturating_add(args.limit) > MAX_RESULT_WINDOW {
+        anyhow::bail!("--offset plus --limit must be at most {MAX_RESULT_WINDOW}");
+    }
+    if args.score_normalize && args.offset > 0 && args.sort != SortOrder::Score {
+        anyhow::bail!("--score-normalize with --offset can only be used with --sort score");
+    }
+    if args.limit_per_dir == Some(0) {
+        anyhow::bail!("--limit-per-dir must be at least 1");
+    }
+    if args.interactive_open && args.format != OutputFormat::Text {
+        anyhow::bail!("--interactive-open can only be used with --format text");
+    }
+    if args.highlight_only && args.format != OutputFormat::Text {
+        anyhow::bail!("--highlight-only can only be used with --format text");
+    }
+    if args.fields_json
+        && matches!(
+            args.format,
+            OutputFormat::Csv | OutputFormat::Null | OutputFormat::Table
+        )
+    {
+        anyhow::bail!("--fields-json cannot be used with --format csv, null, or table");
+    }
 
-        if metadata.len() > MAX_FILE_SIZE_BYTES {
+    let filtered;
+    let query = if args.min_term_length > 1 && args.near_file.is_none() {
+        let (kept, dropped) = drop_short_terms(query, args.min_term_length);
+        if !dropped.is_empty() {
             eprintln!(
-                "  [skip] File exceeds size limit ({} bytes): {path_display}",
-                metadata.len()
+                "  [warn] Dropped query term(s) shorter than {} characters: {}",
+                args.min_term_length,
+                dropped.join(", ")
             );
-            skip_stats.too_large += 1;
-            continue;
         }
-
-        match is_probably_binary(path) {
-            Ok(true) => {
-                eprintln!("  [skip] Detected binary content: {path_display}");
-                skip_stats.binary += 1;
-                continue;
-            }
-            Ok(false) => {}
-            Err(e) => {
-                eprintln!("  [skip] Failed to sniff {path_display}: {e}");
-                skip_stats.read_errors += 1;
-                continue;
-            }
+        if kept.trim().is_empty() {
+            return Err(categorized(
+                FailureKind::QueryParse,
+                format!(
+                    "Query too short: every term is shorter than --min-term-length {}",
+                    args.min_term_length
+                ),
+            )
+            .into());
         }
+        filtered = kept;
+        filtered.as_str()
+    } else {
+        query
+    };
 
-        let path_str = path.to_string_lossy().to_string();
-        let modified = match file_modified_timestamp(&metadata) {
-            Ok(ts) => ts,
-            Err(e) => {
-                eprintln!("  [skip] Failed to read modified time for {path_display}: {e}");
-                skip_stats.read_errors += 1;
-                continue;
-            }
-        };
+    let mut request = SearchRequest {
+        query: query.to_string(),
+        limit: args.limit,
+        offset: args.offset,
+        wildcard: args.wildcard,
+        stem_language: args.stem_language,
+        synonyms: args.synonyms,
+        whole_word: args.whole_word,
+        search_fields: if args.comments {
+            vec!["comments".to_string()]
+        } else {
+            args.search_fields.clone()
+        },
+        field_boosts: args.field_boosts.iter().cloned().collect(),
+        relevance: args.relevance.clone(),
+        sort: args.sort,
+        recency_boost: args.recency_boost,
+        recency_half_life: args.recency_half_life,
+        owner: args.owner,
+        mode: args.mode.clone(),
+        exclude_path: args.exclude_path.clone(),
+        limit_per_dir: args.limit_per_dir,
+        language: args.language.clone(),
+        min_lines: args.min_lines,
+        max_lines: args.max_lines,
+        modified_within: args.modified_within,
+        since: args.since,
+        as_of_generation: args.as_of_generation,
+        // Matching lines are read from the files, so Tantivy snippets would go unused.
+        no_snippet: args.no_snippet || args.highlight_only,
+        fields_json: args.fields_json,
+        file_details: args.format == OutputFormat::Table,
+        match_spans: args.format == OutputFormat::Json,
+        near_file: args.near_file.clone(),
+    };
+    if args.score_normalize && args.offset > 0 {
+        // Scores are normalized against rank 1, so fetch the earlier pages as well;
+        // `present_results` drops them again.
+        request.limit += request.offset;
+        request.offset = 0;
+    }
 
-        if let Some(previous) = previous_metadata.get(&path_str) {
-            if previous.modified == modified && previous.size == metadata.len() {
-                stats.unchanged += 1;
-                new_metadata.insert(path_str.clone(), previous.clone());
-                continue;
-            }
+    if let Some(addr) = &args.server {
+        if args.near_file.is_some()
+            || args.interactive_open
+            || args.preview_lines.is_some()
+            || args.highlight_only
+            || args.relative_to.is_some()
+            || args.cwd_relative
+            || args.line_numbers
+        {
+            anyhow::bail!(
+                "--server cannot be combined with --near-file, --interactive-open, --preview-lines, \
+                 --highlight-only, --relative-to, --cwd-relative, or --line-numbers, which need the \
+                 files locally"
+            );
         }
+        let results = remote_search(addr, &request)?;
+        return present_results(args, query, results);
+    }
 
-        match read_file_streaming(path, metadata.len()) {
-            Ok(file_data) => {
-                let doc = doc!(
-                    path_field => path_str.clone(),
-                    path_exact_field => path_str.clone(),
-                    contents_field => file_data.contents,
-                );
+    if !args.indexes.is_empty() {
+        let results = federated_search(&args.indexes, &request)?;
+        return present_results(args, query, results);
+    }
 
-                if previous_metadata.contains_key(&path_str) {
-                    writer.delete_term(Term::from_field_text(path_exact_field, &path_str));
-                    stats.updated += 1;
-                } else {
-                    stats.indexed += 1;
-                }
+    if args.all_roots {
+        let results = all_roots_search(&request)?;
+        return present_results(args, query, results);
+    }
 
-                writer
-                    .add_document(doc)
-                    .with_context(|| format!("Failed to add document for {}", path.display()))?;
-
-                new_metadata.insert(
-                    path_str.clone(),
-                    FileMetadata {
-                        path: path_str,
-                        modified,
-                        size: metadata.len(),
-                        hash: file_data.hash,
-                    },
+    let mut cfg = load_config()?;
+    if let Some(name) = &args.root_name {
+        cfg = select_root(cfg, name)?;
+    }
+    if args.show_config_source {
+        print_config_source(&cfg);
+    }
+    let index_dir = Path::new(&cfg.index_dir);
+
+    if cfg.last_indexed.is_none() {
+        return finish_without_results(
+            args,
+            &format!(
+                "Index has not been built yet for {}. Run `vaultsearch index{}` to scan your files.",
+                cfg.root,
+                cfg.root_name
+                    .as_ref()
+                    .map(|name| format!(" --root-name {name}"))
+                    .unwrap_or_default()
+            ),
+        )
+        .map(|()| 0);
+    }
+
+    if !tantivy_index_exists(index_dir) {
+        return Err(categorized(
+            FailureKind::IndexMissing,
+            format!(
+                "Index directory missing at {}. Re-run `vaultsearch init` followed by `vaultsearch index`.",
+                index_dir.display()
+            ),
+        )
+        .into());
+    }
+
+    if args.check {
+        let check = check_index_integrity(index_dir, &cfg.analysis)?;
+        eprintln!("Index check: {check}");
+    }
+
+    let relative_base = match (&args.relative_to, args.cwd_relative) {
+        (Some(dir), _) => fs::canonicalize(dir)
+            .with_context(|| format!("--relative-to directory not found: {}", dir.display()))?,
+        (None, true) => std::env::current_dir()
+            .and_then(fs::canonicalize)
+            .context("Failed to determine current directory")?,
+        (None, false) => PathBuf::from(&cfg.root),
+    };
+
+    // `--near-file` and `--since` results depend on files on disk, which may change between runs;
+    // `--modified-within` and `--recency-boost` depend on the current time.
+    let cache_key = (cfg.cache_results
+        && !args.no_cache
+        && request.near_file.is_none()
+        && request.since.is_none()
+        && request.modified_within.is_none()
+        && !request.recency_boost)
+        .then(|| result_cache_key(&cfg, &request, &relative_base))
+        .transpose()?;
+    if let Some(key) = &cache_key {
+        if let Some(results) = cached_results(&cfg, key) {
+            if args.verbose {
+                eprintln!(
+                    "Results served from the result cache ({}).",
+                    result_cache_path(&cfg)?.display()
                 );
+            }
+            return present_results(args, query, results);
+        }
+    }
 
-                if (stats.indexed + stats.updated) % INDEX_PROGRESS_CHUNK == 0 {
-                    println!(
-                        "  Indexed/updated {} files so far...",
-                        stats.indexed + stats.updated
-                    );
-                }
+    // The daemon cannot read `--near-file` references, so those always search directly.
+    if args.daemon && request.near_file.is_none() {
+        if let Some(mut results) = daemon_search(Path::new(&cfg.index_dir), &request)? {
+            for hit in &mut results {
+                hit.relative_path = relative_display_path(&hit.path, &relative_base);
             }
-            Err(e) => {
-                eprintln!("  [skip] Failed to read {}: {e}", path_display);
-                skip_stats.read_errors += 1;
+            if let Some(key) = cache_key {
+                cache_results(&cfg, key, &results);
             }
+            return present_results(args, query, results);
         }
     }
 
-    for (path, _) in previous_metadata
-        .iter()
-        .filter(|(p, _)| !new_metadata.contains_key(*p))
-    {
-        writer.delete_term(Term::from_field_text(path_exact_field, path));
-        stats.removed += 1;
+    // Bound first so the snapshot directory is removed only after the reader is dropped.
+    let (_snapshot, index, reader) = if args.snapshot {
+        let (snapshot, index, reader) = snapshot_index(index_dir, &cfg.analysis)?;
+        (Some(snapshot), index, reader)
+    } else {
+        let (index, reader) = open_index_reader(index_dir, &cfg.analysis, cfg.reader_reload)?;
+        (None, index, reader)
+    };
+    let searcher = reader.searcher();
+
+    if searcher.num_docs() == 0 {
+        return finish_without_results(
+            args,
+            &format!(
+                "Index is empty. Run `vaultsearch index` to index files under {}.",
+                cfg.root
+            ),
+        )
+        .map(|()| 0);
     }
 
-    writer.commit().context("Failed to commit index to disk")?;
+    let results = execute_search(&index, &searcher, &cfg, &request, &relative_base)?;
+    if let Some(key) = cache_key {
+        cache_results(&cfg, key, &results);
+    }
+    present_results(args, query, results)
+}
 
-    save_file_metadata(index_dir, &new_metadata)
-        .context("Failed to persist file metadata alongside index")?;
+/// Recent search results, valid only for the index state recorded in `stamp`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ResultCache {
+    /// `index_generation` and `last_indexed` the results were computed against
+    stamp: String,
+    /// Oldest first
+    entries: Vec<CachedSearch>,
+}
 
-    cfg.last_indexed = Some(Utc::now().to_rfc3339());
-    save_config(cfg)?;
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedSearch {
+    key: String,
+    results: Vec<SearchHit>,
+    /// Highlighted snippets, which `SearchHit` serializes as plain text
+    snippets_html: Vec<String>,
+}
 
-    println!("Indexing complete.");
-    println!("  Added files    : {}", stats.indexed);
-    println!("  Updated files  : {}", stats.updated);
-    println!("  Unchanged files: {}", stats.unchanged);
-    println!("  Removed files  : {}", stats.removed);
-    println!("  Skipped files  : {}", skip_stats.total());
-    println!(
-        "    - Unsupported extension : {}",
-        skip_stats.unsupported_extension
+/// The cache file of `cfg`'s index, so configs, named roots, and project indexes do not
+/// invalidate each other's entries.
+fn result_cache_path(cfg: &AppConfig) -> Result<PathBuf> {
+    let proj_dirs = get_project_dirs()?;
+    let hash = blake3::hash(cfg.index_dir.as_bytes());
+    Ok(proj_dirs.config_dir().join(format!(
+        "{RESULT_CACHE_PREFIX}-{}.json",
+        &hash.to_hex()[..16]
+    )))
+}
+
+/// Index state the cache is valid for; every `index` run changes it.
+fn result_cache_stamp(cfg: &AppConfig) -> String {
+    format!(
+        "{}:{}",
+        cfg.index_generation,
+        cfg.last_indexed.as_deref().unwrap_or_default()
+    )
+}
+
+/// Hash of everything that shapes the results: the request, the config it runs under
+/// (boosts, synonyms, profiles), and the base directory of relative paths.
+fn result_cache_key(
+    cfg: &AppConfig,
+    request: &SearchRequest,
+    relative_base: &Path,
+) -> Result<String> {
+    let mut hasher = Hasher::new();
+    hasher.update(
+        serde_json::to_string(request)
+            .context("Failed to serialize search request")?
+            .as_bytes(),
     );
-    println!("    - Too large             : {}", skip_stats.too_large);
-    println!("    - Binary content        : {}", skip_stats.binary);
-    println!("    - Read errors           : {}", skip_stats.read_errors);
-    println!(
-        "  Last indexed  : {}",
-        cfg.last_indexed.as_deref().unwrap_or("unknown")
+    hasher.update(
+        serde_json::to_string(cfg)
+            .context("Failed to serialize config")?
+            .as_bytes(),
     );
+    hasher.update(relative_base.to_string_lossy().as_bytes());
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+/// A missing, unreadable, or outdated cache file is an empty cache.
+fn load_result_cache(cfg: &AppConfig) -> ResultCache {
+    let stamp = result_cache_stamp(cfg);
+    result_cache_path(cfg)
+        .ok()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|json| serde_json::from_str::<ResultCache>(&json).ok())
+        .filter(|cache| cache.stamp == stamp)
+        .unwrap_or(ResultCache {
+            stamp,
+            entries: Vec::new(),
+        })
+}
+
+fn cached_results(cfg: &AppConfig, key: &str) -> Option<Vec<SearchHit>> {
+    let cached = load_result_cache(cfg)
+        .entries
+        .into_iter()
+        .find(|entry| entry.key == key)?;
+    let mut results = cached.results;
+    for (hit, html) in results.iter_mut().zip(cached.snippets_html) {
+        hit.snippet_html = html;
+    }
+    Some(results)
+}
+
+/// Adds `results` to the cache. Failing to write it only costs the next search a lookup,
+/// so errors are reported as warnings.
+fn cache_results(cfg: &AppConfig, key: String, results: &[SearchHit]) {
+    let mut cache = load_result_cache(cfg);
+    cache.entries.retain(|entry| entry.key != key);
+    if cache.entries.len() >= RESULT_CACHE_ENTRIES {
+        cache.entries.remove(0);
+    }
+    cache.entries.push(CachedSearch {
+        key,
+        results: results.to_vec(),
+        snippets_html: results.iter().map(|hit| hit.snippet_html.clone()).collect(),
+    });
+    if let Err(err) = save_result_cache(cfg, &cache) {
+        eprintln!("  [warn] Failed to update the result cache: {err:#}");
+    }
+}
 
+fn save_result_cache(cfg: &AppConfig, cache: &ResultCache) -> Result<()> {
+    let path = result_cache_path(cfg)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create config directory {}", parent.display()))?;
+    }
+    let json = serde_json::to_vec(cache).context("Failed to serialize result cache")?;
+    write_atomically(&path, &json)
+        .with_context(|| format!("Failed to write result cache {}", path.display()))
+}
+
+/// Removes the result cache files of every index, returning whether there were any.
+fn clear_result_cache() -> Result<bool> {
+    let proj_dirs = get_project_dirs()?;
+    let entries = match fs::read_dir(proj_dirs.config_dir()) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(false),
+        Err(err) => {
+            return Err(err).with_context(|| {
+                format!(
+                    "Failed to list config directory {}",
+                    proj_dirs.config_dir().display()
+                )
+            })
+        }
+    };
+    let mut removed = false;
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        // Also matches the single `result_cache.json` of older versions.
+        if !name.starts_with(RESULT_CACHE_PREFIX) || !name.ends_with(".json") {
+            continue;
+        }
+        let path = entry.path();
+        fs::remove_file(&path)
+            .with_context(|| format!("Failed to remove result cache {}", path.display()))?;
+        removed = true;
+    }
+    Ok(removed)
+}
+
+fn cmd_cache_clear() -> Result<()> {
+    if clear_result_cache()? {
+        println!("Result cache cleared.");
+    } else {
+        println!("Result cache is already empty.");
+    }
     Ok(())
 }
 
-#[derive(Default)]
-struct SkipStats {
-    unsupported_extension: usize,
-    too_large: usize,
-    binary: usize,
-    read_errors: usize,
+/// Prints results (or the no-results message) and runs the `--interactive-open` prompt.
+fn present_results(args: &SearchArgs, query: &str, mut results: Vec<SearchHit>) -> Result<usize> {
+    if args.score_normalize {
+        normalize_scores(&mut results);
+        results.drain(..args.offset.min(results.len()));
+    }
+    if results.is_empty() {
+        return finish_without_results(args, &format!("No results found for query: {query}"))
+            .map(|()| 0);
+    }
+    if args.line_numbers {
+        let terms = query_highlight_terms(query);
+        for result in &mut results {
+            result.line = first_matching_line(Path::new(&result.path), &terms);
+        }
+    }
+
+    write_results(args, query, &results)?;
+
+    if args.interactive_open {
+        let result_paths: Vec<PathBuf> = results.iter().map(|r| PathBuf::from(&r.path)).collect();
+        prompt_and_open(&result_paths, results[0].rank)?;
+    }
+
+    Ok(results.len())
 }
 
-impl SkipStats {
-    fn total(&self) -> usize {
-        self.unsupported_extension + self.too_large + self.binary + self.read_errors
+/// Runs `request` against each of `index_dirs` and merges the hits by score, for
+/// `search --index`. Indexes that cannot be opened or queried are skipped with a
+/// warning; the search fails only if none can be. Paths are shown as stored: absolute,
+/// or relative to the root of the machine that built the index.
+///
+/// BM25 scores depend on each index's own term statistics, so a rare word scores
+/// higher in an index where it is rare; the merged order is approximate.
+fn federated_search(index_dirs: &[PathBuf], request: &SearchRequest) -> Result<Vec<SearchHit>> {
+    if request.sort != SortOrder::Score {
+        anyhow::bail!("--index merges results by score and cannot be combined with --sort");
+    }
+    // Search fields, boosts, and synonyms still come from the config when there is one,
+    // but its root says nothing about where another machine's relative paths live.
+    let (config_path, _) = resolve_config_path(&get_project_dirs()?);
+    let cfg = if config_path.exists() {
+        AppConfig {
+            root: String::new(),
+            ..load_config()?
+        }
+    } else {
+        standalone_config()
+    };
+    let mut per_index = request.clone();
+    per_index.limit = request.offset.saturating_add(request.limit);
+    if per_index.limit > MAX_RESULT_WINDOW {
+        anyhow::bail!("offset + limit must be at most {MAX_RESULT_WINDOW}");
+    }
+    per_index.offset = 0;
+
+    let mut hits = Vec::new();
+    let mut failures = 0;
+    for dir in index_dirs {
+        let searched = open_foreign_index(dir).and_then(|(index, reader)| {
+            execute_search(&index, &reader.searcher(), &cfg, &per_index, Path::new(""))
+        });
+        match searched {
+            Ok(found) => {
+                let label = dir.display().to_string();
+                hits.extend(found.into_iter().map(|hit| SearchHit {
+                    index: Some(label.clone()),
+                    ..hit
+                }));
+            }
+            Err(e) => {
+                eprintln!("  [warn] Skipping index {}: {e:#}", dir.display());
+                failures += 1;
+            }
+        }
+    }
+    if failures == index_dirs.len() {
+        anyhow::bail!("None of the --index directories could be searched");
+    }
+    Ok(merge_by_score(hits, request))
+}
+
+/// Searches the top-level root and each `[roots]` entry with its own index, labeling hits
+/// with the root name. Paths are shown in full, since they come from different roots.
+fn all_roots_search(request: &SearchRequest) -> Result<Vec<SearchHit>> {
+    if request.sort != SortOrder::Score {
+        anyhow::bail!("--all-roots merges results by score and cannot be combined with --sort");
+    }
+    let cfg = load_config()?;
+    warn_overlapping_roots(&cfg);
+    let mut per_root = request.clone();
+    per_root.limit = request.offset.saturating_add(request.limit);
+    if per_root.limit > MAX_RESULT_WINDOW {
+        anyhow::bail!("offset + limit must be at most {MAX_RESULT_WINDOW}");
+    }
+    per_root.offset = 0;
+
+    let mut roots = vec![(MAIN_ROOT_LABEL.to_string(), cfg.clone())];
+    for name in cfg.roots.keys() {
+        roots.push((name.clone(), select_root(cfg.clone(), name)?));
+    }
+    let mut hits = Vec::new();
+    for (label, root_cfg) in &roots {
+        let index_dir = Path::new(&root_cfg.index_dir);
+        if root_cfg.last_indexed.is_none() || !tantivy_index_exists(index_dir) {
+            eprintln!("  [warn] Skipping root `{label}`: not indexed yet (run `vaultsearch index --all-roots`)");
+            continue;
+        }
+        let (index, reader) =
+            open_index_reader(index_dir, &root_cfg.analysis, root_cfg.reader_reload)?;
+        let found = execute_search(
+            &index,
+            &reader.searcher(),
+            root_cfg,
+            &per_root,
+            Path::new(""),
+        )?;
+        hits.extend(found.into_iter().map(|hit| SearchHit {
+            index: Some(label.clone()),
+            ..hit
+        }));
+    }
+    // Paths here are absolute, so a file under nested roots shows up once per index;
+    // keep only its best-scoring hit.
+    hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+    let mut seen = HashSet::new();
+    hits.retain(|hit| seen.insert(hit.path.clone()));
+    Ok(merge_by_score(hits, request))
+}
+
+/// Orders hits from several indexes by score (path breaking ties) and ranks the page
+/// `request` asks for.
+fn merge_by_score(mut hits: Vec<SearchHit>, request: &SearchRequest) -> Vec<SearchHit> {
+    hits.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then_with(|| a.path.cmp(&b.path))
+    });
+    hits.into_iter()
+        .skip(request.offset)
+        .take(request.limit)
+        .enumerate()
+        .map(|(rank, hit)| SearchHit {
+            rank: request.offset + rank + 1,
+            ..hit
+        })
+        .collect()
+}
+
+/// Opens an index built elsewhere (e.g. copied from another machine) without the local
+/// config, registering tokenizers from the names its schema records. Indexes built with
+/// `stop_words` are refused: the words are not stored in the index, so queries could not
+/// be tokenized the way its contents were.
+fn open_foreign_index(dir: &Path) -> Result<(Index, IndexReader)> {
+    if !tantivy_index_exists(dir) {
+        anyhow::bail!("no index found (missing meta.json)");
+    }
+    let index = Index::open_in_dir(dir).context("Failed to open Tantivy index")?;
+    let schema = index.schema();
+    for name in ["path", "path_exact", "contents"] {
+        if indexed_text_field(&schema, name).is_none() {
+            anyhow::bail!("not a vaultsearch index (no `{name}` text field)");
+        }
+    }
+
+    let tokenizer = text_field_tokenizer(&schema, "contents").unwrap_or_default();
+    let max_token_length = match tokenizer.as_str() {
+        "default" => DEFAULT_MAX_TOKEN_LENGTH,
+        name => name
+            .strip_prefix("max_token_")
+            .and_then(|length| length.parse().ok())
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "built with custom stop_words (tokenizer `{name}`), which cannot be \
+                     reproduced from the index alone"
+                )
+            })?,
+    };
+    let analysis = ContentsAnalysis {
+        max_token_length,
+        ..ContentsAnalysis::default()
+    };
+    if !analysis.is_default() {
+        index
+            .tokenizers()
+            .register(&tokenizer, contents_analyzer(&analysis));
+    }
+    // The routed extensions only matter when indexing; the tokenizer needs just the length.
+    if let Some(code_tokenizer) = text_field_tokenizer(&schema, "code") {
+        index
+            .tokenizers()
+            .register(&code_tokenizer, code_analyzer(&analysis));
+    }
+
+    let reader = index.reader().context("Failed to create index reader")?;
+    Ok((index, reader))
+}
+
+/// Divides every score by the highest one so the best result scores 1.0. Only the
+/// returned results are considered, so scores are not comparable across queries.
+fn normalize_scores(results: &mut [SearchHit]) {
+    let top = results.iter().map(|hit| hit.score).fold(0.0_f32, f32::max);
+    if top > 0.0 {
+        for hit in results {
+            hit.score /= top;
+        }
+    }
+}
+
+/// Multiplies each BM25 score by the recency weight of the document's `modified` time.
+/// Documents without one (indexes older than the field) keep their score.
+fn recency_score_tweaker(
+    recency: RecencyBoost,
+    now: i64,
+) -> impl Fn(&SegmentReader) -> Box<dyn FnMut(DocId, Score) -> Score> + Send + Sync {
+    move |segment_reader: &SegmentReader| {
+        let modified = segment_reader.fast_fields().date("modified").ok();
+        Box::new(move |doc: DocId, score: Score| {
+            match modified.as_ref().and_then(|column| column.first(doc)) {
+                Some(modified) => score * recency.weight(now - modified.into_timestamp_secs()),
+                None => score,
+            }
+        })
+    }
+}
+
+/// `path` relative to `base`, or unchanged when it lies outside `base`.
+fn relative_display_path(path: &str, base: &Path) -> String {
+    Path::new(path)
+        .strip_prefix(base)
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or_else(|_| path.to_string())
+}
+
+/// Runs `request` against an open index and returns the ranked hits (empty when nothing matched).
+/// Shared by local searches and `vaultsearch serve`; output formatting is left to the caller.
+fn execute_search(
+    index: &Index,
+    searcher: &Searcher,
+    cfg: &AppConfig,
+    request: &SearchRequest,
+    relative_base: &Path,
+) -> Result<Vec<SearchHit>> {
+    let request = &apply_relevance_profile(cfg, request)?;
+    let schema = index.schema();
+    let path_field = schema.get_field("path").expect("path field");
+    let contents_field = schema.get_field("contents").expect("contents field");
+
+    let mode_filter = request.mode.as_deref().map(parse_mode_filter).transpose()?;
+    let excluded_paths = build_exclude_set(&request.exclude_path)?;
+
+    let tantivy_query = build_search_query(index, searcher, cfg, request)?;
+    let tantivy_query =
+        apply_ownership_filters(tantivy_query, searcher, &schema, request.owner, mode_filter)?;
+    let tantivy_query = apply_range_filters(tantivy_query, &schema, request)?;
+    let tantivy_query = apply_language_filter(tantivy_query, &schema, request.language.as_deref())?;
+    let lines_field = schema.get_field("lines").ok();
+    let comments_field = schema.get_field("comments").ok();
+
+    let mut snippet_generators = Vec::new();
+    let fields_to_snippet = if request.no_snippet {
+        Vec::new()
+    } else {
+        snippet_fields(&schema, contents_field)
+    };
+    for field in fields_to_snippet {
+        let mut generator = SnippetGenerator::create(searcher, &tantivy_query, field)
+            .context("Failed to create snippet generator")?;
+        generator.set_max_num_chars(SNIPPET_MAX_CHARS);
+        snippet_generators.push((field, generator));
+    }
+
+    // `--exclude-path`, `--since`, and `--limit-per-dir` filter after ranking, so fetch more
+    // results until `offset + limit` survive the filters or the query runs out of matches.
+    let root = Path::new(&cfg.root);
+    let recency = request
+        .recency_boost
+        .then(|| RecencyBoost {
+            half_life_days: request
+                .recency_half_life
+                .unwrap_or(cfg.recency_boost.half_life_days),
+            ..cfg.recency_boost
+        })
+        .map(|recency| recency.validate().map(|()| recency))
+        .transpose()?;
+    let wanted = request.offset.saturating_add(request.limit);
+    // Asking tantivy for more slots than there are documents only wastes memory.
+    let max_fetch = (searcher.num_docs() as usize).max(1);
+    let mut fetch_limit = wanted.min(max_fetch);
+    let mut hits = loop {
+        let top_docs = match recency {
+            Some(recency) => searcher.search(
+                &tantivy_query,
+                &TopDocs::with_limit(fetch_limit)
+                    .tweak_score(recency_score_tweaker(recency, Utc::now().timestamp())),
+            ),
+            None => searcher.search(&tantivy_query, &TopDocs::with_limit(fetch_limit)),
+        }
+        .context("Search failed")?;
+        let exhausted = top_docs.len() < fetch_limit || fetch_limit >= max_fetch;
+
+        let mut hits = Vec::with_capacity(top_docs.len());
+        let mut per_dir: HashMap<PathBuf, usize> = HashMap::new();
+        for (score, doc_address) in top_docs {
+            let doc: TantivyDocument = searcher
+                .doc(doc_address)
+                .context("Failed to load document")?;
+            let path = doc
+                .get_first(path_field)
+                .and_then(|v| v.as_str())
+                .map_or_else(
+                    || "<unknown path>".to_string(),
+                    |stored| resolve_stored_path(stored, root),
+                );
+            if excluded_paths
+                .as_ref()
+                .is_some_and(|globs| path_is_excluded(globs, &path, root))
+            {
+                continue;
+            }
+            if let Some(since) = request.since {
+                // Relative only when stored that way and searched without its root (`--index`).
+                if Path::new(&path).is_relative() {
+                    anyhow::bail!(
+                        "--since needs each file on disk, but `{path}` is stored relative to a root this search does not know"
+                    );
+                }
+                if !modified_on_disk_since(Path::new(&path), since) {
+                    continue;
+                }
+            }
+            if let Some(cap) = request.limit_per_dir {
+                let dir = Path::new(&path)
+                    .parent()
+                    .unwrap_or(Path::new(""))
+                    .to_path_buf();
+                let count = per_dir.entry(dir).or_default();
+                if *count >= cap {
+                    continue;
+                }
+                *count += 1;
+            }
+            let lines = lines_field
+                .and_then(|field| doc.get_first(field))
+                .and_then(|v| v.as_u64())
+                .unwrap_or(0);
+            hits.push(RankedDoc {
+                score,
+                path,
+                lines,
+                doc,
+            });
+        }
+
+        if hits.len() >= wanted || exhausted {
+            hits.truncate(wanted);
+            hits.drain(..request.offset.min(hits.len()));
+            break hits;
+        }
+        fetch_limit = fetch_limit.saturating_mul(4).min(max_fetch);
+    };
+    sort_hits(&mut hits, request.sort);
+
+    // Older indexes may predate the field; they simply never report symlinks.
+    let symlink_target_field = schema.get_field("symlink_target").ok();
+    let language_field = schema.get_field("language").ok();
+    let size_field = schema
+        .get_field("size")
+        .ok()
+        .filter(|_| request.file_details);
+    let modified_field = schema
+        .get_field("modified")
+        .ok()
+        .filter(|_| request.file_details);
+    let mut span_finder = if request.match_spans {
+        Some(MatchSpanFinder::new(index, contents_field, &tantivy_query)?)
+    } else {
+        None
+    };
+
+    Ok(hits
+        .iter()
+        .enumerate()
+        .map(|(rank, hit)| SearchHit {
+            rank: request.offset + rank + 1,
+            score: hit.score,
+            path: hit.path.clone(),
+            relative_path: relative_display_path(&hit.path, relative_base),
+            snippet_html: best_snippet(&snippet_generators, comments_field, &hit.doc),
+            symlink_target: symlink_target_field
+                .and_then(|field| hit.doc.get_first(field))
+                .and_then(|v| v.as_str())
+                .map(str::to_string),
+            fields: request
+                .fields_json
+                .then(|| stored_fields_json(&schema, &hit.doc)),
+            size: size_field
+                .and_then(|field| hit.doc.get_first(field))
+                .and_then(|v| v.as_u64()),
+            modified: modified_field
+                .and_then(|field| hit.doc.get_first(field))
+                .and_then(|v| v.as_datetime())
+                .map(|date| date.into_timestamp_secs()),
+            match_spans: span_finder.as_mut().map(|finder| finder.spans(&hit.doc)),
+            language: language_field
+                .and_then(|field| hit.doc.get_first(field))
+                .and_then(|v| v.as_str())
+                .map(str::to_string),
+            index: None,
+            line: None,
+        })
+        .collect())
+}
+
+/// Rewrites `size:` and `modified:` terms with human-friendly values into the
+/// integer and RFC 3339 forms the query parser's range syntax expects, e.g.
+/// `size:>10KB` becomes `size:>10240`. Plain dates cover the whole UTC day, so
+/// `modified:<=2023-12-31` includes files changed on the 31st.
+fn translate_field_ranges(query: &str) -> Result<String> {
+    let mut out = String::with_capacity(query.len());
+    let mut rest = query;
+
+    while let Some((start, field)) = find_range_field(rest) {
+        out.push_str(&rest[..start]);
+        let value_start = start + field.len() + 1;
+        let (translated, consumed) = translate_range_value(field, &rest[value_start..])?;
+        out.push_str(field);
+        out.push(':');
+        out.push_str(&translated);
+        rest = &rest[value_start + consumed..];
+    }
+    out.push_str(rest);
+
+    Ok(out)
+}
+
+/// Finds the earliest `size:` or `modified:` that starts a term (not part of a longer word
+/// or inside a quoted phrase).
+fn find_range_field(text: &str) -> Option<(usize, &'static str)> {
+    ["size", "modified"]
+        .into_iter()
+        .filter_map(|field| {
+            let prefix = format!("{field}:");
+            text.match_indices(&prefix)
+                .map(|(start, _)| start)
+                .find(|&start| {
+                    let before = &text[..start];
+                    let in_quotes = before.matches('"').count() % 2 == 1;
+                    !in_quotes
+                        && before
+                            .chars()
+                            .next_back()
+                            .is_none_or(|c| c.is_whitespace() || matches!(c, '(' | '+' | '-'))
+                })
+                .map(|start| (start, field))
+        })
+        .min()
+}
+
+/// Translates the value after `field:` and returns it with the number of bytes consumed.
+fn translate_range_value(field: &str, text: &str) -> Result<(String, usize)> {
+    if text.starts_with(['[', '{']) {
+        let close = text
+            .find([']', '}'])
+            .ok_or_else(|| range_error(field, text, "range is missing `]` or `}`"))?;
+        let inner = &text[1..close];
+        let (low, high) = inner
+            .split_once(" TO ")
+            .ok_or_else(|| range_error(field, inner, "expected `[LOW TO HIGH]`"))?;
+        let (low, low_open) = range_bound(field, low.trim(), text.starts_with('['), true)?;
+        let (high, high_close) =
+            range_bound(field, high.trim(), text[close..].starts_with(']'), false)?;
+        return Ok((format!("{low_open}{low} TO {high}{high_close}"), close + 1));
+    }
+
+    let end = text
+        .find(|c: char| c.is_whitespace() || c == ')')
+        .unwrap_or(text.len());
+    let token = &text[..end];
+    let op = [">=", "<=", ">", "<"]
+        .into_iter()
+        .find(|op| token.starts_with(op))
+        .unwrap_or("");
+    let value = &token[op.len()..];
+
+    let translated = if field == "size" {
+        let bytes = parse_size_value(value).ok_or_else(|| {
+            range_error(
+                field,
+                value,
+                "use a byte count with an optional unit, e.g. 10KB",
+            )
+        })?;
+        if op.is_empty() {
+            format!("[{bytes} TO {bytes}]")
+        } else {
+            format!("{op}{bytes}")
+        }
+    } else {
+        let (start, day_end) = parse_modified_value(value)?;
+        match (op, day_end) {
+            (">", Some(next_day)) => format!(">={next_day}"),
+            ("<=", Some(next_day)) => format!("<{next_day}"),
+            ("", Some(next_day)) => format!("[{start} TO {next_day}}}"),
+            ("", None) => format!("[{start} TO {start}]"),
+            (op, _) => format!("{op}{start}"),
+        }
+    };
+
+    Ok((translated, end))
+}
+
+/// Translates one end of a bracketed range, returning the bound and its bracket.
+/// Plain dates widen to whole days: an inclusive upper bound becomes the start of
+/// the next day (exclusive), and an exclusive lower bound starts on the next day.
+fn range_bound(field: &str, value: &str, inclusive: bool, lower: bool) -> Result<(String, String)> {
+    let bracket = |inclusive: bool| match (lower, inclusive) {
+        (true, true) => "[",
+        (true, false) => "{",
+        (false, true) => "]",
+        (false, false) => "}",
+    };
+    if value == "*" {
+        return Ok(("*".to_string(), bracket(inclusive).to_string()));
+    }
+
+    if field == "size" {
+        let bytes = parse_size_value(value).ok_or_else(|| {
+            range_error(
+                field,
+                value,
+                "use a byte count with an optional unit, e.g. 10KB",
+            )
+        })?;
+        return Ok((bytes.to_string(), bracket(inclusive).to_string()));
+    }
+
+    let (start, day_end) = parse_modified_value(value)?;
+    match day_end {
+        Some(next_day) if lower != inclusive => Ok((next_day, bracket(lower).to_string())),
+        _ => Ok((start, bracket(inclusive).to_string())),
+    }
+}
+
+/// Parses size flags such as `--min-file-size 1KB` with the same units as `size:` queries.
+fn parse_size_arg(value: &str) -> std::result::Result<u64, String> {
+    parse_size_value(value).ok_or_else(|| {
+        format!("invalid size `{value}`: use a byte count with an optional unit, e.g. 512 or 2KB")
+    })
+}
+
+/// Parses sizes such as `500`, `10KB`, or `1.5mb` (1 KB = 1024 bytes, like the index summary).
+fn parse_size_value(value: &str) -> Option<u64> {
+    let split = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: f64 = number.parse().ok()?;
+    let multiplier: u64 = match unit.to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" => 1024,
+        "M" | "MB" => 1024 * 1024,
+        "G" | "GB" => 1024 * 1024 * 1024,
+        _ => return None,
+    };
+    Some((number * multiplier as f64).round() as u64)
+}
+
+/// Parses `--since` (an RFC 3339 timestamp, or a `YYYY-MM-DD` date at midnight UTC) into Unix seconds.
+fn parse_since(text: &str) -> std::result::Result<i64, String> {
+    let text = text.trim();
+    if let Ok(timestamp) = chrono::DateTime::parse_from_rfc3339(text) {
+        return Ok(timestamp.timestamp());
+    }
+    chrono::NaiveDate::parse_from_str(text, "%Y-%m-%d")
+        .map(|date| date.and_time(chrono::NaiveTime::MIN).and_utc().timestamp())
+        .map_err(|_| format!("use a date like 2023-01-31 or an RFC 3339 timestamp, got `{text}`"))
+}
+
+/// Whether the file at `path` still exists and was modified at or after `since` (Unix
+/// seconds), going by the filesystem rather than the indexed `modified` field.
+fn modified_on_disk_since(path: &Path, since: i64) -> bool {
+    fs::metadata(path)
+        .ok()
+        .and_then(|metadata| file_modified_timestamp(&metadata).ok())
+        .is_some_and(|modified| modified >= since)
+}
+
+/// Parses a `modified:` value into an RFC 3339 timestamp. For a plain `YYYY-MM-DD`
+/// date (midnight UTC) it also returns the start of the following day.
+fn parse_modified_value(value: &str) -> Result<(String, Option<String>)> {
+    if let Ok(timestamp) = chrono::DateTime::parse_from_rfc3339(value) {
+        let utc = timestamp.with_timezone(&Utc);
+        return Ok((utc.to_rfc3339_opts(chrono::SecondsFormat::Secs, true), None));
+    }
+
+    let date = chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d").map_err(|_| {
+        range_error(
+            "modified",
+            value,
+            "use a date like 2023-01-31 or an RFC 3339 timestamp",
+        )
+    })?;
+    let midnight = |date: chrono::NaiveDate| {
+        date.and_time(chrono::NaiveTime::MIN)
+            .and_utc()
+            .to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+    };
+    let next_day = date
+        .succ_opt()
+        .ok_or_else(|| range_error("modified", value, "date is out of range"))?;
+    Ok((midnight(date), Some(midnight(next_day))))
+}
+
+fn range_error(field: &str, value: &str, hint: &str) -> anyhow::Error {
+    categorized(
+        FailureKind::QueryParse,
+        format!("Invalid `{field}:` value `{value}`: {hint}"),
+    )
+    .into()
+}
+
+/// Builds the Tantivy query for `request` before the `--owner`/`--mode` filters:
+/// the parsed query text (after `size:`/`modified:` translation), expanded for
+/// `--wildcard` or `--stem-language`, or the `--near-file` similarity query.
+fn build_search_query(
+    index: &Index,
+    searcher: &Searcher,
+    cfg: &AppConfig,
+    request: &SearchRequest,
+) -> Result<Box<dyn Query>> {
+    let schema = index.schema();
+    let contents_field = schema.get_field("contents").expect("contents field");
+
+    if let Some(reference) = &request.near_file {
+        let path_exact_field = schema.get_field("path_exact").expect("path_exact field");
+        return build_near_file_query(
+            index,
+            searcher,
+            reference,
+            contents_field,
+            path_exact_field,
+            cfg.store_relative_paths.then(|| Path::new(&cfg.root)),
+            cfg.size_limits.limit_for(reference),
+        );
+    }
+
+    let mut query = translate_field_ranges(&request.query)?;
+    if request.whole_word {
+        if request.wildcard || request.stem_language.is_some() {
+            anyhow::bail!("--whole-word cannot be combined with --wildcard or --stem-language");
+        }
+        // `"big ca"*` matches any word starting with `ca` after `big`.
+        if query.contains("\"*") {
+            return Err(categorized(
+                FailureKind::QueryParse,
+                format!(
+                    "Phrase prefix queries (`\"…\"*`) match partial words; remove the `*` \
+                     to use --whole-word: {query}"
+                ),
+            )
+            .into());
+        }
+    }
+    if request.synonyms {
+        query = expand_synonyms(&query, &cfg.synonyms);
+    }
+    let query = query.as_str();
+    let (default_fields, boosts) = resolve_query_fields(&schema, cfg, request);
+    let mut query_parser = QueryParser::for_index(index, default_fields.clone());
+    for (field, boost) in boosts {
+        query_parser.set_field_boost(field, boost);
+    }
+
+    if request.wildcard {
+        build_wildcard_query(&query_parser, searcher, query, &default_fields)
+    } else if let Some(language) = request.stem_language {
+        // Stems are only collected from prose; code identifiers are matched as written.
+        let code_field = schema.get_field("code").ok();
+        let prose_fields: Vec<Field> = default_fields
+            .iter()
+            .copied()
+            .filter(|&field| Some(field) != code_field)
+            .collect();
+        build_stemmed_query(&query_parser, searcher, query, &prose_fields, language)
+    } else {
+        query_parser.parse_query(query).with_context(|| {
+            categorized(
+                FailureKind::QueryParse,
+                format!("Failed to parse query: {query}"),
+            )
+        })
+    }
+}
+
+/// Compiles `--exclude-path` globs, or `None` when there are none.
+fn build_exclude_set(patterns: &[String]) -> Result<Option<GlobSet>> {
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob = Glob::new(pattern)
+            .with_context(|| format!("Invalid --exclude-path glob `{pattern}`"))?;
+        builder.add(glob);
+    }
+    builder
+        .build()
+        .map(Some)
+        .context("Failed to compile --exclude-path globs")
+}
+
+/// Matches the stored absolute path and, for patterns like `archive/**`, the path
+/// relative to the index root.
+fn path_is_excluded(globs: &GlobSet, path: &str, root: &Path) -> bool {
+    globs.is_match(path)
+        || Path::new(path)
+            .strip_prefix(root)
+            .is_ok_and(|relative| globs.is_match(relative))
+}
+
+/// Fills in the settings `request` leaves unset from its `--relevance` profile, or from
+/// `default_relevance_profile` when none was named.
+fn apply_relevance_profile<'a>(
+    cfg: &AppConfig,
+    request: &'a SearchRequest,
+) -> Result<Cow<'a, SearchRequest>> {
+    let (name, setting) = match (&request.relevance, &cfg.default_relevance_profile) {
+        (Some(name), _) => (name, "--relevance"),
+        (None, Some(name)) => (name, "default_relevance_profile"),
+        (None, None) => return Ok(Cow::Borrowed(request)),
+    };
+    let Some(profile) = cfg.relevance_profiles.get(name) else {
+        let known = if cfg.relevance_profiles.is_empty() {
+            "none are configured; add a `[relevance_profiles.<name>]` table".to_string()
+        } else {
+            let names: Vec<&str> = cfg.relevance_profiles.keys().map(String::as_str).collect();
+            format!("configured profiles: {}", names.join(", "))
+        };
+        return Err(categorized(
+            FailureKind::Config,
+            format!("Unknown relevance profile `{name}` in {setting}; {known}"),
+        )
+        .into());
+    };
+
+    let mut request = request.clone();
+    if request.search_fields.is_empty() {
+        request.search_fields = profile.search_fields.clone();
+    }
+    for (field, weight) in &profile.field_boosts {
+        request.field_boosts.entry(field.clone()).or_insert(*weight);
+    }
+    request.recency_boost |= profile.recency_boost;
+    request.recency_half_life = request.recency_half_life.or(profile.recency_half_life_days);
+    Ok(Cow::Owned(request))
+}
+
+/// Default fields and boosts for bare query terms: `--search-field`/`--field-boost`
+/// over `search_fields`/`field_boosts` from config, over `path` and `contents`.
+/// Names that are not text fields of the schema are skipped with a warning.
+fn resolve_query_fields(
+    schema: &Schema,
+    cfg: &AppConfig,
+    request: &SearchRequest,
+) -> (Vec<Field>, Vec<(Field, f32)>) {
+    let text_field = |name: &str, setting: &str| match schema.get_field(name) {
+        Ok(field)
+            if matches!(
+                schema.get_field_entry(field).field_type(),
+                FieldType::Str(_)
+            ) =>
+        {
+            Some(field)
+        }
+        Ok(_) => {
+            eprintln!("  [warn] Ignoring {setting} `{name}`: not a text field");
+            None
+        }
+        Err(_) => {
+            eprintln!("  [warn] Ignoring {setting} `{name}`: no such field in the index schema");
+            None
+        }
+    };
+
+    let names = if request.search_fields.is_empty() {
+        &cfg.search_fields
+    } else {
+        &request.search_fields
+    };
+    let mut fields: Vec<Field> = Vec::new();
+    for name in names {
+        if let Some(field) = text_field(name, "search field") {
+            if !fields.contains(&field) {
+                fields.push(field);
+            }
+        }
+    }
+    if fields.is_empty() {
+        if !names.is_empty() {
+            eprintln!("  [warn] No usable search fields; searching `path` and `contents`");
+        }
+        fields = ["path", "contents"]
+            .into_iter()
+            .map(|name| schema.get_field(name).expect("built-in field"))
+            .collect();
+        // Routed code is searched alongside prose unless `search_fields` says otherwise.
+        fields.extend(schema.get_field("code").ok());
+    }
+
+    let mut weights = cfg.field_boosts.clone();
+    weights.extend(request.field_boosts.clone());
+    let boosts = weights
+        .iter()
+        .filter_map(|(name, &boost)| text_field(name, "field boost").map(|field| (field, boost)))
+        .collect();
+
+    (fields, boosts)
+}
+
+/// Parses a `--field-boost FIELD=WEIGHT` pair.
+fn parse_field_boost(text: &str) -> std::result::Result<(String, f32), String> {
+    let (field, weight) = text
+        .split_once('=')
+        .ok_or_else(|| format!("expected FIELD=WEIGHT (e.g. path=3), got `{text}`"))?;
+    let weight: f32 = weight
+        .trim()
+        .parse()
+        .ok()
+        .filter(|w: &f32| w.is_finite() && *w > 0.0)
+        .ok_or_else(|| format!("boost for `{field}` must be a positive number, got `{weight}`"))?;
+    Ok((field.trim().to_string(), weight))
+}
+
+fn parse_mode_filter(text: &str) -> Result<ModeFilter> {
+    let (digits, all_of) = match text.strip_prefix('-') {
+        Some(rest) => (rest, true),
+        None => (text, false),
+    };
+    let bits = u32::from_str_radix(digits, 8)
+        .ok()
+        .filter(|bits| *bits <= 0o7777)
+        .with_context(|| {
+            format!("Invalid --mode `{text}`: expected octal permission bits such as 644 or -002")
+        })?;
+    Ok(if all_of {
+        ModeFilter::AllOf(bits)
+    } else {
+        ModeFilter::Exact(bits)
+    })
+}
+
+/// Restricts `query` to files matching the `--owner`/`--mode` filters without changing scores.
+fn apply_ownership_filters(
+    query: Box<dyn Query>,
+    searcher: &Searcher,
+    schema: &Schema,
+    owner: Option<u64>,
+    mode: Option<ModeFilter>,
+) -> Result<Box<dyn Query>> {
+    if owner.is_none() && mode.is_none() {
+        return Ok(query);
+    }
+    if !cfg!(unix) {
+        anyhow::bail!("--owner and --mode are only supported on Unix");
+    }
+    let outdated = || {
+        anyhow::anyhow!(
+            "This index has no owner or permission fields. Re-run `vaultsearch init --force` to rebuild it."
+        )
+    };
+
+    let mut clauses: Vec<(Occur, Box<dyn Query>)> = vec![(Occur::Must, query)];
+    if let Some(uid) = owner {
+        let uid_field = schema.get_field("uid").map_err(|_| outdated())?;
+        let uid_query = TermQuery::new(
+            Term::from_field_u64(uid_field, uid),
+            IndexRecordOption::Basic,
+        );
+        clauses.push((
+            Occur::Must,
+            Box::new(ConstScoreQuery::new(Box::new(uid_query), 0.0)),
+        ));
+    }
+    if let Some(filter) = mode {
+        // Bitmask filters cannot be expressed as a single term, so match every mode present
+        // in the index that satisfies the filter.
+        let mode_field = schema.get_field("mode").map_err(|_| outdated())?;
+        let mode_clauses: Vec<(Occur, Box<dyn Query>)> = indexed_u64_values(searcher, mode_field)?
+            .into_iter()
+            .filter(|&mode| u32::try_from(mode).is_ok_and(|mode| filter.matches(mode)))
+            .map(|mode| {
+                let term_query = TermQuery::new(
+                    Term::from_field_u64(mode_field, mode),
+                    IndexRecordOption::Basic,
+                );
+                (Occur::Should, Box::new(term_query) as Box<dyn Query>)
+            })
+            .collect();
+        clauses.push((
+            Occur::Must,
+            Box::new(ConstScoreQuery::new(
+                Box::new(BooleanQuery::new(mode_clauses)),
+                0.0,
+            )),
+        ));
+    }
+
+    Ok(Box::new(BooleanQuery::new(clauses)))
+}
+
+/// Restricts `query` to files within the `--min-lines`/`--max-lines` and
+/// `--modified-within` bounds without changing scores.
+fn apply_range_filters(
+    query: Box<dyn Query>,
+    schema: &Schema,
+    request: &SearchRequest,
+) -> Result<Box<dyn Query>> {
+    let outdated = |what: &str| {
+        anyhow::anyhow!(
+            "This index has no {what}. Re-run `vaultsearch init --force` to rebuild it."
+        )
+    };
+
+    let mut clauses: Vec<(Occur, Box<dyn Query>)> = vec![(Occur::Must, query)];
+    if request.min_lines.is_some() || request.max_lines.is_some() {
+        let lines_field = schema
+            .get_field("lines")
+            .map_err(|_| outdated("line counts"))?;
+        let bound = |value: Option<u64>| match value {
+            Some(n) => Bound::Included(Term::from_field_u64(lines_field, n)),
+            None => Bound::Unbounded,
+        };
+        let range = RangeQuery::new(bound(request.min_lines), bound(request.max_lines));
+        clauses.push((
+            Occur::Must,
+            Box::new(ConstScoreQuery::new(Box::new(range), 0.0)),
+        ));
+    }
+    if let Some(seconds) = request.modified_within {
+        let modified_field = schema
+            .get_field("modified")
+            .map_err(|_| outdated("modification times"))?;
+        let seconds = i64::try_from(seconds).unwrap_or(i64::MAX);
+        let cutoff = Utc::now().timestamp().saturating_sub(seconds);
+        let range = RangeQuery::new(
+            Bound::Included(Term::from_field_date(
+                modified_field,
+                DateTime::from_timestamp_secs(cutoff),
+            )),
+            Bound::Unbounded,
+        );
+        clauses.push((
+            Occur::Must,
+            Box::new(ConstScoreQuery::new(Box::new(range), 0.0)),
+        ));
+    }
+    if let Some(generation) = request.as_of_generation {
+        let generation_field = schema
+            .get_field("generation")
+            .map_err(|_| outdated("indexing generations"))?;
+        let range = RangeQuery::new(
+            Bound::Unbounded,
+            Bound::Included(Term::from_field_u64(generation_field, generation)),
+        );
+        clauses.push((
+            Occur::Must,
+            Box::new(ConstScoreQuery::new(Box::new(range), 0.0)),
+        ));
+    }
+
+    if clauses.len() == 1 {
+        let (_, query) = clauses.pop().expect("query clause");
+        return Ok(query);
+    }
+    Ok(Box::new(BooleanQuery::new(clauses)))
+}
+
+/// Restricts `query` to files detected as `--language` without changing scores.
+fn apply_language_filter(
+    query: Box<dyn Query>,
+    schema: &Schema,
+    language: Option<&str>,
+) -> Result<Box<dyn Query>> {
+    let Some(language) = language else {
+        return Ok(query);
+    };
+    let language_field = schema.get_field("language").map_err(|_| {
+        anyhow::anyhow!(
+            "This index has no detected languages. Re-run `vaultsearch init --force` to rebuild it."
+        )
+    })?;
+    let language_query = TermQuery::new(
+        Term::from_field_text(language_field, language),
+        IndexRecordOption::Basic,
+    );
+    Ok(Box::new(BooleanQuery::new(vec![
+        (Occur::Must, query),
+        (
+            Occur::Must,
+            Box::new(ConstScoreQuery::new(Box::new(language_query), 0.0)),
+        ),
+    ])))
+}
+
+/// Parses `--language` codes into whatlang's ISO 639-3 form, keeping `unknown` as is.
+fn parse_language_code(text: &str) -> std::result::Result<String, String> {
+    let code = text.trim().to_ascii_lowercase();
+    if code == UNKNOWN_LANGUAGE {
+        return Ok(code);
+    }
+    whatlang::Lang::from_code(&code)
+        .map(|lang| lang.code().to_string())
+        .ok_or_else(|| {
+            format!(
+                "unknown language code `{text}`: use an ISO 639-3 code such as eng, fra, or spa, or `unknown`"
+            )
+        })
+}
+
+/// Parses `--modified-within` durations such as `90m`, `24h`, `7d`, or `2w` into seconds.
+fn parse_duration_secs(text: &str) -> std::result::Result<u64, String> {
+    let invalid = || {
+        format!("invalid duration `{text}`: use a number followed by s, m, h, d, or w (e.g. 24h, 7d, 2w)")
+    };
+    let split = text
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(invalid)?;
+    let (number, unit) = text.split_at(split);
+    let number: u64 = number.parse().map_err(|_| invalid())?;
+    let unit_secs: u64 = match unit.to_ascii_lowercase().as_str() {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Err(invalid()),
+    };
+    number.checked_mul(unit_secs).ok_or_else(invalid)
+}
+
+/// Distinct values of an indexed u64 field across all segments.
+fn indexed_u64_values(searcher: &Searcher, field: Field) -> Result<BTreeSet<u64>> {
+    let mut values = BTreeSet::new();
+    for segment_reader in searcher.segment_readers() {
+        let inverted_index = segment_reader
+            .inverted_index(field)
+            .context("Failed to open inverted index")?;
+        let mut terms = inverted_index
+            .terms()
+            .stream()
+            .context("Failed to stream term dictionary")?;
+        while terms.advance() {
+            if let Ok(bytes) = <[u8; 8]>::try_from(terms.key()) {
+                values.insert(u64::from_be_bytes(bytes));
+            }
+        }
+    }
+    Ok(values)
+}
+
+/// Builds a "more like this" query from the reference file's most distinctive terms.
+///
+/// The file is tokenized like indexed contents, each term is weighted by TF-IDF
+/// (its count in the file times `ln(1 + N / df)` over the index), and the top
+/// `NEAR_FILE_MAX_TERMS` become boosted OR clauses. Terms absent from the index
+/// are ignored, and the reference file itself is excluded from the results.
+fn build_near_file_query(
+    index: &Index,
+    searcher: &Searcher,
+    reference: &Path,
+    contents_field: Field,
+    path_exact_field: Field,
+    relative_to: Option<&Path>,
+    max_bytes: u64,
+) -> Result<Box<dyn Query>> {
+    let size = fs::metadata(reference)
+        .with_context(|| format!("Failed to read reference file {}", reference.display()))?
+        .len();
+    let text = read_file_streaming(reference, size, max_bytes)?.contents;
+
+    let mut tokenizer = index
+        .tokenizer_for_field(contents_field)
+        .context("Failed to load contents tokenizer")?;
+    let mut term_freqs: HashMap<String, usize> = HashMap::new();
+    let mut stream = tokenizer.token_stream(&text);
+    while let Some(token) = stream.next() {
+        if token.text.chars().count() >= NEAR_FILE_MIN_TERM_CHARS {
+            *term_freqs.entry(token.text.clone()).or_default() += 1;
+        }
+    }
+
+    let num_docs = searcher.num_docs();
+    let weighted = top_weighted_terms(
+        term_freqs,
+        |term| {
+            searcher
+                .doc_freq(&Term::from_field_text(contents_field, term))
+                .unwrap_or(0)
+        },
+        num_docs,
+        NEAR_FILE_MAX_TERMS,
+    );
+    if weighted.is_empty() {
+        anyhow::bail!(
+            "No distinctive terms from {} appear in the index",
+            reference.display()
+        );
+    }
+
+    let mut clauses: Vec<(Occur, Box<dyn Query>)> = weighted
+        .into_iter()
+        .map(|(term, weight)| {
+            let term_query = TermQuery::new(
+                Term::from_field_text(contents_field, &term),
+                IndexRecordOption::WithFreqs,
+            );
+            let boosted: Box<dyn Query> = Box::new(BoostQuery::new(Box::new(term_query), weight));
+            (Occur::Should, boosted)
+        })
+        .collect();
+
+    let canonical = fs::canonicalize(reference).unwrap_or_else(|_| reference.to_path_buf());
+    let exclude_self = TermQuery::new(
+        Term::from_field_text(path_exact_field, &stored_path(&canonical, relative_to)),
+        IndexRecordOption::Basic,
+    );
+    clauses.push((Occur::MustNot, Box::new(exclude_self)));
+
+    Ok(Box::new(BooleanQuery::new(clauses)))
+}
+
+/// Ranks terms by TF-IDF, dropping terms that never occur in the index.
+fn top_weighted_terms(
+    term_freqs: HashMap<String, usize>,
+    doc_freq: impl Fn(&str) -> u64,
+    num_docs: u64,
+    max_terms: usize,
+) -> Vec<(String, f32)> {
+    let mut weighted: Vec<(String, f32)> = term_freqs
+        .into_iter()
+        .filter_map(|(term, tf)| {
+            let df = doc_freq(&term);
+            if df == 0 {
+                return None;
+            }
+            let idf = (1.0 + num_docs as f32 / df as f32).ln();
+            Some((term, tf as f32 * idf))
+        })
+        .collect();
+
+    weighted.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    weighted.truncate(max_terms);
+    weighted
+}
+
+/// Parses `query` after rewriting each bare word into an `OR` group of every
+/// indexed term that shares its stem in `language`, so `haus` also finds
+/// `häuser` and `hauses`. Operators and grouping are kept as written.
+///
+/// Contents are indexed unstemmed, so the stems are compared at query time by
+/// scanning the term dictionary of each default field, like `--wildcard`. At most
+/// `STEM_MAX_EXPANSIONS` terms are kept per word and field. Stemming with a
+/// language other than the documents' own mostly finds nothing beyond the exact
+/// words.
+fn build_stemmed_query(
+    query_parser: &QueryParser,
+    searcher: &Searcher,
+    query: &str,
+    fields: &[Field],
+    language: StemLanguage,
+) -> Result<Box<dyn Query>> {
+    let tokens = split_stemmable_words(query);
+    let mut stemmer = stem_analyzer(language);
+    let wanted: HashSet<String> = tokens
+        .iter()
+        .filter_map(|(_, word)| word.as_deref())
+        .map(|word| stem_word(&mut stemmer, word))
+        .collect();
+
+    let mut forms: HashMap<String, BTreeSet<String>> = HashMap::new();
+    for &field in fields {
+        for (stem, found) in expand_stems(searcher, field, &mut stemmer, &wanted)? {
+            forms.entry(stem).or_default().extend(found);
+        }
+    }
+
+    let rewritten: Vec<String> = tokens
+        .iter()
+        .map(|(token, word)| {
+            let Some(word) = word else {
+                return token.to_string();
+            };
+            let mut group = forms
+                .get(&stem_word(&mut stemmer, word))
+                .cloned()
+                .unwrap_or_default();
+            group.insert(word.clone());
+            if group.len() == 1 {
+                return token.to_string();
+            }
+            let prefix = &token[..token.len() - token.trim_start_matches(['+', '-']).len()];
+            let group: Vec<String> = group.into_iter().collect();
+            format!("{prefix}({})", group.join(" OR "))
+        })
+        .collect();
+
+    query_parser
+        .parse_query(&rewritten.join(" "))
+        .with_context(|| {
+            categorized(
+                FailureKind::QueryParse,
+                format!("Failed to parse query: {query}"),
+            )
+        })
+}
+
+/// Collects the indexed terms of `field` whose stem is in `wanted`, grouped by stem.
+fn expand_stems(
+    searcher: &Searcher,
+    field: Field,
+    stemmer: &mut TextAnalyzer,
+    wanted: &HashSet<String>,
+) -> Result<HashMap<String, BTreeSet<String>>> {
+    let mut matches: HashMap<String, BTreeSet<String>> = HashMap::new();
+
+    for segment_reader in searcher.segment_readers() {
+        let inverted_index = segment_reader
+            .inverted_index(field)
+            .context("Failed to open inverted index")?;
+        let mut terms = inverted_index
+            .terms()
+            .stream()
+            .context("Failed to stream term dictionary")?;
+
+        while terms.advance() {
+            let Ok(text) = std::str::from_utf8(terms.key()) else {
+                continue;
+            };
+            let stem = stem_word(stemmer, text);
+            if wanted.contains(&stem) {
+                let forms = matches.entry(stem).or_default();
+                if forms.len() < STEM_MAX_EXPANSIONS {
+                    forms.insert(text.to_string());
+                }
+            }
+        }
+    }
+
+    Ok(matches)
+}
+
+fn stem_analyzer(language: StemLanguage) -> TextAnalyzer {
+    TextAnalyzer::builder(RawTokenizer::default())
+        .filter(Stemmer::new(language.tantivy_language()))
+        .build()
+}
+
+/// Stems a single lowercase word.
+fn stem_word(stemmer: &mut TextAnalyzer, word: &str) -> String {
+    let mut stream = stemmer.token_stream(word);
+    match stream.next() {
+        Some(token) => token.text.clone(),
+        None => word.to_string(),
+    }
+}
+
+/// Rewrites each bare word that is a key of `synonyms` (case-insensitively) into an
+/// OR group with its synonyms, e.g. `+car` becomes `+(car OR automobile OR vehicle)`.
+/// Words missing from the map, and synonyms themselves, are not expanded.
+fn expand_synonyms(query: &str, synonyms: &BTreeMap<String, Vec<String>>) -> String {
+    let synonyms: HashMap<String, &Vec<String>> = synonyms
+        .iter()
+        .map(|(word, alternatives)| (word.to_lowercase(), alternatives))
+        .collect();
+
+    split_stemmable_words(query)
+        .into_iter()
+        .map(|(token, word)| {
+            let Some(alternatives) = word.as_ref().and_then(|word| synonyms.get(word)) else {
+                return token.to_string();
+            };
+            let prefix = &token[..token.len() - token.trim_start_matches(['+', '-']).len()];
+            let mut group = vec![token[prefix.len()..].to_string()];
+            for alternative in alternatives.iter() {
+                let alternative = alternative.replace('"', "");
+                let alternative = alternative.trim();
+                if alternative.chars().all(char::is_alphanumeric) {
+                    group.push(alternative.to_string());
+                } else if !alternative.is_empty() {
+                    // Multi-word or punctuated synonyms are matched as phrases.
+                    group.push(format!("\"{alternative}\""));
+                }
+            }
+            if group.len() == 1 {
+                return token.to_string();
+            }
+            format!("{prefix}({})", group.join(" OR "))
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Splits `query` on whitespace, pairing each token with its lowercase word when
+/// it is a bare word (optionally prefixed with `+` or `-`) that can be stemmed.
+/// Quoted phrases, `field:` terms, operators, and words with punctuation are
+/// passed through untouched.
+fn split_stemmable_words(query: &str) -> Vec<(&str, Option<String>)> {
+    let mut tokens = Vec::new();
+    let mut in_quotes = false;
+    let mut in_range = false;
+
+    for token in query.split_whitespace() {
+        let starts_in_quotes = in_quotes;
+        in_quotes ^= token.matches('"').count() % 2 == 1;
+        let starts_in_range = in_range;
+        in_range = range_open_after(token, in_range);
+
+        let body = token.trim_start_matches(['+', '-']);
+        let is_word = !starts_in_quotes
+            && !starts_in_range
+            && token.len() - body.len() <= 1
+            && !body.is_empty()
+            && body.chars().all(char::is_alphanumeric)
+            && !matches!(token, "AND" | "OR" | "NOT");
+        tokens.push((token, is_word.then(|| body.to_lowercase())));
+    }
+
+    tokens
+}
+
+/// Removes bare words shorter than `min_len` characters from `query`, for
+/// `--min-term-length`, returning the remaining query and the dropped words.
+/// Phrases, `field:` terms, and operators are always kept.
+fn drop_short_terms(query: &str, min_len: usize) -> (String, Vec<String>) {
+    let mut kept = Vec::new();
+    let mut dropped = Vec::new();
+    for (token, word) in split_stemmable_words(query) {
+        if word.is_some_and(|word| word.chars().count() < min_len) {
+            dropped.push(token.to_string());
+        } else {
+            kept.push(token);
+        }
+    }
+    (kept.join(" "), dropped)
+}
+
+/// Tracks whether a `[LOW TO HIGH]` range is still open after `token`, so its
+/// `TO` and `*` are never mistaken for words or wildcards.
+fn range_open_after(token: &str, in_range: bool) -> bool {
+    match token.rfind(['[', '{', ']', '}']) {
+        Some(index) => matches!(token.as_bytes()[index], b'[' | b'{'),
+        None => in_range,
+    }
+}
+
+/// Parses `query`, expanding bare terms containing `*` into the matching terms
+/// from the term dictionary of each default field.
+///
+/// Every wildcard term scans the full term dictionary of each default field, so
+/// wildcard searches get slower as the index grows. At most
+/// `WILDCARD_MAX_EXPANSIONS` terms are kept per pattern and field.
+fn build_wildcard_query(
+    query_parser: &QueryParser,
+    searcher: &Searcher,
+    query: &str,
+    fields: &[Field],
+) -> Result<Box<dyn Query>> {
+    let (rest, patterns) = split_wildcard_terms(query);
+    let mut clauses: Vec<(Occur, Box<dyn Query>)> = Vec::new();
+
+    if !rest.trim().is_empty() {
+        let parsed = query_parser.parse_query(&rest).with_context(|| {
+            categorized(
+                FailureKind::QueryParse,
+                format!("Failed to parse query: {rest}"),
+            )
+        })?;
+        clauses.push((Occur::Should, parsed));
+    }
+
+    for pattern in &patterns {
+        for &field in fields {
+            for term in expand_wildcard(searcher, field, pattern)? {
+                let term_query = TermQuery::new(term, IndexRecordOption::WithFreqs);
+                clauses.push((Occur::Should, Box::new(term_query)));
+            }
+        }
+    }
+
+    Ok(Box::new(BooleanQuery::new(clauses)))
+}
+
+fn expand_wildcard(searcher: &Searcher, field: Field, pattern: &str) -> Result<Vec<Term>> {
+    let mut matches = BTreeSet::new();
+
+    'segments: for segment_reader in searcher.segment_readers() {
+        let inverted_index = segment_reader
+            .inverted_index(field)
+            .context("Failed to open inverted index")?;
+        let mut terms = inverted_index
+            .terms()
+            .stream()
+            .context("Failed to stream term dictionary")?;
+
+        while terms.advance() {
+            let Ok(text) = std::str::from_utf8(terms.key()) else {
+                continue;
+            };
+            if wildcard_matches(pattern, text) {
+                matches.insert(text.to_string());
+                if matches.len() >= WILDCARD_MAX_EXPANSIONS {
+                    break 'segments;
+                }
+            }
+        }
+    }
+
+    Ok(matches
+        .into_iter()
+        .map(|text| Term::from_field_text(field, &text))
+        .collect())
+}
+
+/// Glob-style match where `*` matches any run of characters (including none).
+fn wildcard_matches(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut remaining) = text.strip_prefix(first) else {
+        return false;
+    };
+
+    let rest: Vec<&str> = parts.collect();
+    let Some((last, middle)) = rest.split_last() else {
+        // No `*` at all: the whole text must equal the pattern.
+        return remaining.is_empty();
+    };
+
+    for part in middle {
+        match remaining.find(part) {
+            Some(idx) => remaining = &remaining[idx + part.len()..],
+            None => return false,
+        }
+    }
+
+    remaining.ends_with(last)
+}
+
+/// Separates bare `*` terms from the rest of the query, returning the remaining
+/// query text and a lowercase glob pattern per wildcard term. Quoted phrases and
+/// fielded terms (`field:value`) are left for the query parser.
+fn split_wildcard_terms(query: &str) -> (String, Vec<String>) {
+    let mut rest = Vec::new();
+    let mut patterns = Vec::new();
+    let mut in_quotes = false;
+    let mut in_range = false;
+
+    for token in query.split_whitespace() {
+        let starts_in_quotes = in_quotes;
+        in_quotes ^= token.matches('"').count() % 2 == 1;
+        let starts_in_range = in_range;
+        in_range = range_open_after(token, in_range);
+
+        let is_wildcard = !starts_in_quotes
+            && !starts_in_range
+            && token.contains('*')
+            && !token.contains(['"', ':'])
+            && token.chars().any(|c| c != '*');
+        if is_wildcard {
+            // Indexed terms are lowercased by the default tokenizer.
+            patterns.push(token.to_lowercase());
+        } else {
+            rest.push(token);
+        }
+    }
+
+    (rest.join(" "), patterns)
+}
+
+/// One ranked result as presented to the user.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SearchHit {
+    rank: usize,
+    score: f32,
+    path: String,
+    relative_path: String,
+    /// Snippet with `<b>` highlight tags and HTML-escaped text, as produced by Tantivy
+    #[serde(
+        rename = "snippet",
+        serialize_with = "serialize_plain_snippet",
+        deserialize_with = "deserialize_plain_snippet"
+    )]
+    snippet_html: String,
+    /// Where the file resolved to, if it was indexed through a symlink
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    symlink_target: Option<String>,
+    /// All stored fields of the document, when `--fields-json` is given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    fields: Option<serde_json::Map<String, serde_json::Value>>,
+    /// File size in bytes, when `--format table` asks for it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    size: Option<u64>,
+    /// Modification time in Unix seconds, when `--format table` asks for it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    modified: Option<i64>,
+    /// Index directory the hit came from, for `search --index`, or root name for `--all-roots`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    index: Option<String>,
+    /// Language detected at indexing time (ISO 639-3, or `unknown`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    language: Option<String>,
+    /// 1-based line of the first query term in the file on disk, for `--line-numbers`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    line: Option<u64>,
+    /// `[start, end)` byte offsets of query terms in the indexed `contents`, for `--format json`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    match_spans: Option<Vec<(usize, usize)>>,
+}
+
+fn serialize_plain_snippet<S: serde::Serializer>(
+    html: &str,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&plain_snippet(html))
+}
+
+/// Reads a plain snippet (as sent by `serve`) back into escaped HTML without highlights.
+fn deserialize_plain_snippet<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<String, D::Error> {
+    let plain = String::deserialize(deserializer)?;
+    Ok(encode_text(&plain).into_owned())
+}
+
+fn write_results(args: &SearchArgs, query: &str, results: &[SearchHit]) -> Result<()> {
+    let mut out = open_results_output(args)?;
+    let color = args.output.is_none() && stdout_color();
+    let markers = search_highlight_markers(args, color);
+    // Machine-readable snippets stay plain unless markers were asked for explicitly.
+    let custom_markers = search_highlight_markers(args, false);
+
+    match args.format {
+        OutputFormat::Text => {
+            // Path-only listings stay compact by default; richer result blocks get a blank line.
+            let compact = args.no_snippet
+                && args.preview_lines.is_none()
+                && !args.fields_json
+                && !args.highlight_only;
+            let separator = match args.separator.as_deref() {
+                Some("") => None,
+                Some(custom) => Some(custom),
+                None => (!compact).then_some(""),
+            };
+
+            // Preview lines are highlighted like snippets, so both views read the same way.
+            let highlight_terms = if args.highlight_only || args.preview_lines.is_some() {
+                query_highlight_terms(query)
+            } else {
+                HashSet::new()
+            };
+
+            if !args.no_header {
+                writeln!(out, "Results for query: {query}")?;
+            }
+            for (index, result) in results.iter().enumerate() {
+                if let Some(separator) = separator.filter(|_| index > 0) {
+                    writeln!(out, "{separator}")?;
+                }
+                write_hit_line(&mut out, result)?;
+                if args.verbose {
+                    let language = result.language.as_deref().unwrap_or(UNKNOWN_LANGUAGE);
+                    writeln!(out, "      language: {language}")?;
+                }
+                if args.highlight_only {
+                    write_matching_lines(
+                        &mut out,
+                        Path::new(&result.path),
+                        &highlight_terms,
+                        args.max_matches_per_file,
+                        markers,
+                    )?;
+                } else if !args.no_snippet {
+                    let snippet = highlight_snippet(&result.snippet_html, markers);
+                    writeln!(out, "      {snippet}")?;
+                }
+                if let Some(fields) = &result.fields {
+                    let json = serde_json::to_string_pretty(fields)
+                        .context("Failed to serialize stored fields to JSON")?;
+                    for line in json.lines() {
+                        writeln!(out, "      {line}")?;
+                    }
+                }
+                if let Some(lines) = args.preview_lines.filter(|&n| n > 0) {
+                    write_tail_preview(
+                        &mut out,
+                        Path::new(&result.path),
+                        lines,
+                        &highlight_terms,
+                        markers,
+                    )?;
+                }
+            }
+        }
+        OutputFormat::Json => {
+            let mut json =
+                serde_json::to_value(results).context("Failed to serialize results to JSON")?;
+            if let (Some(markers), Some(items)) = (custom_markers, json.as_array_mut()) {
+                for (item, result) in items.iter_mut().zip(results) {
+                    item["snippet"] = highlight_snippet(&result.snippet_html, Some(markers)).into();
+                }
+            }
+            serde_json::to_writer_pretty(&mut out, &json)
+                .context("Failed to serialize results to JSON")?;
+            writeln!(out)?;
+        }
+        OutputFormat::Csv => write_csv_results(&mut out, results, custom_markers)?,
+        OutputFormat::Null => {
+            for result in results {
+                out.write_all(result.path.as_bytes())?;
+                out.write_all(b"\0")?;
+            }
+        }
+        OutputFormat::Table => {
+            // Files are not fitted to the terminal.
+            let width = args.output.is_none().then(terminal_width).flatten();
+            write_table_results(&mut out, results, !args.no_header, width)?;
+        }
+    }
+
+    finish_results_output(args, out, results.len())
+}
+
+fn terminal_width() -> Option<usize> {
+    Table::new().width().map(usize::from).or_else(|| {
+        std::env::var("COLUMNS")
+            .ok()
+            .and_then(|columns| columns.trim().parse().ok())
+    })
+}
+
+/// Stdout, or the `--output` file (created along with any missing parent directories).
+fn open_results_output(args: &SearchArgs) -> Result<Box<dyn Write>> {
+    let Some(path) = &args.output else {
+        return Ok(Box::new(io::stdout().lock()));
+    };
+    if let Some(parent) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory {}", parent.display()))?;
+    }
+    let file = fs::File::create(path)
+        .with_context(|| format!("Failed to create output file {}", path.display()))?;
+    Ok(Box::new(io::BufWriter::new(file)))
+}
+
+/// Flushes the results and, for `--output`, reports how many were written.
+fn finish_results_output(args: &SearchArgs, mut out: Box<dyn Write>, count: usize) -> Result<()> {
+    out.flush()?;
+    if let Some(path) = &args.output {
+        eprintln!("Wrote {count} result(s) to {}", path.display());
+    }
+    Ok(())
+}
+
+/// Narrowest the path column gets before the table is allowed to overflow the terminal.
+const TABLE_MIN_PATH_CHARS: usize = 12;
+
+/// Writes `--format table` output. Snippets are left out; when `width` is known (the
+/// terminal's, or `$COLUMNS`), paths are shortened in the middle so rows fit on one line.
+fn write_table_results(
+    out: &mut impl Write,
+    results: &[SearchHit],
+    header: bool,
+    width: Option<usize>,
+) -> Result<()> {
+    const HEADERS: [&str; 5] = ["#", "Score", "Size", "Modified", "Path"];
+
+    let rows: Vec<[String; 4]> = results
+        .iter()
+        .map(|result| {
+            [
+                result.rank.to_string(),
+                format!("{:.3}", result.score),
+                result.size.map(format_size).unwrap_or_else(|| "-".into()),
+                result
+                    .modified
+                    .and_then(|secs| chrono::DateTime::from_timestamp(secs, 0))
+                    .map(|date| {
+                        date.with_timezone(&chrono::Local)
+                            .format("%Y-%m-%d %H:%M")
+                            .to_string()
+                    })
+                    .unwrap_or_else(|| "-".into()),
+            ]
+        })
+        .collect();
+
+    // Each column has a border to its left and one space of padding on either side,
+    // plus the closing border on the right.
+    let path_chars = width.map(|width| {
+        let fixed: usize = (0..4)
+            .map(|column| {
+                rows.iter()
+                    .map(|row| row[column].chars().count())
+                    .chain(header.then(|| HEADERS[column].chars().count()))
+                    .max()
+                    .unwrap_or(0)
+            })
+            .sum();
+        let overhead = HEADERS.len() * 3 + 1;
+        width
+            .saturating_sub(fixed + overhead)
+            .max(TABLE_MIN_PATH_CHARS)
+    });
+
+    let mut table = Table::new();
+    table
+        .load_preset(comfy_table::presets::UTF8_FULL_CONDENSED)
+        .set_content_arrangement(ContentArrangement::Disabled);
+    if header {
+        table.set_header(HEADERS);
+    }
+    for (row, result) in rows.into_iter().zip(results) {
+        let path = match path_chars {
+            Some(max) => truncate_middle(&result.relative_path, max),
+            None => result.relative_path.clone(),
+        };
+        let [rank, score, size, modified] = row;
+        table.add_row([rank, score, size, modified, path]);
+    }
+    for column in 0..3 {
+        if let Some(column) = table.column_mut(column) {
+            column.set_cell_alignment(CellAlignment::Right);
+        }
+    }
+
+    writeln!(out, "{table}")?;
+    Ok(())
+}
+
+/// Shortens `text` to at most `max_chars` characters by replacing its middle with `…`.
+/// When the split is uneven, the end (usually the file name) keeps the extra character.
+fn truncate_middle(text: &str, max_chars: usize) -> String {
+    let count = text.chars().count();
+    if count <= max_chars {
+        return text.to_string();
+    }
+    let Some(keep) = max_chars.checked_sub(1) else {
+        return String::new();
+    };
+    let head = keep / 2;
+    let tail = keep - head;
+    let mut shortened: String = text.chars().take(head).collect();
+    shortened.push('…');
+    shortened.extend(text.chars().skip(count - tail));
+    shortened
+}
+
+/// Writes the `rank. [score] path` line that starts each text result.
+fn write_hit_line(out: &mut impl Write, result: &SearchHit) -> Result<()> {
+    let via = result
+        .symlink_target
+        .as_deref()
+        .map(|target| format!(" (via symlink → {target})"))
+        .unwrap_or_default();
+    let line = result
+        .line
+        .map(|line| format!(":{line}"))
+        .unwrap_or_default();
+    writeln!(
+        out,
+        "{:>2}. [score: {:.3}] {}{line}{via}",
+        result.rank, result.score, result.relative_path
+    )?;
+    Ok(())
+}
+
+fn write_csv_results(
+    out: impl Write,
+    results: &[SearchHit],
+    markers: Option<HighlightMarkers>,
+) -> Result<()> {
+    let mut writer = csv::Writer::from_writer(out);
+    writer.write_record(["rank", "score", "path", "relative_path", "snippet"])?;
+    for result in results {
+        writer.write_record([
+            result.rank.to_string(),
+            format!("{:.3}", result.score),
+            result.path.clone(),
+            result.relative_path.clone(),
+            highlight_snippet(&result.snippet_html, markers),
+        ])?;
+    }
+    writer.flush().context("Failed to write CSV results")?;
+    Ok(())
+}
+
+/// Collects every stored field of `doc` by name, truncating long text so whole files are not dumped.
+/// Single-valued fields become plain JSON values; multi-valued fields become arrays.
+fn stored_fields_json(
+    schema: &Schema,
+    doc: &TantivyDocument,
+) -> serde_json::Map<String, serde_json::Value> {
+    doc.to_named_doc(schema)
+        .0
+        .into_iter()
+        .map(|(name, values)| {
+            let mut values: Vec<serde_json::Value> = values
+                .into_iter()
+                .map(|value| match value {
+                    OwnedValue::Str(text) => {
+                        serde_json::Value::String(truncate_chars(&text, FIELDS_JSON_MAX_CHARS))
+                    }
+                    other => serde_json::to_value(other).unwrap_or(serde_json::Value::Null),
+                })
+                .collect();
+            let value = if values.len() == 1 {
+                values.remove(0)
+            } else {
+                serde_json::Value::Array(values)
+            };
+            (name, value)
+        })
+        .collect()
+}
+
+fn truncate_chars(text: &str, max_chars: usize) -> String {
+    match text.char_indices().nth(max_chars) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text.to_string(),
+    }
+}
+
+/// Reports why there are no results: as plain text for humans, or as an empty
+/// result set (with the message on stderr) so machine-readable output stays valid.
+fn finish_without_results(args: &SearchArgs, message: &str) -> Result<()> {
+    if args.format == OutputFormat::Text {
+        let mut out = open_results_output(args)?;
+        writeln!(out, "{message}")?;
+        return finish_results_output(args, out, 0);
+    }
+
+    eprintln!("{message}");
+    write_results(args, "", &[])
+}
+
+/// Indexed forms of the `contents` terms in `query`, as `--highlight-only` looks for them
+/// in each line. Expansions from `--wildcard`, `--stem-language`, and `--synonyms` are
+/// not included.
+fn query_highlight_terms(query: &str) -> HashSet<String> {
+    let schema = build_schema(&ContentsAnalysis::default());
+    let contents_field = schema.get_field("contents").expect("contents field");
+    let parser = QueryParser::new(schema, vec![contents_field], TokenizerManager::default());
+    let Ok(parsed) = translate_field_ranges(query).and_then(|text| Ok(parser.parse_query(&text)?))
+    else {
+        return HashSet::new();
+    };
+
+    let mut terms = HashSet::new();
+    parsed.query_terms(&mut |term, _| {
+        if term.field() == contents_field {
+            if let Some(text) = term.value().as_str() {
+                terms.insert(text.to_string());
+            }
+        }
+    });
+    terms
+}
+
+/// Locates the query's `contents` (or `code`) terms in stored documents for `match_spans`,
+/// using the index's own tokenizers so stop words and the token length limit match
+/// indexing. Expansions the query does not list as terms (wildcards, fuzzy matches) are
+/// not found.
+struct MatchSpanFinder {
+    /// `contents`, then `code` when the schema has it, each with its tokenizer and terms
+    fields: Vec<(Field, TextAnalyzer, HashSet<String>)>,
+}
+
+impl MatchSpanFinder {
+    fn new(index: &Index, contents_field: Field, query: &dyn Query) -> Result<Self> {
+        let mut fields = Vec::new();
+        let code_field = index.schema().get_field("code").ok();
+        for field in std::iter::once(contents_field).chain(code_field) {
+            let analyzer = index
+                .tokenizer_for_field(field)
+                .context("Failed to load the contents tokenizer")?;
+            let mut terms = HashSet::new();
+            query.query_terms(&mut |term, _| {
+                if term.field() == field {
+                    if let Some(text) = term.value().as_str() {
+                        terms.insert(text.to_string());
+                    }
+                }
+            });
+            fields.push((field, analyzer, terms));
+        }
+        Ok(Self { fields })
+    }
+
+    /// Up to `MAX_MATCH_SPANS` spans, in document order.
+    fn spans(&mut self, doc: &TantivyDocument) -> Vec<(usize, usize)> {
+        let Some((analyzer, terms, contents)) =
+            self.fields.iter_mut().find_map(|(field, analyzer, terms)| {
+                let text = doc.get_first(*field).and_then(|value| value.as_str())?;
+                Some((analyzer, &*terms, text))
+            })
+        else {
+            return Vec::new();
+        };
+        let mut spans = matching_term_ranges(analyzer, contents, terms);
+        spans.truncate(MAX_MATCH_SPANS);
+        spans
+            .into_iter()
+            .map(|range| (range.start, range.end))
+            .collect()
+    }
+}
+
+/// Byte ranges of the words in `line` that tokenize to one of `terms`.
+fn matching_term_ranges(
+    analyzer: &mut TextAnalyzer,
+    line: &str,
+    terms: &HashSet<String>,
+) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut stream = analyzer.token_stream(line);
+    while stream.advance() {
+        let token = stream.token();
+        if terms.contains(&token.text) {
+            ranges.push(token.offset_from..token.offset_to);
+        }
+    }
+    ranges
+}
+
+/// Line number of the first line of `path` containing one of `terms`, matched as
+/// `--highlight-only` does. `None` when no line matches or the file can no longer be read,
+/// or cannot be located because its path is relative.
+fn first_matching_line(path: &Path, terms: &HashSet<String>) -> Option<u64> {
+    if path.is_relative() {
+        return None;
+    }
+    let file = fs::File::open(path).ok()?;
+    let mut analyzer = TokenizerManager::default()
+        .get("default")
+        .expect("default tokenizer");
+    for (index, line) in BufReader::new(file).split(b'\n').enumerate() {
+        let line = String::from_utf8_lossy(&line.ok()?).into_owned();
+        let line = truncate_chars(&line, HIGHLIGHT_LINE_MAX_CHARS);
+        if !matching_term_ranges(&mut analyzer, &line, terms).is_empty() {
+            return Some(index as u64 + 1);
+        }
+    }
+    None
+}
+
+/// Prints up to `max_lines` lines of `path` that contain a query term, with line numbers
+/// and the terms highlighted, like `grep -n`.
+fn write_matching_lines(
+    out: &mut impl Write,
+    path: &Path,
+    terms: &HashSet<String>,
+    max_lines: usize,
+    markers: Option<HighlightMarkers>,
+) -> Result<()> {
+    if path.is_relative() {
+        writeln!(out, "      (lines unavailable: {UNKNOWN_ROOT_NOTE})")?;
+        return Ok(());
+    }
+    let file = match fs::File::open(path) {
+        Ok(file) => file,
+        Err(e) => {
+            writeln!(out, "      (lines unavailable: {e})")?;
+            return Ok(());
+        }
+    };
+    let mut analyzer = TokenizerManager::default()
+        .get("default")
+        .expect("default tokenizer");
+
+    let mut shown = 0;
+    let mut more = 0;
+    for (index, line) in BufReader::new(file).split(b'\n').enumerate() {
+        let line = match line {
+            Ok(bytes) => String::from_utf8_lossy(&bytes)
+                .trim_end_matches('\r')
+                .to_string(),
+            Err(e) => {
+                writeln!(out, "      (stopped reading: {e})")?;
+                break;
+            }
+        };
+        let line = truncate_chars(&line, HIGHLIGHT_LINE_MAX_CHARS);
+        let ranges = matching_term_ranges(&mut analyzer, &line, terms);
+        if ranges.is_empty() {
+            continue;
+        }
+        if shown == max_lines {
+            more += 1;
+            continue;
+        }
+        shown += 1;
+        let html = render_highlights(&line, &ranges);
+        writeln!(
+            out,
+            "      {:>4}: {}",
+            index + 1,
+            highlight_snippet(&html, markers)
+        )?;
+    }
+
+    if more > 0 {
+        writeln!(out, "      … {more} more matching line(s)")?;
+    } else if shown == 0 {
+        writeln!(
+            out,
+            "      (no matching lines; the match is in the path or indexed text)"
+        )?;
+    }
+    Ok(())
+}
+
+fn write_tail_preview(
+    out: &mut impl Write,
+    path: &Path,
+    lines: usize,
+    terms: &HashSet<String>,
+    markers: Option<HighlightMarkers>,
+) -> Result<()> {
+    if path.is_relative() {
+        writeln!(out, "      (preview unavailable: {UNKNOWN_ROOT_NOTE})")?;
+        return Ok(());
+    }
+    match read_tail_lines(path, lines) {
+        Ok(tail) => {
+            writeln!(out, "      --- last {} line(s) ---", tail.len())?;
+            let mut analyzer = TokenizerManager::default()
+                .get("default")
+                .expect("default tokenizer");
+            for line in tail {
+                let ranges = match markers {
+                    Some(_) => matching_term_ranges(&mut analyzer, &line, terms),
+                    None => Vec::new(),
+                };
+                if ranges.is_empty() {
+                    writeln!(out, "      {line}")?;
+                } else {
+                    let html = render_highlights(&line, &ranges);
+                    writeln!(out, "      {}", highlight_snippet(&html, markers))?;
+                }
+            }
+        }
+        Err(e) => writeln!(out, "      (preview unavailable: {e})")?,
+    }
+    Ok(())
+}
+
+/// Returns up to `count` trailing lines, reading at most `PREVIEW_TAIL_MAX_BYTES` from the end of the file.
+fn read_tail_lines(path: &Path, count: usize) -> Result<Vec<String>> {
+    let mut file =
+        fs::File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let len = file
+        .metadata()
+        .with_context(|| format!("Failed to read metadata for {}", path.display()))?
+        .len();
+    let start = len.saturating_sub(PREVIEW_TAIL_MAX_BYTES);
+    file.seek(SeekFrom::Start(start))
+        .with_context(|| format!("Failed to seek in {}", path.display()))?;
+
+    let mut buf = Vec::new();
+    file.read_to_end(&mut buf)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let text = String::from_utf8_lossy(&buf);
+
+    let mut lines: Vec<&str> = text.lines().collect();
+    if start > 0 && !lines.is_empty() {
+        // The first line is probably cut off by the read window.
+        lines.remove(0);
+    }
+
+    let skip = lines.len().saturating_sub(count);
+    Ok(lines[skip..].iter().map(|l| l.to_string()).collect())
+}
+
+/// A search hit with its stored document loaded, ready for ordering and display.
+struct RankedDoc {
+    score: f32,
+    path: String,
+    /// Line count, or 0 for indexes built before it was recorded
+    lines: u64,
+    doc: TantivyDocument,
+}
+
+/// Orders hits deterministically: equal scores (common for short files) fall back to path order.
+fn sort_hits(hits: &mut [RankedDoc], order: SortOrder) {
+    match order {
+        SortOrder::Score => hits.sort_by(|a, b| {
+            b.score
+                .total_cmp(&a.score)
+                .then_with(|| a.path.cmp(&b.path))
+        }),
+        SortOrder::Path => hits.sort_by(|a, b| {
+            a.path
+                .cmp(&b.path)
+                .then_with(|| b.score.total_cmp(&a.score))
+        }),
+        SortOrder::Lines => hits.sort_by(|a, b| {
+            b.lines
+                .cmp(&a.lines)
+                .then_with(|| b.score.total_cmp(&a.score))
+                .then_with(|| a.path.cmp(&b.path))
+        }),
+    }
+}
+
+/// Returns the query text from the argument, stdin (`-`), or `--query-file`.
+fn resolve_query(query: Option<&str>, query_file: Option<&Path>) -> Result<String> {
+    let raw = match (query, query_file) {
+        (_, Some(file)) => fs::read_to_string(file)
+            .with_context(|| format!("Failed to read query file {}", file.display()))?,
+        (Some("-"), None) => {
+            let mut buf = String::new();
+            io::stdin()
+                .read_to_string(&mut buf)
+                .context("Failed to read query from stdin")?;
+            buf
+        }
+        (Some(query), None) => query.to_string(),
+        (None, None) => anyhow::bail!("No search query provided"),
+    };
+
+    let trimmed = raw.trim();
+    if trimmed.is_empty() {
+        anyhow::bail!("Search query is empty");
+    }
+
+    Ok(trimmed.to_string())
+}
+
+/// Builds `--any-of`'s OR group over `contents` from a keyword file: one term per line,
+/// skipping blank lines and `#` comments. Multi-word or punctuated lines match as phrases.
+fn any_of_query(path: &Path) -> Result<String> {
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read keyword file {}", path.display()))?;
+    let terms: Vec<String> = text
+        .lines()
+        .map(|line| line.replace('"', ""))
+        .map(|line| line.trim().to_string())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|term| {
+            if term.chars().all(char::is_alphanumeric) {
+                format!("contents:{term}")
+            } else {
+                format!("contents:\"{term}\"")
+            }
+        })
+        .collect();
+    if terms.is_empty() {
+        anyhow::bail!("The keyword file {} lists no terms", path.display());
+    }
+    Ok(format!("({})", terms.join(" OR ")))
+}
+
+/// Outcome of reading one line at the `Open #:` prompt.
+#[derive(Debug, PartialEq, Eq)]
+enum Selection {
+    Quit,
+    Open(usize),
+    Invalid,
+}
+
+/// Parses a result number as printed (starting at `first_rank`, which is past 1 when
+/// `--offset` skips results), returning the 0-based index into the results.
+fn parse_selection(input: &str, first_rank: usize, result_count: usize) -> Selection {
+    let trimmed = input.trim();
+    if trimmed.is_empty() || trimmed.eq_ignore_ascii_case("q") {
+        return Selection::Quit;
+    }
+
+    match trimmed.parse::<usize>() {
+        Ok(n) if n >= first_rank && n - first_rank < result_count => {
+            Selection::Open(n - first_rank)
+        }
+        _ => Selection::Invalid,
+    }
+}
+
+fn prompt_and_open(paths: &[PathBuf], first_rank: usize) -> Result<()> {
+    let stdin = io::stdin();
+    let mut input = String::new();
+
+    loop {
+        print!("Open #: ");
+        io::stdout().flush().context("Failed to flush stdout")?;
+
+        input.clear();
+        let bytes_read = stdin
+            .lock()
+            .read_line(&mut input)
+            .context("Failed to read selection from stdin")?;
+        if bytes_read == 0 {
+            return Ok(());
+        }
+
+        match parse_selection(&input, first_rank, paths.len()) {
+            Selection::Quit => return Ok(()),
+            Selection::Open(idx) => {
+                if let Err(e) = open_in_editor(&paths[idx]) {
+                    eprintln!("  [warn] {e:#}");
+                }
+            }
+            Selection::Invalid => {
+                println!(
+                    "Enter a result number between {first_rank} and {}, or press Enter / q to quit.",
+                    first_rank + paths.len() - 1
+                );
+            }
+        }
+    }
+}
+
+/// Launches `$VISUAL` (or `$EDITOR`) on the given path and waits for it to exit.
+fn open_in_editor(path: &Path) -> Result<()> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .ok()
+        .filter(|value| !value.trim().is_empty())
+        .context("Neither $VISUAL nor $EDITOR is set; cannot open result")?;
+
+    // Editors are often configured with arguments, e.g. `code --wait`.
+    let mut parts = editor.split_whitespace();
+    let program = parts.next().expect("editor command is non-empty");
+
+    let status = process::Command::new(program)
+        .args(parts)
+        .arg(path)
+        .status()
+        .with_context(|| format!("Failed to launch editor `{editor}`"))?;
+
+    if !status.success() {
+        anyhow::bail!("Editor `{editor}` exited with {status}");
+    }
+
+    Ok(())
+}
+
+/// Results for one line of a `batch` queries file.
+struct BatchResult {
+    query: String,
+    results: Vec<SearchHit>,
+}
+
+/// Serializes batch results as a JSON object keyed by query, in queries-file order.
+struct BatchJson<'a>(&'a [BatchResult]);
+
+impl Serialize for BatchJson<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.0.iter().map(|batch| (&batch.query, &batch.results)))
+    }
+}
+
+fn cmd_batch(args: &BatchArgs) -> Result<()> {
+    if args.limit == 0 {
+        anyhow::bail!("--limit must be at least 1");
+    }
+    if args.limit > MAX_RESULT_WINDOW {
+        anyhow::bail!("--limit must be at most {MAX_RESULT_WINDOW}");
+    }
+    if matches!(
+        args.format,
+        OutputFormat::Csv | OutputFormat::Null | OutputFormat::Table
+    ) {
+        anyhow::bail!("batch supports --format text or json");
+    }
+
+    let text = fs::read_to_string(&args.queries_file).with_context(|| {
+        format!(
+            "Failed to read queries file {}",
+            args.queries_file.display()
+        )
+    })?;
+    let queries = parse_batch_queries(&text);
+
+    let state = ServerState::open()?;
+    let searcher = state.reader.searcher();
+    let root = PathBuf::from(&state.cfg.root);
+
+    let mut batches = Vec::with_capacity(queries.len());
+    for (line, query) in queries {
+        let request = SearchRequest {
+            query: query.clone(),
+            limit: args.limit,
+            offset: 0,
+            wildcard: false,
+            stem_language: None,
+            synonyms: false,
+            whole_word: false,
+            search_fields: Vec::new(),
+            field_boosts: BTreeMap::new(),
+            relevance: None,
+            sort: args.sort,
+            recency_boost: false,
+            recency_half_life: None,
+            owner: None,
+            mode: None,
+            exclude_path: Vec::new(),
+            limit_per_dir: None,
+            language: None,
+            min_lines: None,
+            max_lines: None,
+            modified_within: None,
+            since: None,
+            as_of_generation: None,
+            no_snippet: args.no_snippet,
+            fields_json: false,
+            file_details: false,
+            match_spans: false,
+            near_file: None,
+        };
+        let results = execute_search(&state.index, &searcher, &state.cfg, &request, &root)
+            .with_context(|| format!("{}:{line}", args.queries_file.display()))?;
+        batches.push(BatchResult { query, results });
+    }
+
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    if args.format == OutputFormat::Json {
+        serde_json::to_writer_pretty(&mut out, &BatchJson(&batches))
+            .context("Failed to serialize results to JSON")?;
+        writeln!(out)?;
+        return Ok(());
+    }
+
+    for (index, batch) in batches.iter().enumerate() {
+        if index > 0 {
+            writeln!(out)?;
+        }
+        writeln!(out, "Results for query: {}", batch.query)?;
+        if batch.results.is_empty() {
+            writeln!(out, "    No results.")?;
+        }
+        for result in &batch.results {
+            write_hit_line(&mut out, result)?;
+            if !args.no_snippet {
+                let markers = stdout_color().then_some(ANSI_HIGHLIGHT);
+                let snippet = highlight_snippet(&result.snippet_html, markers);
+                writeln!(out, "      {snippet}")?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the queries in a `batch` file with their 1-based line numbers, skipping
+/// blank lines, `#` comments, and repeats of an earlier query.
+fn parse_batch_queries(text: &str) -> Vec<(usize, String)> {
+    let mut seen = HashSet::new();
+    text.lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .filter(|(_, line)| seen.insert(*line))
+        .map(|(line, query)| (line, query.to_string()))
+        .collect()
+}
+
+fn cmd_explain_query(
+    query: &str,
+    wildcard: bool,
+    stem_language: Option<StemLanguage>,
+    synonyms: bool,
+) -> Result<()> {
+    let state = ServerState::open()?;
+    let searcher = state.reader.searcher();
+    let request = SearchRequest {
+        wildcard,
+        stem_language,
+        synonyms,
+        ..query_only_request(query)
+    };
+    let request = apply_relevance_profile(&state.cfg, &request)?;
+    let parsed = build_search_query(&state.index, &searcher, &state.cfg, &request)?;
+
+    println!("Query: {query}");
+    let mut lines = Vec::new();
+    describe_query(parsed.as_ref(), &state.index.schema(), 0, &mut lines);
+    for line in lines {
+        println!("{line}");
+    }
+    println!();
+    println!("should = optional, at least one must match when a group has no `must` clause (OR)");
+    println!("must = required (`+term` or AND), must not = excluded (`-term` or NOT)");
+
+    Ok(())
+}
+
+/// Parses `query` the way `search` would and reports only whether that failed.
+fn cmd_validate_query(query: &str, verbose: bool) -> Result<()> {
+    let state = ServerState::open()?;
+    let searcher = state.reader.searcher();
+    let request = query_only_request(query);
+    let request = apply_relevance_profile(&state.cfg, &request)?;
+    build_search_query(&state.index, &searcher, &state.cfg, &request)?;
+    if verbose {
+        println!("Query is valid: {query}");
+    }
+    Ok(())
+}
+
+/// Request for `query` with every filter and expansion off, as used by `explain-query` and `validate-query`.
+fn query_only_request(query: &str) -> SearchRequest {
+    SearchRequest {
+        query: query.to_string(),
+        limit: TOP_RESULTS,
+        offset: 0,
+        wildcard: false,
+        stem_language: None,
+        synonyms: false,
+        whole_word: false,
+        search_fields: Vec::new(),
+        field_boosts: BTreeMap::new(),
+        relevance: None,
+        sort: SortOrder::Score,
+        recency_boost: false,
+        recency_half_life: None,
+        owner: None,
+        mode: None,
+        exclude_path: Vec::new(),
+        limit_per_dir: None,
+        language: None,
+        min_lines: None,
+        max_lines: None,
+        modified_within: None,
+        since: None,
+        as_of_generation: None,
+        no_snippet: true,
+        fields_json: false,
+        file_details: false,
+        match_spans: false,
+        near_file: None,
+    }
+}
+
+/// Renders `query` as an indented tree, one clause per line. Query types without
+/// public accessors (ranges, boosts) fall back to their `Debug` output.
+fn describe_query(query: &dyn Query, schema: &Schema, depth: usize, lines: &mut Vec<String>) {
+    let indent = "  ".repeat(depth);
+
+    if let Some(boolean) = query.downcast_ref::<BooleanQuery>() {
+        lines.push(format!("{indent}boolean"));
+        for (occur, clause) in boolean.clauses() {
+            let occur = match occur {
+                Occur::Should => "should",
+                Occur::Must => "must",
+                Occur::MustNot => "must not",
+            };
+            let mut clause_lines = Vec::new();
+            describe_query(clause.as_ref(), schema, depth + 1, &mut clause_lines);
+            if let Some(first) = clause_lines.first_mut() {
+                let text = first.trim_start().to_string();
+                *first = format!("{indent}  {occur}: {text}");
+            }
+            lines.extend(clause_lines);
+        }
+    } else if let Some(term_query) = query.downcast_ref::<TermQuery>() {
+        lines.push(format!(
+            "{indent}term {}",
+            describe_term(schema, term_query.term())
+        ));
+    } else if let Some(phrase) = query.downcast_ref::<PhraseQuery>() {
+        let words: Vec<String> = phrase
+            .phrase_terms()
+            .iter()
+            .map(|term| term.value().as_str().unwrap_or("?").to_string())
+            .collect();
+        lines.push(format!(
+            "{indent}phrase {}:\"{}\"",
+            schema.get_field_name(phrase.field()),
+            words.join(" ")
+        ));
+    } else if let Some(range) = query.downcast_ref::<RangeQuery>() {
+        let debug = format!("{range:?}");
+        let bounds = describe_range_bounds(&debug).unwrap_or(debug);
+        lines.push(format!(
+            "{indent}range {}: {bounds}",
+            schema.get_field_name(range.field())
+        ));
+    } else {
+        lines.push(format!("{indent}{query:?}"));
+    }
+}
+
+/// Turns the bounds in a `RangeQuery`'s `Debug` output (its only view of them) into
+/// `>= LOW, < HIGH`. Returns `None` if the format is not recognized.
+fn describe_range_bounds(debug: &str) -> Option<String> {
+    let bound = |label: &str, inclusive: &str, exclusive: &str| -> Option<Option<String>> {
+        let rest = &debug[debug.find(label)? + label.len()..];
+        if rest.starts_with("Unbounded") {
+            return Some(None);
+        }
+        let op = if rest.starts_with("Included(") {
+            inclusive
+        } else if rest.starts_with("Excluded(") {
+            exclusive
+        } else {
+            return None;
+        };
+        let after_type = &rest[rest.find("type=")?..];
+        let value_start = after_type.find(", ")? + 2;
+        let value = &after_type[value_start..value_start + after_type[value_start..].find(')')?];
+        Some(Some(format!("{op} {value}")))
+    };
+
+    let parts: Vec<String> = [
+        bound("lower_bound: ", ">=", ">")?,
+        bound("upper_bound: ", "<=", "<")?,
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    Some(if parts.is_empty() {
+        "any value".to_string()
+    } else {
+        parts.join(", ")
+    })
+}
+
+fn describe_term(schema: &Schema, term: &Term) -> String {
+    let field = schema.get_field_name(term.field());
+    let value = term.value();
+    if let Some(text) = value.as_str() {
+        format!("{field}:{text:?}")
+    } else if let Some(number) = value.as_u64() {
+        format!("{field}:{number}")
+    } else {
+        format!("{term:?}")
+    }
+}
+
+fn cmd_compact() -> Result<()> {
+    let cfg = load_config()?;
+    let index_dir = Path::new(&cfg.index_dir);
+
+    if !tantivy_index_exists(index_dir) {
+        return Err(categorized(
+            FailureKind::IndexMissing,
+            format!(
+                "Index missing at {}. Re-run `vaultsearch init` to recreate it.",
+                index_dir.display()
+            ),
+        )
+        .into());
+    }
+
+    let index = open_index(index_dir, &cfg.analysis)?;
+    // Only keep ids: Tantivy treats files of any `SegmentMeta` still alive as in use, which
+    // would stop the merged-away segments from being garbage collected below.
+    let (segment_ids, has_deletes) = {
+        let segments = index
+            .searchable_segment_metas()
+            .context("Failed to read index segments")?;
+        let has_deletes = segments.iter().any(|meta| meta.has_deletes());
+        let ids: Vec<_> = segments.iter().map(|meta| meta.id()).collect();
+        (ids, has_deletes)
+    };
+    let size_before = index_size_bytes(index_dir)?;
+
+    // Creating the writer takes Tantivy's writer lock, so a concurrent `index` run cannot interleave.
+    let mut writer: IndexWriter = index.writer(INDEX_WRITER_HEAP_BYTES).context(
+        "Failed to lock the index for writing. Is another vaultsearch command updating it?",
+    )?;
+
+    // A single segment is still rewritten when it holds deletes, since merging purges them.
+    let needs_merge = segment_ids.len() > 1 || has_deletes;
+    if needs_merge {
+        writer
+            .merge(&segment_ids)
+            .wait()
+            .context("Failed to merge index segments")?;
+    }
+    writer
+        .garbage_collect_files()
+        .wait()
+        .context("Failed to remove obsolete index files")?;
+    writer
+        .wait_merging_threads()
+        .context("Failed to finish index merges")?;
+
+    let segments_after = index
+        .searchable_segment_ids()
+        .context("Failed to read index segments")?
+        .len();
+    let size_after = index_size_bytes(index_dir)?;
+
+    if needs_merge {
+        println!("Compaction complete.");
+    } else {
+        println!("Index is already compact.");
+    }
+    println!("  Index directory: {}", index_dir.display());
+    println!(
+        "  Segments       : {} -> {segments_after}",
+        segment_ids.len()
+    );
+    println!(
+        "  Size on disk   : {} -> {} ({} reclaimed)",
+        format_size(size_before),
+        format_size(size_after),
+        format_size(size_before.saturating_sub(size_after))
+    );
+
+    Ok(())
+}
+
+/// Contents of the index directory at one point in time, for `vaultsearch manifest`.
+#[derive(Debug, Serialize, Deserialize)]
+struct IndexManifest {
+    index_dir: String,
+    created: String,
+    files: Vec<ManifestEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct ManifestEntry {
+    /// File name relative to the index directory
+    path: String,
+    size: u64,
+    /// BLAKE3 hash of the file contents, in hex
+    blake3: String,
+}
+
+fn cmd_manifest(verify: Option<&Path>) -> Result<()> {
+    let cfg = load_config()?;
+    let index_dir = Path::new(&cfg.index_dir);
+    if !tantivy_index_exists(index_dir) {
+        return Err(categorized(
+            FailureKind::IndexMissing,
+            format!(
+                "Index missing at {}. Re-run `vaultsearch init` to recreate it.",
+                index_dir.display()
+            ),
+        )
+        .into());
+    }
+    let files = manifest_entries(index_dir)?;
+
+    let Some(manifest_path) = verify else {
+        let manifest = IndexManifest {
+            index_dir: cfg.index_dir.clone(),
+            created: now_override()?.unwrap_or_else(Utc::now).to_rfc3339(),
+            files,
+        };
+        let json =
+            serde_json::to_string_pretty(&manifest).context("Failed to serialize manifest")?;
+        println!("{json}");
+        return Ok(());
+    };
+
+    let json = fs::read_to_string(manifest_path)
+        .with_context(|| format!("Failed to read manifest {}", manifest_path.display()))?;
+    let manifest: IndexManifest = serde_json::from_str(&json)
+        .with_context(|| format!("Manifest {} is not valid JSON", manifest_path.display()))?;
+    let current: HashMap<&str, &ManifestEntry> = files
+        .iter()
+        .map(|entry| (entry.path.as_str(), entry))
+        .collect();
+    let mut differences = 0usize;
+    for expected in &manifest.files {
+        match current.get(expected.path.as_str()) {
+            None => {
+                println!("  [missing] {}", expected.path);
+                differences += 1;
+            }
+            Some(actual) if actual.size != expected.size => {
+                println!(
+                    "  [changed] {} (size {} -> {})",
+                    expected.path, expected.size, actual.size
+                );
+                differences += 1;
+            }
+            Some(actual) if actual.blake3 != expected.blake3 => {
+                println!("  [changed] {} (contents differ)", expected.path);
+                differences += 1;
+            }
+            Some(_) => {}
+        }
+    }
+    let listed: HashSet<&str> = manifest
+        .files
+        .iter()
+        .map(|entry| entry.path.as_str())
+        .collect();
+    for entry in files
+        .iter()
+        .filter(|entry| !listed.contains(entry.path.as_str()))
+    {
+        println!("  [extra] {}", entry.path);
+        differences += 1;
+    }
+
+    if differences > 0 {
+        return Err(categorized(
+            FailureKind::IndexCorrupt,
+            format!(
+                "{differences} file(s) in {} differ from the manifest {}",
+                index_dir.display(),
+                manifest_path.display()
+            ),
+        )
+        .into());
+    }
+    println!(
+        "Verified {} file(s) in {} against {}: all match.",
+        manifest.files.len(),
+        index_dir.display(),
+        manifest_path.display()
+    );
+    Ok(())
+}
+
+/// Name, size, and BLAKE3 hash of each file in the index directory, sorted by name. Tantivy's
+/// lock files are left out: they are empty and come and go with running commands.
+fn manifest_entries(index_dir: &Path) -> Result<Vec<ManifestEntry>> {
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(index_dir)
+        .with_context(|| format!("Failed to list index directory {}", index_dir.display()))?
+    {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if !entry.file_type()?.is_file() || name.ends_with(".lock") {
+            continue;
+        }
+        let path = entry.path();
+        let mut file =
+            fs::File::open(&path).with_context(|| format!("Failed to open {}", path.display()))?;
+        // Streamed so large segment files are never held in memory.
+        let mut hasher = Hasher::new();
+        let size = io::copy(&mut file, &mut hasher)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        entries.push(ManifestEntry {
+            path: name,
+            size,
+            blake3: hasher.finalize().to_hex().to_string(),
+        });
+    }
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(entries)
+}
+
+fn cmd_forget(pattern: &str, force: bool) -> Result<()> {
+    if pattern.trim().is_empty() {
+        anyhow::bail!("The glob is empty; pass a pattern such as `archive/**`");
+    }
+    let glob = Glob::new(pattern).with_context(|| format!("Invalid glob `{pattern}`"))?;
+    let globs = GlobSetBuilder::new()
+        .add(glob)
+        .build()
+        .with_context(|| format!("Failed to compile glob `{pattern}`"))?;
+
+    let state = ServerState::open()?;
+    let index_dir = Path::new(&state.cfg.index_dir);
+    let root = Path::new(&state.cfg.root);
+    let path_exact_field = state
+        .index
+        .schema()
+        .get_field("path_exact")
+        .expect("path_exact field");
+
+    // `path_exact` holds each file's full path as a single term, so every document can be
+    // deleted by term without loading it.
+    let live_paths = count_live_terms(&state.reader.searcher(), path_exact_field, str::to_string)?;
+    let matching: Vec<&String> = live_paths
+        .keys()
+        .filter(|path| path_is_excluded(&globs, path, root))
+        .collect();
+    if matching.is_empty() {
+        println!("No indexed documents match `{pattern}`.");
+        return Ok(());
+    }
+    if matching.len() == live_paths.len() && !force {
+        anyhow::bail!(
+            "`{pattern}` matches all {} indexed documents; pass --force to forget them anyway, \
+             or run `vaultsearch init --force` to start over",
+            matching.len()
+        );
+    }
+
+    let mut writer: IndexWriter = state.index.writer(INDEX_WRITER_HEAP_BYTES).context(
+        "Failed to lock the index for writing. Is another vaultsearch command updating it?",
+    )?;
+    for path in &matching {
+        writer.delete_term(Term::from_field_text(path_exact_field, path));
+    }
+    writer.commit().context("Failed to commit index to disk")?;
+
+    // Without this, the next `index` run would skip unchanged files that still exist.
+    let mut metadata = load_file_metadata(index_dir)?;
+    for path in &matching {
+        metadata.remove(*path);
+    }
+    save_file_metadata(index_dir, &metadata)
+        .context("Failed to persist file metadata alongside index")?;
+    // Forgetting leaves `index_generation` alone, so cached results would still list the files.
+    clear_result_cache()?;
+
+    println!(
+        "Forgot {} document(s) matching `{pattern}`.",
+        matching.len()
+    );
+    Ok(())
+}
+
+fn cmd_serve(listen: &str) -> Result<()> {
+    let state = Arc::new(ServerState::open()?);
+    let listener =
+        TcpListener::bind(listen).with_context(|| format!("Failed to listen on {listen}"))?;
+
+    println!(
+        "Serving {} on {}",
+        state.cfg.index_dir,
+        listener
+            .local_addr()
+            .context("Failed to read listen address")?
+    );
+    io::stdout().flush()?;
+
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                eprintln!("  [warn] Failed to accept connection: {e}");
+                continue;
+            }
+        };
+        let state = Arc::clone(&state);
+        thread::spawn(move || {
+            let peer = stream
+                .peer_addr()
+                .map(|addr| addr.to_string())
+                .unwrap_or_else(|_| "unknown peer".to_string());
+            // A client that never finishes a request line would otherwise hold this thread forever.
+            let result = stream
+                .set_read_timeout(Some(Duration::from_secs(REMOTE_TIMEOUT_SECS)))
+                .and_then(|()| stream.try_clone())
+                .map_err(anyhow::Error::from)
+                .and_then(|writer| serve_connection(&state, stream, writer));
+            if let Err(e) = result {
+                eprintln!("  [warn] Connection from {peer} failed: {e:#}");
+            }
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(unix)]
+fn cmd_daemon() -> Result<()> {
+    use std::os::unix::net::{UnixListener, UnixStream};
+
+    let state = Arc::new(ServerState::open()?);
+    let socket_path = daemon_socket_path(Path::new(&state.cfg.index_dir))?;
+
+    if socket_path.exists() {
+        if UnixStream::connect(&socket_path).is_ok() {
+            anyhow::bail!(
+                "A vaultsearch daemon is already listening on {}",
+                socket_path.display()
+            );
+        }
+        // Left behind by a daemon that did not shut down cleanly.
+        fs::remove_file(&socket_path)
+            .with_context(|| format!("Failed to remove stale socket {}", socket_path.display()))?;
+    }
+    let listener = UnixListener::bind(&socket_path)
+        .with_context(|| format!("Failed to listen on {}", socket_path.display()))?;
+
+    println!(
+        "Daemon serving {} on {}",
+        state.cfg.index_dir,
+        socket_path.display()
+    );
+    io::stdout().flush()?;
+
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                eprintln!("  [warn] Failed to accept connection: {e}");
+                continue;
+            }
+        };
+        let state = Arc::clone(&state);
+        thread::spawn(move || {
+            // Same as `serve`: a stalled client must not hold this thread forever.
+            let result = stream
+                .set_read_timeout(Some(Duration::from_secs(REMOTE_TIMEOUT_SECS)))
+                .and_then(|()| stream.try_clone())
+                .map_err(anyhow::Error::from)
+                .and_then(|writer| serve_connection(&state, stream, writer));
+            if let Err(e) = result {
+                eprintln!("  [warn] Daemon connection failed: {e:#}");
+            }
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn cmd_daemon() -> Result<()> {
+    anyhow::bail!("`vaultsearch daemon` requires Unix domain sockets and is only available on Unix")
+}
+
+fn cmd_last_errors(reason: Option<SkipReason>) -> Result<()> {
+    let path = skips_path()?;
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            println!("No skipped files recorded yet. Run `vaultsearch index` first.");
+            return Ok(());
+        }
+        Err(e) => {
+            return Err(e).with_context(|| format!("Failed to read skip log {}", path.display()))
+        }
+    };
+
+    let mut skipped = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let file: SkippedFile = serde_json::from_str(line).with_context(|| {
+            format!("Invalid entry on line {} of {}", number + 1, path.display())
+        })?;
+        if reason.is_none_or(|reason| file.reason == reason) {
+            skipped.push(file);
+        }
+    }
+
+    match reason {
+        Some(reason) => println!(
+            "Files skipped by the last indexing run ({}): {}",
+            reason.label(),
+            skipped.len()
+        ),
+        None => println!("Files skipped by the last indexing run: {}", skipped.len()),
+    }
+    for file in &skipped {
+        println!("  [{}] {}", file.reason.label(), file.message);
+    }
+    Ok(())
+}
+
+fn cmd_tags(by: &str, limit: Option<usize>) -> Result<()> {
+    if limit == Some(0) {
+        anyhow::bail!("--limit must be at least 1");
+    }
+    let state = ServerState::open()?;
+    let searcher = state.reader.searcher();
+    let schema = state.index.schema();
+
+    let (counts, value_heading, count_heading) = if by == TAGS_BY_EXTENSION {
+        // `path_exact` holds each file's full path as a single term.
+        let path_exact_field = schema.get_field("path_exact").expect("path_exact field");
+        let counts = count_live_terms(&searcher, path_exact_field, |path| {
+            Path::new(path)
+                .extension()
+                .map(|ext| ext.to_string_lossy().to_lowercase())
+                .unwrap_or_else(|| "(none)".to_string())
+        })?;
+        (counts, "EXTENSION".to_string(), "FILES")
+    } else {
+        let field = indexed_text_field(&schema, by).ok_or_else(|| {
+            let mut choices = vec![TAGS_BY_EXTENSION.to_string()];
+            choices.extend(
+                schema
+                    .fields()
+                    .map(|(_, entry)| entry.name().to_string())
+                    .filter(|name| indexed_text_field(&schema, name).is_some()),
+            );
+            anyhow::anyhow!(
+                "`{by}` is not an indexed text field; use one of: {}",
+                choices.join(", ")
+            )
+        })?;
+        let counts = count_live_terms(&searcher, field, str::to_string)?;
+        (counts, by.to_uppercase(), "DOCUMENTS")
+    };
+
+    let mut counts: Vec<(String, u64)> = counts.into_iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    let total = counts.len();
+    if let Some(limit) = limit {
+        counts.truncate(limit);
+    }
+
+    if counts.is_empty() {
+        println!("No indexed documents. Run `vaultsearch index` first.");
+        return Ok(());
+    }
+    let width = counts
+        .iter()
+        .map(|(value, _)| value.chars().count())
+        .chain([value_heading.len()])
+        .max()
+        .unwrap_or(0);
+    println!("{value_heading:<width$}  {count_heading}");
+    for (value, count) in &counts {
+        println!("{value:<width$}  {count:>5}");
+    }
+    println!();
+    if counts.len() < total {
+        println!("Showing {} of {total} distinct values", counts.len());
+    } else {
+        println!("{total} distinct values");
+    }
+    Ok(())
+}
+
+/// The field named `name` if it is a text field with an inverted index.
+fn indexed_text_field(schema: &Schema, name: &str) -> Option<Field> {
+    let field = schema.get_field(name).ok()?;
+    match schema.get_field_entry(field).field_type() {
+        FieldType::Str(options) if options.get_indexing_options().is_some() => Some(field),
+        _ => None,
+    }
+}
+
+/// Counts live (not deleted) documents per term of `field` across all segments, grouping
+/// terms by `key`. Term dictionaries keep the terms of deleted documents until segments are
+/// merged, so postings are checked whenever a segment has deletes.
+fn count_live_terms(
+    searcher: &Searcher,
+    field: Field,
+    key: impl Fn(&str) -> String,
+) -> Result<HashMap<String, u64>> {
+    let mut counts: HashMap<String, u64> = HashMap::new();
+
+    for segment_reader in searcher.segment_readers() {
+        let inverted_index = segment_reader
+            .inverted_index(field)
+            .context("Failed to open inverted index")?;
+        let mut terms = inverted_index
+            .terms()
+            .stream()
+            .context("Failed to stream term dictionary")?;
+
+        while terms.advance() {
+            let Ok(text) = std::str::from_utf8(terms.key()) else {
+                continue;
+            };
+            let term_info = terms.value();
+            let live = if segment_reader.alive_bitset().is_none() {
+                u64::from(term_info.doc_freq)
+            } else {
+                let mut postings = inverted_index
+                    .read_postings_from_terminfo(term_info, IndexRecordOption::Basic)
+                    .context("Failed to read postings")?;
+                let mut live = 0;
+                while postings.doc() != TERMINATED {
+                    if !segment_reader.is_deleted(postings.doc()) {
+                        live += 1;
+                    }
+                    postings.advance();
+                }
+                live
+            };
+            if live > 0 {
+                *counts.entry(key(text)).or_default() += live;
+            }
+        }
+    }
+
+    Ok(counts)
+}
+
+fn cmd_schema(format: SchemaFormat) -> Result<()> {
+    let cfg = load_config().ok();
+    let analysis = cfg
+        .as_ref()
+        .map(|cfg| cfg.analysis.clone())
+        .unwrap_or_default();
+    let expected_schema = build_schema(&analysis);
+
+    // Prefer the schema of the index on disk so users see what their index actually contains.
+    // It is read without `open_index` so a stop-words mismatch is reported below, not hidden.
+    let on_disk = cfg.and_then(|cfg| {
+        let index_dir = PathBuf::from(&cfg.index_dir);
+        if !tantivy_index_exists(&index_dir) {
+            return None;
+        }
+        Index::open_in_dir(&index_dir)
+            .ok()
+            .map(|index| (index_dir, index.schema()))
+    });
+
+    let (source, schema) = match on_disk {
+        Some((index_dir, schema)) => (format!("index at {}", index_dir.display()), schema),
+        None => ("built-in schema".to_string(), expected_schema.clone()),
+    };
+
+    let description = SchemaDescription {
+        source,
+        matches_builtin: schema == expected_schema,
+        fields: describe_schema(&schema),
+    };
+
+    match format {
+        SchemaFormat::Json => {
+            let json = serde_json::to_string_pretty(&description)
+                .context("Failed to serialize schema description")?;
+            println!("{json}");
+        }
+        SchemaFormat::Table => {
+            println!("Schema source  : {}", description.source);
+            println!(
+                "Matches builtin: {}",
+                if description.matches_builtin {
+                    "yes"
+                } else {
+                    "no (re-run `vaultsearch init --force`)"
+                }
+            );
+            println!();
+            println!("FIELD        TYPE   INDEXED  STORED  FAST  TOKENIZER  RECORD");
+            for field in &description.fields {
+                println!(
+                    "{:<12} {:<6} {:<8} {:<7} {:<5} {:<10} {}",
+                    field.name,
+                    field.field_type,
+                    yes_no(field.indexed),
+                    yes_no(field.stored),
+                    yes_no(field.fast),
+                    field.tokenizer.as_deref().unwrap_or("-"),
+                    field.record.as_deref().unwrap_or("-"),
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Serialize)]
+struct SchemaDescription {
+    source: String,
+    matches_builtin: bool,
+    fields: Vec<SchemaFieldDescription>,
+}
+
+#[derive(Debug, Serialize)]
+struct SchemaFieldDescription {
+    name: String,
+    #[serde(rename = "type")]
+    field_type: String,
+    indexed: bool,
+    stored: bool,
+    fast: bool,
+    tokenizer: Option<String>,
+    record: Option<String>,
+}
+
+fn describe_schema(schema: &Schema) -> Vec<SchemaFieldDescription> {
+    schema
+        .fields()
+        .map(|(_, entry)| {
+            let field_type = entry.field_type();
+            let tokenizer = match field_type {
+                FieldType::Str(options) => options
+                    .get_indexing_options()
+                    .map(|indexing| indexing.tokenizer().to_string()),
+                _ => None,
+            };
+
+            SchemaFieldDescription {
+                name: entry.name().to_string(),
+                field_type: field_type.value_type().name().to_ascii_lowercase(),
+                indexed: entry.is_indexed(),
+                stored: entry.is_stored(),
+                fast: entry.is_fast(),
+                tokenizer,
+                record: field_type.index_record_option().map(|record| {
+                    match record {
+                        IndexRecordOption::Basic => "basic",
+                        IndexRecordOption::WithFreqs => "freqs",
+                        IndexRecordOption::WithFreqsAndPositions => "positions",
+                    }
+                    .to_string()
+                }),
+            }
+        })
+        .collect()
+}
+
+fn yes_no(value: bool) -> &'static str {
+    if value {
+        "yes"
+    } else {
+        "no"
+    }
+}
+
+/// Stored, tokenized text fields that can produce snippets, with `contents` first.
+fn snippet_fields(schema: &Schema, contents_field: Field) -> Vec<Field> {
+    let mut fields = vec![contents_field];
+    fields.extend(schema.get_field("code").ok());
+    let first = fields.clone();
+    fields.extend(schema.fields().filter_map(|(field, entry)| {
+        let tokenized = match entry.field_type() {
+            FieldType::Str(options) => options
+                .get_indexing_options()
+                .is_some_and(|indexing| indexing.tokenizer() != "raw"),
+            _ => false,
+        };
+        (!first.contains(&field) && tokenized && entry.is_stored()).then_some(field)
+    }));
+    fields
+}
+
+/// Picks the snippet with the most highlighted matches across fields, preferring
+/// earlier fields on ties and falling back to the (possibly unhighlighted) contents
+/// snippet, and renders it as HTML trimmed to word boundaries. For files routed to
+/// `code`, that field stands in for `contents`.
+///
+/// When only another field (such as the path) matched, the start of the contents is
+/// shown instead, labeled with `FILENAME_MATCH_LABEL`, so filename matches are not
+/// mistaken for content matches. Matches in `comments_field` are content, so they are
+/// shown as they are.
+fn best_snippet(
+    generators: &[(Field, SnippetGenerator)],
+    comments_field: Option<Field>,
+    doc: &TantivyDocument,
+) -> String {
+    let snippets: Vec<_> = generators
+        .iter()
+        .map(|(field, generator)| (*field, generator.snippet_from_doc(doc)))
+        .collect();
+    // The first field the document has text in: `contents`, or `code` for routed files.
+    let Some(contents) = snippets
+        .iter()
+        .find(|(field, _)| doc.get_first(*field).is_some())
+        .or(snippets.first())
+    else {
+        return String::new();
+    };
+
+    let (field, snippet) = snippets.iter().fold(contents, |best, candidate| {
+        if candidate.1.highlighted().len() > best.1.highlighted().len() {
+            candidate
+        } else {
+            best
+        }
+    });
+
+    if contents.1.highlighted().is_empty()
+        && !snippet.highlighted().is_empty()
+        && Some(*field) != comments_field
+    {
+        let text = doc
+            .get_first(contents.0)
+            .and_then(|v| v.as_str())
+            .unwrap_or("");
+        let excerpt = leading_excerpt(text, SNIPPET_MAX_CHARS);
+        return format!("{FILENAME_MATCH_LABEL} {}", encode_text(&excerpt))
+            .trim_end()
+            .to_string();
+    }
+
+    let source = doc.get_first(*field).and_then(|v| v.as_str()).unwrap_or("");
+    snippet_to_html(snippet.fragment(), snippet.highlighted(), source)
+}
+
+/// The first `max_chars` characters of `text` on one line, cut at a word boundary with `…`.
+fn leading_excerpt(text: &str, max_chars: usize) -> String {
+    let collapsed = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    let Some((cut, _)) = collapsed.char_indices().nth(max_chars) else {
+        return collapsed;
+    };
+    let head = &collapsed[..cut];
+    let head = match head.rfind(' ') {
+        Some(space) if !collapsed[cut..].starts_with(' ') => &head[..space],
+        _ => head,
+    };
+    format!("{}…", head.trim_end())
+}
+
+/// Renders a snippet fragment as HTML with `<b>` highlights, trimming partial words at
+/// either edge and adding `…` where the fragment does not reach the start or end of `source`.
+fn snippet_to_html(fragment: &str, highlighted: &[Range<usize>], source: &str) -> String {
+    if fragment.is_empty() {
+        return String::new();
+    }
+    // Fragments are slices of the stored text; locate it to see what surrounds it.
+    let Some(offset) = source.find(fragment) else {
+        return render_highlights(fragment, highlighted);
+    };
+
+    let first_highlight = highlighted
+        .iter()
+        .map(|r| r.start)
+        .min()
+        .unwrap_or(fragment.len());
+    let last_highlight = highlighted.iter().map(|r| r.end).max().unwrap_or(0);
+
+    let mut start = 0;
+    let starts_mid_word = source[..offset]
+        .chars()
+        .next_back()
+        .is_some_and(|c| !c.is_whitespace());
+    if starts_mid_word {
+        if let Some(space) = fragment[..first_highlight].find(char::is_whitespace) {
+            start = space;
+        }
+    }
+
+    let mut end = fragment.len();
+    let ends_mid_word = source[offset + fragment.len()..]
+        .chars()
+        .next()
+        .is_some_and(|c| !c.is_whitespace());
+    if ends_mid_word {
+        if let Some(space) = fragment[last_highlight.max(start)..].rfind(char::is_whitespace) {
+            end = last_highlight.max(start) + space;
+        }
+    }
+
+    // Drop whitespace at the new edges so the ellipsis sits against the text.
+    let trimmed = &fragment[start..end];
+    start += trimmed.len() - trimmed.trim_start().len();
+    end -= trimmed.len() - trimmed.trim_end().len();
+
+    let shifted: Vec<Range<usize>> = highlighted
+        .iter()
+        .filter(|r| r.start >= start && r.end <= end)
+        .map(|r| r.start - start..r.end - start)
+        .collect();
+
+    let mut html = String::new();
+    if !source[..offset + start].trim().is_empty() {
+        html.push('…');
+    }
+    html.push_str(&render_highlights(&fragment[start..end], &shifted));
+    if !source[offset + end..].trim().is_empty() {
+        html.push('…');
+    }
+    html
+}
+
+fn render_highlights(text: &str, highlighted: &[Range<usize>]) -> String {
+    let mut html = String::new();
+    let mut cursor = 0;
+    for range in collapse_overlapped_ranges(highlighted) {
+        if range.start < cursor {
+            continue;
+        }
+        html.push_str(&encode_text(&text[cursor..range.start]));
+        html.push_str("<b>");
+        html.push_str(&encode_text(&text[range.clone()]));
+        html.push_str("</b>");
+        cursor = range.end;
+    }
+    html.push_str(&encode_text(&text[cursor..]));
+    html
+}
+
+/// Resolves `--color`: `auto` highlights only on a terminal and honors a non-empty
+/// `NO_COLOR` (https://no-color.org); `always` and `never` override both.
+fn use_color(choice: ColorChoice, no_color: Option<&std::ffi::OsStr>, is_tty: bool) -> bool {
+    match choice {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => is_tty && no_color.is_none_or(|value| value.is_empty()),
+    }
+}
+
+/// Whether output to stdout highlights matches.
+fn stdout_color() -> bool {
+    USE_COLOR.get().copied().unwrap_or(false)
+}
+
+/// Strings written around each highlighted match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct HighlightMarkers<'a> {
+    open: &'a str,
+    close: &'a str,
+}
+
+/// Terminal bold, the default highlight.
+const ANSI_HIGHLIGHT: HighlightMarkers<'static> = HighlightMarkers {
+    open: "\x1b[1m",
+    close: "\x1b[0m",
+};
+
+/// The markers `search` wraps matches in: `--highlight-open`/`--highlight-close` whenever
+/// either is given (in every output format), otherwise bold when `color` is on.
+fn search_highlight_markers(args: &SearchArgs, color: bool) -> Option<HighlightMarkers<'_>> {
+    if args.highlight_open.is_none() && args.highlight_close.is_none() {
+        return color.then_some(ANSI_HIGHLIGHT);
+    }
+    Some(HighlightMarkers {
+        open: args
+            .highlight_open
+            .as_deref()
+            .unwrap_or(ANSI_HIGHLIGHT.open),
+        close: args
+            .highlight_close
+            .as_deref()
+            .unwrap_or(ANSI_HIGHLIGHT.close),
+    })
+}
+
+/// Renders snippet highlights with `markers`, or drops them when there are none.
+fn highlight_snippet(snippet_html: &str, markers: Option<HighlightMarkers>) -> String {
+    let markers = markers.unwrap_or(HighlightMarkers {
+        open: "",
+        close: "",
+    });
+    // Entities are decoded per text run, so a literal `&lt;b&gt;` in a file is never
+    // mistaken for a highlight tag.
+    let mut out = String::with_capacity(snippet_html.len());
+    for (index, part) in snippet_html.split("<b>").enumerate() {
+        if index > 0 {
+            out.push_str(markers.open);
+        }
+        let mut runs = part.splitn(2, "</b>");
+        out.push_str(&decode_html_entities(runs.next().unwrap_or("")));
+        if let Some(after) = runs.next() {
+            out.push_str(markers.close);
+            out.push_str(&decode_html_entities(after));
+        }
+    }
+    out
+}
+
+/// Snippet text with highlight tags removed, for machine-readable output.
+fn plain_snippet(snippet_html: &str) -> String {
+    highlight_snippet(snippet_html, None)
+}
+
+/// The time set in `$VAULTSEARCH_NOW`, if any.
+fn now_override() -> Result<Option<chrono::DateTime<Utc>>> {
+    let Some(value) = std::env::var_os(NOW_ENV_VAR) else {
+        return Ok(None);
+    };
+    let value = value.to_string_lossy();
+    let parsed = chrono::DateTime::parse_from_rfc3339(&value).map_err(|e| {
+        categorized(
+            FailureKind::Config,
+            format!("${NOW_ENV_VAR} is not an RFC 3339 timestamp: {value:?} ({e})"),
+        )
+    })?;
+    Ok(Some(parsed.with_timezone(&Utc)))
+}
+
+fn perform_indexing(cfg: &mut AppConfig, options: &IndexOptions) -> Result<()> {
+    let root = Path::new(&cfg.root);
+    let index_dir = Path::new(&cfg.index_dir);
+    // Read before indexing so a malformed override fails without touching the index.
+    let now_override = now_override()?;
+
+    if !tantivy_index_exists(index_dir) {
+        return Err(categorized(
+            FailureKind::IndexMissing,
+            format!(
+                "Index missing at {}. Re-run `vaultsearch init` to recreate it.",
+                index_dir.display()
+            ),
+        )
+        .into());
+    }
+
+    println!("Indexing...");
+    if let Some((config_path, source)) = &cfg.loaded_from {
+        println!(
+            "  Config file    : {} ({})",
+            config_path.display(),
+            source.describe()
+        );
+    }
+    println!("  Root directory : {}", root.display());
+    println!("  Index directory: {}", index_dir.display());
+    if let Some(project_config) = &cfg.project_config {
+        println!("  Project config : {}", project_config.display());
+    }
+
+    if options.check {
+        let check = check_index_integrity(index_dir, &cfg.analysis)?;
+        println!("  Index check    : {check}");
+    }
+
+    if options.ocr && !cfg!(feature = "ocr") {
+        anyhow::bail!(
+            "--ocr requires vaultsearch to be built with the `ocr` feature (cargo build --features ocr)"
+        );
+    }
+
+    let max_depth = index_max_depth(cfg, options);
+    let progress_every = options
+        .progress_every
+        .or(cfg.progress_every)
+        .unwrap_or(INDEX_PROGRESS_CHUNK);
+    let min_file_size = options.min_file_size.or(cfg.min_file_size).unwrap_or(0);
+    let merge_policy = options.merge_policy.unwrap_or(cfg.merge_policy);
+    let merge_min_segments = options.merge_min_segments.or(cfg.merge_min_segments);
+    if merge_policy == MergePolicyKind::NoMerge && options.merge_min_segments.is_some() {
+        anyhow::bail!("--merge-min-segments only applies to the `log` merge policy");
+    }
+    if let Some(depth) = max_depth {
+        println!("  Max depth      : {depth}");
+    }
+    if min_file_size > 0 {
+        println!("  Min file size  : {min_file_size} bytes");
+    }
+    if merge_policy == MergePolicyKind::NoMerge {
+        println!("  Merge policy   : no-merge");
+    } else if let Some(min) = merge_min_segments {
+        println!("  Merge policy   : log (merge at {min} segments)");
+    }
+    if options.read_threads.is_some() || options.index_threads.is_some() {
+        println!(
+            "  Threads        : {} read, {} index",
+            options
+                .read_threads
+                .map_or_else(|| "auto".to_string(), |n| n.to_string()),
+            options
+                .index_threads
+                .map_or_else(|| "auto".to_string(), |n| n.to_string())
+        );
+    }
+
+    let index = open_index(index_dir, &cfg.analysis)?;
+    let schema = index.schema();
+
+    if schema != build_schema(&cfg.analysis) {
+        anyhow::bail!(
+            "Index schema is out of date. Re-run `vaultsearch init --force` to rebuild it."
+        );
+    }
+
+    let path_field = schema.get_field("path").expect("path field");
+    let path_exact_field = schema.get_field("path_exact").expect("path_exact field");
+    let contents_field = schema.get_field("contents").expect("contents field");
+    let code_field = schema.get_field("code").ok();
+    let mut routed_to_code = 0usize;
+    let uid_field = schema.get_field("uid").expect("uid field");
+    let gid_field = schema.get_field("gid").expect("gid field");
+    let mode_field = schema.get_field("mode").expect("mode field");
+    let symlink_target_field = schema
+        .get_field("symlink_target")
+        .expect("symlink_target field");
+    let size_field = schema.get_field("size").expect("size field");
+    let modified_field = schema.get_field("modified").expect("modified field");
+    let lines_field = schema.get_field("lines").expect("lines field");
+    let generation_field = schema.get_field("generation").expect("generation field");
+    let language_field = schema.get_field("language").expect("language field");
+    let comments_field = schema.get_field("comments").expect("comments field");
+    let unfinished_run = load_unfinished_run(index_dir);
+    let resuming = options.resume && unfinished_run.is_some();
+    let run = match unfinished_run {
+        Some(run) if options.resume => {
+            println!(
+                "  Resuming       : generation {} (started {})",
+                run.generation, run.started
+            );
+            run
+        }
+        unfinished => {
+            if options.resume {
+                println!("  Resuming       : nothing to resume; indexing normally");
+            } else if let Some(run) = unfinished {
+                eprintln!(
+                    "  [warn] The run started {} did not finish; the files it committed are kept. \
+                     Pass --resume to continue it.",
+                    run.started
+                );
+            }
+            UnfinishedRun {
+                generation: cfg.index_generation + 1,
+                started: now_override.unwrap_or_else(Utc::now).to_rfc3339(),
+            }
+        }
+    };
+    let generation = run.generation;
+    // With `store_relative_paths`, documents and metadata are keyed by the path below the root.
+    let relative_to = cfg.store_relative_paths.then_some(root);
+    let checkpoint_every = options.checkpoint_every.unwrap_or(INDEX_CHECKPOINT_FILES);
+
+    // Tantivy index writer: 50 MB heap, grown so each explicitly requested thread gets its minimum.
+    let mut writer = match options.index_threads.map(usize::from) {
+        Some(threads) => index.writer_with_num_threads(
+            threads,
+            INDEX_WRITER_HEAP_BYTES.max(threads * INDEX_WRITER_MIN_HEAP_PER_THREAD),
+        ),
+        None => index.writer(INDEX_WRITER_HEAP_BYTES),
+    }
+    .context("Failed to create Tantivy index writer")?;
+    writer.set_merge_policy(merge_policy.policy(merge_min_segments));
+    let read_threads = options
+        .read_threads
+        .map(usize::from)
+        .unwrap_or_else(default_read_threads);
+
+    let previous_metadata = load_file_metadata(index_dir).unwrap_or_else(|e| {
+        eprintln!(
+            "  [warn] Failed to load previous metadata ({}). Starting fresh.",
+            e
+        );
+        HashMap::new()
+    });
+
+    let mut new_metadata: HashMap<String, FileMetadata> = HashMap::new();
+    // Canonical paths seen this run, so a file reachable via a symlink is only indexed once.
+    let mut seen_paths: HashSet<PathBuf> = HashSet::new();
+    let mut skip_stats = SkipStats {
+        max_recorded: cfg.max_recorded_skips.unwrap_or(DEFAULT_MAX_RECORDED_SKIPS),
+        ..SkipStats::default()
+    };
+    let mut stats = IndexingStats::default();
+
+    let mut walker = walkdir::WalkDir::new(root).follow_links(options.follow_symlinks);
+    if let Some(depth) = max_depth {
+        // WalkDir counts the root itself as depth 0, so files directly inside it are depth 1.
+        walker = walker.max_depth(depth + 1);
+    }
+
+    let excluded_dirs: HashSet<&str> = if options.no_default_excludes {
+        HashSet::new()
+    } else {
+        cfg.default_excluded_dirs
+            .iter()
+            .map(String::as_str)
+            .collect()
+    };
+    let mut pruned_dirs = 0usize;
+    let ignore_rules = IgnoreRules::load(&options.ignore_files)?;
+    if !options.ignore_files.is_empty() {
+        let sources: Vec<String> = options
+            .ignore_files
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect();
+        println!(
+            "  Ignore files   : {} ({} patterns)",
+            sources.join(", "),
+            ignore_rules.len()
+        );
+    }
+    let mut ignored_paths = 0usize;
+    let tracked_files = if options.git_tracked_only {
+        match git_tracked_files(root) {
+            Ok(files) => {
+                println!("  Git-tracked    : {} files", files.len());
+                Some(files)
+            }
+            Err(e) => {
+                eprintln!("  [warn] {e:#}; indexing every file under the root instead");
+                None
+            }
+        }
+    } else {
+        None
+    };
+    let walk_entries = walker.into_iter().filter_entry(|entry| {
+        let excluded = entry.depth() > 0
+            && entry.file_type().is_dir()
+            && entry
+                .file_name()
+                .to_str()
+                .is_some_and(|name| excluded_dirs.contains(name));
+        if excluded {
+            pruned_dirs += 1;
+            return false;
+        }
+        let ignored = entry.depth() > 0
+            && entry.path().strip_prefix(root).is_ok_and(|relative| {
+                ignore_rules.is_ignored(relative, entry.file_type().is_dir())
+            });
+        if ignored {
+            ignored_paths += 1;
+        }
+        !ignored
+    });
+    let paths: Box<dyn Iterator<Item = PathBuf>> = match tracked_files {
+        Some(files) => Box::new(
+            files
+                .into_iter()
+                .filter(|relative| tracked_file_included(relative, max_depth, &excluded_dirs))
+                .filter(|relative| {
+                    let ignored = ignore_rules.is_ignored_with_parents(relative);
+                    if ignored {
+                        ignored_paths += 1;
+                    }
+                    !ignored
+                })
+                .map(|relative| root.join(relative)),
+        ),
+        None => Box::new(
+            walk_entries
+                .filter_map(|e| e.ok())
+                .map(walkdir::DirEntry::into_path),
+        ),
+    };
+
+    let interrupt_requested = install_interrupt_handler();
+    let mut interrupted = false;
+    let mut pending = Vec::new();
+    let mut phases = PhaseTimes::default();
+    let started = Instant::now();
+
+    for path in paths {
+        if interrupt_requested.load(Ordering::SeqCst) {
+            interrupted = true;
+            break;
+        }
+        let path = path.as_path();
+
+        if !path.is_file() {
+            continue;
+        }
+
+        let path_display = path.display();
+
+        let canonical = match fs::canonicalize(path) {
+            Ok(canonical) => canonical,
+            Err(e) => {
+                skip_stats.skip(
+                    SkipReason::ReadError,
+                    path,
+                    format!("Failed to resolve {path_display}: {e}"),
+                );
+                continue;
+            }
+        };
+        // The root is canonical, so any difference means a symlink was followed to get here.
+        let symlink_target = (options.follow_symlinks && canonical != path)
+            .then(|| canonical.to_string_lossy().to_string());
+        if !seen_paths.insert(canonical) {
+            skip_stats.skip(
+                SkipReason::DuplicatePath,
+                path,
+                format!("Already indexed via another path: {path_display}"),
+            );
+            continue;
+        }
+
+        let ocr_candidate = options.ocr && is_ocr_image(path);
+        let sidecar = find_sidecar(path, &cfg.sidecar_suffix);
+        // Files that cannot be read as text are still indexed through their sidecar alone,
+        // or by path alone with `--index-binary-paths`.
+        let mut sidecar_only = false;
+        let mut path_only = false;
+
+        if !ocr_candidate && !is_text_like(path) {
+            // Keep text recognized by an earlier `--ocr` run instead of dropping it from the index.
+            let path_str = stored_path(path, relative_to);
+            if let Some(previous) = previous_metadata.get(&path_str).filter(|m| m.ocr) {
+                stats.unchanged += 1;
+                if resuming && previous.generation == generation {
+                    stats.resumed += 1;
+                }
+                new_metadata.insert(path_str, previous.clone());
+                continue;
+            }
+
+            if sidecar.is_some() {
+                sidecar_only = true;
+            } else if options.index_binary_paths {
+                path_only = true;
+            } else {
+                skip_stats.skip(
+                    SkipReason::UnsupportedExtension,
+                    path,
+                    format!("Unsupported extension: {path_display}"),
+                );
+                continue;
+            }
+        }
+
+        let metadata = match fs::metadata(path) {
+            Ok(meta) => meta,
+            Err(e) => {
+                skip_stats.skip(
+                    SkipReason::ReadError,
+                    path,
+                    format!("Failed to read metadata for {path_display}: {e}"),
+                );
+                continue;
+            }
+        };
+
+        if metadata.len() < min_file_size {
+            skip_stats.skip(
+                SkipReason::TooSmall,
+                path,
+                format!(
+                    "File is below min_file_size ({} bytes): {path_display}",
+                    metadata.len()
+                ),
+            );
+            continue;
+        }
+
+        let size_limit = if ocr_candidate {
+            OCR_MAX_FILE_SIZE_BYTES
+        } else {
+            cfg.size_limits.limit_for(path)
+        };
+        if metadata.len() > size_limit && !sidecar_only && !path_only {
+            if sidecar.is_some() {
+                sidecar_only = true;
+            } else {
+                skip_stats.skip(
+                    SkipReason::TooLarge,
+                    path,
+                    format!(
+                        "File exceeds size limit ({} bytes): {path_display}",
+                        metadata.len()
+                    ),
+                );
+                continue;
+            }
+        }
+
+        let path_str = stored_path(path, relative_to);
+        let modified = match file_modified_timestamp(&metadata) {
+            Ok(ts) => ts,
+            Err(e) => {
+                skip_stats.skip(
+                    SkipReason::ReadError,
+                    path,
+                    format!("Failed to read modified time for {path_display}: {e}"),
+                );
+                continue;
+            }
+        };
+
+        let ownership = file_ownership(&metadata);
+        let sidecar_stamp = sidecar.as_ref().map(|sidecar| sidecar.stamp);
+
+        if let Some(previous) = previous_metadata.get(&path_str) {
+            if previous.modified == modified
+                && previous.size == metadata.len()
+                && previous.ownership == ownership
+                && previous.symlink_target == symlink_target
+                && previous.sidecar == sidecar_stamp
+                && (!previous.path_only || options.index_binary_paths)
+            {
+                stats.unchanged += 1;
+                if resuming && previous.generation == generation {
+                    stats.resumed += 1;
+                }
+                new_metadata.insert(path_str.clone(), previous.clone());
+                continue;
+            }
+        }
+
+        pending.push(PendingFile {
+            path: path.to_path_buf(),
+            path_str,
+            size: metadata.len(),
+            modified,
+            size_limit,
+            ocr: ocr_candidate,
+            sidecar,
+            sidecar_only,
+            path_only,
+            ownership,
+            symlink_target,
+        });
+    }
+    phases.scan = started.elapsed();
+
+    // Reader threads load contents while this thread feeds the Tantivy writer, so slow
+    // disks or OCR do not leave the writer idle.
+    let queue = Mutex::new(pending.into_iter());
+    let extractors = ExtractorRegistry::builtin(options.ocr);
+    thread::scope(|scope| -> Result<()> {
+        let (sender, receiver) = mpsc::sync_channel(read_threads * 4);
+        for _ in 0..read_threads {
+            let sender = sender.clone();
+            let queue = &queue;
+            let size_limits = &cfg.size_limits;
+            let extractors = &extractors;
+            let interrupt_requested = &interrupt_requested;
+            scope.spawn(move || {
+                while !interrupt_requested.load(Ordering::SeqCst) {
+                    let Some(file) = queue.lock().expect("pending queue lock").next() else {
+                        break;
+                    };
+                    let loading = Instant::now();
+                    let mut sniff = Duration::ZERO;
+                    let loaded = load_pending_file(&file, size_limits, extractors, &mut sniff);
+                    let language = loaded
+                        .as_ref()
+                        .map_or(UNKNOWN_LANGUAGE, |data| detect_language(&data.contents));
+                    let comments = match (&loaded, comment_syntax(&file.path)) {
+                        (Ok(data), Some(syntax)) => extract_comments(&data.contents, syntax),
+                        _ => String::new(),
+                    };
+                    let read = loading.elapsed().saturating_sub(sniff);
+                    if sender
+                        .send((file, loaded, language, comments, (sniff, read)))
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(sender);
+
+        loop {
+            let waiting = Instant::now();
+            let Ok((file, loaded, language, comments, (sniff, read))) = receiver.recv() else {
+                break;
+            };
+            phases.wait += waiting.elapsed();
+            phases.sniff += sniff;
+            phases.read += read;
+            let adding = Instant::now();
+
+            let (file_data, path_only) = match loaded {
+                Ok(file_data) => (file_data, file.path_only),
+                Err(failure)
+                    if options.index_binary_paths
+                        && matches!(failure.reason, SkipReason::Binary) =>
+                {
+                    (FileReadResult::path_only(), true)
+                }
+                Err(failure) => {
+                    skip_stats.skip(failure.reason, &file.path, failure.message);
+                    continue;
+                }
+            };
+            if file.sidecar.is_some() {
+                stats.with_sidecar += 1;
+            }
+            if path_only {
+                stats.path_only += 1;
+            }
+
+            let text_field = match code_field {
+                Some(code_field) if cfg.analysis.routes_to_code(&file.path) => {
+                    routed_to_code += 1;
+                    code_field
+                }
+                _ => contents_field,
+            };
+            let mut doc = doc!(
+                path_field => file.path_str.clone(),
+                path_exact_field => file.path_str.clone(),
+                text_field => file_data.contents,
+                size_field => file.size,
+                modified_field => DateTime::from_timestamp_secs(file.modified),
+                lines_field => file_data.lines,
+                generation_field => generation,
+                language_field => language,
+            );
+            if let Some(ownership) = file.ownership {
+                doc.add_u64(uid_field, u64::from(ownership.uid));
+                doc.add_u64(gid_field, u64::from(ownership.gid));
+                doc.add_u64(mode_field, u64::from(ownership.mode));
+            }
+            if let Some(target) = &file.symlink_target {
+                doc.add_text(symlink_target_field, target);
+            }
+            if !comments.is_empty() {
+                doc.add_text(comments_field, comments);
+            }
+
+            if previous_metadata.contains_key(&file.path_str) {
+                writer.delete_term(Term::from_field_text(path_exact_field, &file.path_str));
+                stats.updated += 1;
+            } else {
+                stats.indexed += 1;
+            }
+
+            writer
+                .add_document(doc)
+                .with_context(|| format!("Failed to add document for {}", file.path.display()))?;
+
+            new_metadata.insert(
+                file.path_str.clone(),
+                FileMetadata {
+                    path: file.path_str,
+                    modified: file.modified,
+                    size: file.size,
+                    hash: file_data.hash,
+                    ocr: file.ocr,
+                    ownership: file.ownership,
+                    symlink_target: file.symlink_target,
+                    sidecar: file.sidecar.map(|sidecar| sidecar.stamp),
+                    path_only,
+                    generation,
+                },
+            );
+
+            phases.add += adding.elapsed();
+
+            if progress_every > 0 && (stats.indexed + stats.updated) % progress_every == 0 {
+                println!(
+                    "  Indexed/updated {} files so far...",
+                    stats.indexed + stats.updated
+                );
+            }
+            if checkpoint_every > 0 && (stats.indexed + stats.updated) % checkpoint_every == 0 {
+                // Commit what is done so far so a killed run can pick up here with `--resume`.
+                writer
+                    .commit()
+                    .context("Failed to commit index checkpoint")?;
+                let mut checkpoint = new_metadata.clone();
+                keep_unreached_metadata(&mut checkpoint, &previous_metadata);
+                save_file_metadata(index_dir, &checkpoint)
+                    .context("Failed to persist file metadata at checkpoint")?;
+                save_unfinished_run(index_dir, &run)?;
+            }
+        }
+        Ok(())
+    })?;
+    // Readers stop early on Ctrl-C, leaving some walked files unread.
+    interrupted |= interrupt_requested.load(Ordering::SeqCst);
+
+    if interrupted {
+        // Files the walk never reached or read are still in the index; keep their metadata so the
+        // next run does not add them a second time.
+        keep_unreached_metadata(&mut new_metadata, &previous_metadata);
+    } else {
+        for (path, _) in previous_metadata
+            .iter()
+            .filter(|(p, _)| !new_metadata.contains_key(*p))
+        {
+            writer.delete_term(Term::from_field_text(path_exact_field, path));
+            stats.removed += 1;
+        }
+    }
+
+    let committing = Instant::now();
+    writer.commit().context("Failed to commit index to disk")?;
+    phases.commit = committing.elapsed();
+
+    let saving = Instant::now();
+    save_file_metadata(index_dir, &new_metadata)
+        .context("Failed to persist file metadata alongside index")?;
+    if interrupted {
+        save_unfinished_run(index_dir, &run)?;
+    } else {
+        clear_unfinished_run(index_dir)?;
+    }
+
+    cfg.last_indexed = Some(now_override.unwrap_or_else(Utc::now).to_rfc3339());
+    cfg.index_generation = generation;
+    save_config(cfg)?;
+    let skips_path = save_skipped_files(&skip_stats.recorded)?;
+    phases.save = saving.elapsed();
+    phases.total = started.elapsed();
+
+    println!("Indexing complete.");
+    println!("  Pruned dirs    : {pruned_dirs} (default_excluded_dirs)");
+    if !options.ignore_files.is_empty() {
+        println!("  Ignored paths  : {ignored_paths} (--ignore-file)");
+    }
+    println!("  Added files    : {}", stats.indexed);
+    if code_field.is_some() {
+        println!("  Routed to code : {routed_to_code} added or updated ([field_routing])");
+    }
+    println!("  Updated files  : {}", stats.updated);
+    println!("  Unchanged files: {}", stats.unchanged);
+    if resuming {
+        println!(
+            "  Already indexed: {} (by the interrupted run)",
+            stats.resumed
+        );
+    }
+    println!("  Removed files  : {}", stats.removed);
+    if merge_policy == MergePolicyKind::NoMerge {
+        let segments = index
+            .searchable_segment_ids()
+            .context("Failed to read index segments")?
+            .len();
+        println!(
+            "  Segments       : {segments} (no-merge; run `vaultsearch compact` to merge them)"
+        );
+    }
+    if !cfg.sidecar_suffix.is_empty() {
+        println!(
+            "  Sidecars read  : {} ({})",
+            stats.with_sidecar, cfg.sidecar_suffix
+        );
+    }
+    if options.index_binary_paths {
+        println!(
+            "  Paths only     : {} (binary or unsupported)",
+            stats.path_only
+        );
+    }
+    println!("  Skipped files  : {}", skip_stats.total());
+    println!(
+        "    - Unsupported extension : {}",
+        skip_stats.unsupported_extension
+    );
+    println!("    - Too large             : {}", skip_stats.too_large);
+    if min_file_size > 0 {
+        println!("    - Too small             : {}", skip_stats.too_small);
+    }
+    println!("    - Binary content        : {}", skip_stats.binary);
+    println!("    - Read errors           : {}", skip_stats.read_errors);
+    println!(
+        "    - Duplicate paths       : {}",
+        skip_stats.duplicate_path
+    );
+    if options.ocr {
+        println!("    - OCR failures          : {}", skip_stats.ocr_failures);
+    }
+    if skip_stats.total() > 0 {
+        let unrecorded = skip_stats.total() - skip_stats.recorded.len();
+        println!(
+            "  Skip log       : {} ({} recorded{}; see `vaultsearch last-errors`)",
+            skips_path.display(),
+            skip_stats.recorded.len(),
+            if unrecorded > 0 {
+                format!(", {unrecorded} over max_recorded_skips")
+            } else {
+                String::new()
+            }
+        );
+    }
+    println!(
+        "  Last indexed  : {}",
+        cfg.last_indexed.as_deref().unwrap_or("unknown")
+    );
+    println!("  Generation    : {}", cfg.index_generation);
+    if options.profile_stats {
+        phases.print(read_threads);
+    }
+
+    if interrupted {
+        return Err(categorized(
+            FailureKind::Interrupted,
+            format!(
+                "Indexing interrupted; committed {} added or updated files. Run `vaultsearch index --resume` to finish.",
+                stats.indexed + stats.updated
+            ),
+        )
+        .into());
+    }
+
+    if let Some(max_pct) = options.fail_if_skip_ratio {
+        let kept = stats.indexed + stats.updated + stats.unchanged;
+        let skipped_pct = skip_ratio(kept, skip_stats.total()) * 100.0;
+        if skipped_pct > max_pct {
+            anyhow::bail!(
+                "Skipped {skipped_pct:.1}% of files, above the --fail-if-skip-ratio threshold of {max_pct}%. \
+                 Check the extension list, size limits, and excluded directories."
+            );
+        }
+    }
+
+    if options.fail_on_empty_index {
+        let reader = index.reader().context("Failed to create index reader")?;
+        if reader.searcher().num_docs() == 0 {
+            anyhow::bail!(
+                "The index has no documents after indexing {} (--fail-on-empty-index). \
+                 Check `root`, --max-depth, --ignore-file, and the excluded directories.",
+                cfg.root
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Installs a Ctrl-C handler that asks the indexing loop to stop, so the files indexed so
+/// far are committed and the writer lock is released. A second Ctrl-C exits immediately.
+fn install_interrupt_handler() -> Arc<AtomicBool> {
+    // `index --all-roots` indexes several roots in one process; the handler can only be set once.
+    static REQUESTED: OnceLock<Arc<AtomicBool>> = OnceLock::new();
+    let requested = REQUESTED.get_or_init(|| {
+        let requested = Arc::new(AtomicBool::new(false));
+        let flag = Arc::clone(&requested);
+        let installed = ctrlc::set_handler(move || {
+            if flag.swap(true, Ordering::SeqCst) {
+                process::exit(i32::from(FailureKind::Interrupted.exit_code()));
+            }
+            eprintln!(
+                "\nInterrupted; committing files indexed so far (press Ctrl-C again to abort)..."
+            );
+        });
+        if let Err(e) = installed {
+            eprintln!("  [warn] Failed to install Ctrl-C handler: {e}");
+        }
+        requested
+    });
+    Arc::clone(requested)
+}
+
+/// Fraction of walked files that were skipped; 0 when nothing was found at all.
+fn skip_ratio(kept: usize, skipped: usize) -> f64 {
+    let total = kept + skipped;
+    if total == 0 {
+        return 0.0;
+    }
+    skipped as f64 / total as f64
+}
+
+/// Reading files is IO-bound, so use more threads than cores.
+fn default_read_threads() -> usize {
+    thread::available_parallelism()
+        .map_or(1, |n| n.get() * 2)
+        .min(MAX_DEFAULT_READ_THREADS)
+}
+
+/// A file the walk decided to (re)index; its contents are loaded on a reader thread.
+struct PendingFile {
+    path: PathBuf,
+    path_str: String,
+    size: u64,
+    modified: i64,
+    size_limit: u64,
+    ocr: bool,
+    sidecar: Option<Sidecar>,
+    sidecar_only: bool,
+    /// Index the path with empty contents, without reading the file
+    path_only: bool,
+    ownership: Option<FileOwnership>,
+    symlink_target: Option<String>,
+}
+
+/// Why a pending file was skipped after all, with the message to print.
+struct LoadFailure {
+    reason: SkipReason,
+    message: String,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum SkipReason {
+    UnsupportedExtension,
+    TooLarge,
+    TooSmall,
+    Binary,
+    ReadError,
+    DuplicatePath,
+    OcrFailure,
+}
+
+impl SkipReason {
+    fn label(self) -> &'static str {
+        match self {
+            SkipReason::UnsupportedExtension => "unsupported-extension",
+            SkipReason::TooLarge => "too-large",
+            SkipReason::TooSmall => "too-small",
+            SkipReason::Binary => "binary",
+            SkipReason::ReadError => "read-error",
+            SkipReason::DuplicatePath => "duplicate-path",
+            SkipReason::OcrFailure => "ocr-failure",
+        }
+    }
+}
+
+/// One line of `skips.jsonl`.
+#[derive(Debug, Serialize, Deserialize)]
+struct SkippedFile {
+    path: String,
+    reason: SkipReason,
+    /// The `[skip]` message printed during indexing
+    message: String,
+}
+
+/// Sniffs a pending file, reads it with the extractor for its extension, and appends its sidecar. Runs on reader threads.
+fn load_pending_file(
+    file: &PendingFile,
+    size_limits: &SizeLimits,
+    extractors: &ExtractorRegistry,
+    sniff_time: &mut Duration,
+) -> std::result::Result<FileReadResult, LoadFailure> {
+    let path_display = file.path.display();
+    let failure = |reason, message| LoadFailure { reason, message };
+    let mut sidecar_only = file.sidecar_only;
+
+    if file.path_only {
+        return Ok(FileReadResult::path_only());
+    }
+
+    if !file.ocr && !sidecar_only {
+        let sniffing = Instant::now();
+        let sniffed = is_probably_binary(&file.path);
+        *sniff_time = sniffing.elapsed();
+        match sniffed {
+            Ok(true) if file.sidecar.is_some() => sidecar_only = true,
+            Ok(true) => {
+                return Err(failure(
+                    SkipReason::Binary,
+                    format!("Detected binary content: {path_display}"),
+                ))
+            }
+            Ok(false) => {}
+            Err(e) => {
+                return Err(failure(
+                    SkipReason::ReadError,
+                    format!("Failed to sniff {path_display}: {e}"),
+                ))
+            }
+        }
+    }
+
+    if let Some(sidecar) = file.sidecar.as_ref().filter(|_| sidecar_only) {
+        return read_sidecar(sidecar, size_limits).map_err(|e| {
+            failure(
+                SkipReason::ReadError,
+                format!("Failed to read {path_display}: {e}"),
+            )
+        });
+    }
+
+    let mut file_data = extractors
+        .for_path(&file.path)
+        .extract(&file.path, file.size, file.size_limit)
+        .map_err(|e| {
+            if file.ocr {
+                failure(
+                    SkipReason::OcrFailure,
+                    format!("OCR failed for {path_display}: {e:#}"),
+                )
+            } else {
+                failure(
+                    SkipReason::ReadError,
+                    format!("Failed to read {path_display}: {e}"),
+                )
+            }
+        })?;
+
+    if let Some(sidecar) = &file.sidecar {
+        match read_sidecar(sidecar, size_limits) {
+            Ok(sidecar_data) => {
+                file_data.contents.push_str("\n\n");
+                file_data.contents.push_str(&sidecar_data.contents);
+            }
+            Err(e) => eprintln!(
+                "  [warn] Failed to read sidecar {}: {e}",
+                sidecar.path.display()
+            ),
+        }
+    }
+
+    Ok(file_data)
+}
+
+/// Wall-clock time per indexing phase, for `index --profile-stats`.
+#[derive(Default)]
+struct PhaseTimes {
+    /// Walking the root, reading metadata, and comparing it with the previous run
+    scan: Duration,
+    /// Binary detection, summed over the read threads
+    sniff: Duration,
+    /// Reading and extracting contents and sidecars, summed over the read threads
+    read: Duration,
+    /// The writer thread waiting for the read threads to deliver a file
+    wait: Duration,
+    /// Building documents and handing them to Tantivy
+    add: Duration,
+    /// Committing, which waits for Tantivy's indexing threads to finish tokenizing
+    commit: Duration,
+    /// Saving file metadata and the config
+    save: Duration,
+    total: Duration,
+}
+
+impl PhaseTimes {
+    fn print(&self, read_threads: usize) {
+        let secs = |duration: Duration| format!("{:.3}s", duration.as_secs_f64());
+        println!("  Profile (wall-clock):");
+        println!("    - Scan                  : {}", secs(self.scan));
+        println!(
+            "    - Sniff                 : {} (summed over {read_threads} read threads)",
+            secs(self.sniff)
+        );
+        println!(
+            "    - Read/extract          : {} (summed over {read_threads} read threads)",
+            secs(self.read)
+        );
+        println!("    - Waiting for reads     : {}", secs(self.wait));
+        println!("    - Add documents         : {}", secs(self.add));
+        println!("    - Tokenize and commit   : {}", secs(self.commit));
+        println!("    - Save metadata         : {}", secs(self.save));
+        println!("    - Total                 : {}", secs(self.total));
+    }
+}
+
+#[derive(Default)]
+struct SkipStats {
+    unsupported_extension: usize,
+    too_large: usize,
+    /// Below `min_file_size`
+    too_small: usize,
+    binary: usize,
+    read_errors: usize,
+    ocr_failures: usize,
+    duplicate_path: usize,
+    /// The first `max_recorded` skipped files, saved for `last-errors`
+    recorded: Vec<SkippedFile>,
+    max_recorded: usize,
+}
+
+impl SkipStats {
+    /// Prints the `[skip]` line for `path`, counts it, and records it for `last-errors`.
+    fn skip(&mut self, reason: SkipReason, path: &Path, message: String) {
+        eprintln!("  [skip] {message}");
+        match reason {
+            SkipReason::UnsupportedExtension => self.unsupported_extension += 1,
+            SkipReason::TooLarge => self.too_large += 1,
+            SkipReason::TooSmall => self.too_small += 1,
+            SkipReason::Binary => self.binary += 1,
+            SkipReason::ReadError => self.read_errors += 1,
+            SkipReason::DuplicatePath => self.duplicate_path += 1,
+            SkipReason::OcrFailure => self.ocr_failures += 1,
+        }
+        if self.recorded.len() < self.max_recorded {
+            self.recorded.push(SkippedFile {
+                path: path.to_string_lossy().to_string(),
+                reason,
+                message,
+            });
+        }
+    }
+
+    fn total(&self) -> usize {
+        self.unsupported_extension
+            + self.too_large
+            + self.too_small
+            + self.binary
+            + self.read_errors
+            + self.ocr_failures
+            + self.duplicate_path
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct FileMetadata {
+    path: String,
+    modified: i64,
+    size: u64,
+    hash: String,
+    /// Whether `contents` came from OCR rather than reading the file as text
+    #[serde(default)]
+    ocr: bool,
+    /// Owner and permissions at indexing time (Unix only), so `chmod`/`chown` trigger a re-index
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ownership: Option<FileOwnership>,
+    /// Resolved target when the file was reached through a symlink (`--follow-symlinks` only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    symlink_target: Option<String>,
+    /// Modification time and size of the sidecar indexed with this file, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sidecar: Option<SidecarStamp>,
+    /// Indexed by path alone (`--index-binary-paths`); dropped by runs without the flag
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    path_only: bool,
+    /// Run that last added or updated this file, so `--resume` can count what it already did
+    #[serde(default, skip_serializing_if = "is_zero")]
+    generation: u64,
+}
+
+/// A sibling `<file><sidecar_suffix>` whose text is indexed as part of `<file>`.
+struct Sidecar {
+    path: PathBuf,
+    stamp: SidecarStamp,
+}
+
+/// Change-detection fields for a sidecar, recorded in the main file's metadata.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct SidecarStamp {
+    modified: i64,
+    size: u64,
+}
+
+/// Returns the sidecar next to `path`, if `suffix` is set and the file exists.
+fn find_sidecar(path: &Path, suffix: &str) -> Option<Sidecar> {
+    if suffix.is_empty() {
+        return None;
+    }
+    let mut name = path.file_name()?.to_os_string();
+    name.push(suffix);
+    let sidecar_path = path.with_file_name(name);
+    let metadata = fs::metadata(&sidecar_path).ok().filter(|m| m.is_file())?;
+    let modified = file_modified_timestamp(&metadata).ok()?;
+
+    Some(Sidecar {
+        path: sidecar_path,
+        stamp: SidecarStamp {
+            modified,
+            size: metadata.len(),
+        },
+    })
+}
+
+fn read_sidecar(sidecar: &Sidecar, size_limits: &SizeLimits) -> Result<FileReadResult> {
+    let limit = size_limits.limit_for(&sidecar.path);
+    if sidecar.stamp.size > limit {
+        anyhow::bail!("sidecar exceeds size limit ({} bytes)", sidecar.stamp.size);
+    }
+    read_file_streaming(&sidecar.path, sidecar.stamp.size, limit)
+}
+
+/// Files tracked by git under `root`, relative to it, from `git ls-files`. Fails when git
+/// is not installed or `root` is not inside a work tree.
+fn git_tracked_files(root: &Path) -> Result<Vec<PathBuf>> {
+    let output = process::Command::new("git")
+        .arg("-C")
+        .arg(root)
+        .args(["ls-files", "-z", "--cached"])
+        .stdin(process::Stdio::null())
+        .output()
+        .context("Failed to run `git ls-files`; is git installed and on PATH?")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "{} is not inside a git repository ({})",
+            root.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(output
+        .stdout
+        .split(|&b| b == 0)
+        .filter(|name| !name.is_empty())
+        .map(path_from_git_bytes)
+        .collect())
+}
+
+#[cfg(unix)]
+fn path_from_git_bytes(name: &[u8]) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+
+    PathBuf::from(std::ffi::OsStr::from_bytes(name))
+}
+
+#[cfg(not(unix))]
+fn path_from_git_bytes(name: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(name).as_ref())
+}
+
+/// Applies the walk's depth limit and excluded directories to a tracked file's
+/// root-relative path.
+fn tracked_file_included(
+    relative: &Path,
+    max_depth: Option<usize>,
+    excluded_dirs: &HashSet<&str>,
+) -> bool {
+    let dirs: Vec<_> = relative
+        .parent()
+        .into_iter()
+        .flat_map(Path::components)
+        .collect();
+    max_depth.is_none_or(|depth| dirs.len() <= depth)
+        && !dirs.iter().any(|dir| {
+            dir.as_os_str()
+                .to_str()
+                .is_some_and(|name| excluded_dirs.contains(name))
+        })
+}
+
+/// Patterns read from `index --ignore-file`, matched against root-relative paths. The
+/// syntax is a subset of `.gitignore`: `#` comments, `*`/`?`/`**` globs, a trailing `/` for
+/// directories only, and patterns containing a `/` anchored at the root (others match a
+/// name at any depth). Negation (`!pattern`) is rejected rather than silently ignored.
+#[derive(Default)]
+struct IgnoreRules {
+    /// Patterns matching files and directories
+    any: Option<GlobSet>,
+    /// Patterns written with a trailing `/`
+    dirs_only: Option<GlobSet>,
+    patterns: usize,
+}
+
+impl IgnoreRules {
+    fn load(paths: &[PathBuf]) -> Result<Self> {
+        let mut any = GlobSetBuilder::new();
+        let mut dirs_only = GlobSetBuilder::new();
+        let (mut any_count, mut dirs_count) = (0, 0);
+
+        for path in paths {
+            let text = fs::read_to_string(path)
+                .with_context(|| format!("Failed to read ignore file {}", path.display()))?;
+            for (number, line) in text.lines().enumerate() {
+                let line = line.trim();
+                if line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+                let location = || format!("{}:{}", path.display(), number + 1);
+                if line.starts_with('!') {
+                    anyhow::bail!("{}: negated patterns (`!`) are not supported", location());
+                }
+                let (pattern, dir_only) = match line.strip_suffix('/') {
+                    Some(dir) => (dir, true),
+                    None => (line, false),
+                };
+                let pattern = match pattern.strip_prefix('/') {
+                    Some(anchored) => anchored.to_string(),
+                    None if pattern.contains('/') => pattern.to_string(),
+                    None => format!("**/{pattern}"),
+                };
+                let glob = globset::GlobBuilder::new(&pattern)
+                    .literal_separator(true)
+                    .build()
+                    .with_context(|| format!("{}: invalid pattern `{line}`", location()))?;
+                if dir_only {
+                    dirs_only.add(glob);
+                    dirs_count += 1;
+                } else {
+                    any.add(glob);
+                    any_count += 1;
+                }
+            }
+        }
+
+        let build = |builder: GlobSetBuilder, count: usize| -> Result<Option<GlobSet>> {
+            if count == 0 {
+                return Ok(None);
+            }
+            builder
+                .build()
+                .map(Some)
+                .context("Failed to compile ignore patterns")
+        };
+        Ok(Self {
+            any: build(any, any_count)?,
+            dirs_only: build(dirs_only, dirs_count)?,
+            patterns: any_count + dirs_count,
+        })
+    }
+
+    fn len(&self) -> usize {
+        self.patterns
+    }
+
+    fn is_ignored(&self, relative: &Path, is_dir: bool) -> bool {
+        self.any.as_ref().is_some_and(|set| set.is_match(relative))
+            || (is_dir
+                && self
+                    .dirs_only
+                    .as_ref()
+                    .is_some_and(|set| set.is_match(relative)))
+    }
+
+    /// For paths not produced by the walk (`--git-tracked-only`), where ignored parent
+    /// directories were never pruned.
+    fn is_ignored_with_parents(&self, relative: &Path) -> bool {
+        self.is_ignored(relative, false)
+            || relative
+                .ancestors()
+                .skip(1)
+                .filter(|dir| !dir.as_os_str().is_empty())
+                .any(|dir| self.is_ignored(dir, true))
+    }
+}
+
+/// Owner and permission bits indexed for audit filters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct FileOwnership {
+    uid: u32,
+    gid: u32,
+    /// Permission bits only (`mode & 0o7777`), without the file type
+    mode: u32,
+}
+
+#[cfg(unix)]
+fn file_ownership(metadata: &fs::Metadata) -> Option<FileOwnership> {
+    use std::os::unix::fs::MetadataExt;
+
+    Some(FileOwnership {
+        uid: metadata.uid(),
+        gid: metadata.gid(),
+        mode: metadata.mode() & 0o7777,
+    })
+}
+
+#[cfg(not(unix))]
+fn file_ownership(_metadata: &fs::Metadata) -> Option<FileOwnership> {
+    None
+}
+
+#[derive(Default)]
+struct IndexingStats {
+    indexed: usize,
+    updated: usize,
+    unchanged: usize,
+    removed: usize,
+    with_sidecar: usize,
+    /// Binary or unsupported files indexed by path alone (`--index-binary-paths`)
+    path_only: usize,
+    /// Unchanged files the resumed run had already committed (`--resume`)
+    resumed: usize,
+}
+
+// ---- Server ----
+
+/// Index handles kept open by `serve` so each request only pays for the search itself.
+struct ServerState {
+    cfg: AppConfig,
+    index: Index,
+    reader: IndexReader,
+}
+
+impl ServerState {
+    /// Opens the configured index for `serve`, `daemon`, `batch`, `tags`, and `forget`.
+    fn open() -> Result<Self> {
+        let cfg = load_config()?;
+        let index_dir = Path::new(&cfg.index_dir);
+
+        if !tantivy_index_exists(index_dir) {
+            return Err(categorized(
+                FailureKind::IndexMissing,
+                format!(
+                    "Index missing at {}. Re-run `vaultsearch init` to recreate it.",
+                    index_dir.display()
+                ),
+            )
+            .into());
+        }
+
+        // `reader_reload` decides how commits from later `vaultsearch index` runs are picked up.
+        let (index, reader) = open_index_reader(index_dir, &cfg.analysis, cfg.reader_reload)?;
+        Ok(Self { cfg, index, reader })
+    }
+
+    fn search(&self, request: &SearchRequest) -> SearchResponse {
+        if request.limit == 0 {
+            return SearchResponse::Error("limit must be at least 1".to_string());
+        }
+        if request.limit_per_dir == Some(0) {
+            return SearchResponse::Error("limit_per_dir must be at least 1".to_string());
+        }
+        if request.offset.saturating_add(request.limit) > MAX_RESULT_WINDOW {
+            return SearchResponse::Error(format!(
+                "offset + limit must be at most {MAX_RESULT_WINDOW}"
+            ));
+        }
+        if self.cfg.reader_reload == ReaderReload::Manual {
+            // Nothing watches for commits, so catch up on them before answering.
+            if let Err(e) = self.reader.reload() {
+                return SearchResponse::Error(format!("Failed to reload index reader: {e}"));
+            }
+        }
+        let searcher = self.reader.searcher();
+        let root = PathBuf::from(&self.cfg.root);
+        match execute_search(&self.index, &searcher, &self.cfg, request, &root) {
+            Ok(results) => SearchResponse::Results(results),
+            Err(e) => SearchResponse::Error(format!("{e:#}")),
+        }
+    }
+}
+
+/// Answers newline-delimited JSON `SearchRequest`s with one `SearchResponse` line each
+/// until the client disconnects. A line longer than `MAX_REQUEST_LINE_BYTES` gets an error
+/// response and ends the connection, as does a read timeout set on the stream.
+fn serve_connection(state: &ServerState, reader: impl Read, mut writer: impl Write) -> Result<()> {
+    let mut reader = BufReader::new(reader);
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        let read = (&mut reader)
+            .take(MAX_REQUEST_LINE_BYTES + 1)
+            .read_until(b'\n', &mut line)
+            .map_err(|e| match e.kind() {
+                io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => {
+                    anyhow::anyhow!("No complete request within {REMOTE_TIMEOUT_SECS}s")
+                }
+                _ => anyhow::Error::new(e).context("Failed to read request"),
+            })?;
+        if read == 0 {
+            return Ok(());
+        }
+        let oversized = line.len() as u64 > MAX_REQUEST_LINE_BYTES && !line.ends_with(b"\n");
+        let response = if oversized {
+            SearchResponse::Error(format!(
+                "Invalid request: line exceeds {MAX_REQUEST_LINE_BYTES} bytes"
+            ))
+        } else {
+            let text = String::from_utf8_lossy(&line);
+            if text.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str::<SearchRequest>(&text) {
+                Ok(request) => state.search(&request),
+                Err(e) => SearchResponse::Error(format!("Invalid request: {e}")),
+            }
+        };
+        serde_json::to_writer(&mut writer, &response).context("Failed to encode response")?;
+        writer.write_all(b"\n")?;
+        writer.flush()?;
+        if oversized {
+            return Ok(());
+        }
+    }
+}
+
+fn remote_search(addr: &str, request: &SearchRequest) -> Result<Vec<SearchHit>> {
+    let stream = TcpStream::connect(addr)
+        .with_context(|| format!("Failed to connect to vaultsearch server at {addr}"))?;
+    stream
+        .set_read_timeout(Some(Duration::from_secs(REMOTE_TIMEOUT_SECS)))
+        .context("Failed to configure connection")?;
+    let writer = stream
+        .try_clone()
+        .context("Failed to configure connection")?;
+    exchange_request(stream, writer, request).with_context(|| format!("Search on {addr} failed"))
+}
+
+/// Asks the local daemon to run `request`; `None` means no daemon is listening.
+#[cfg(unix)]
+fn daemon_search(index_dir: &Path, request: &SearchRequest) -> Result<Option<Vec<SearchHit>>> {
+    use std::os::unix::net::UnixStream;
+
+    let socket_path = daemon_socket_path(index_dir)?;
+    let Ok(stream) = UnixStream::connect(&socket_path) else {
+        return Ok(None);
+    };
+    stream
+        .set_read_timeout(Some(Duration::from_secs(REMOTE_TIMEOUT_SECS)))
+        .context("Failed to configure daemon connection")?;
+    let writer = stream
+        .try_clone()
+        .context("Failed to configure daemon connection")?;
+    exchange_request(stream, writer, request)
+        .context("Search via daemon failed")
+        .map(Some)
+}
+
+#[cfg(not(unix))]
+fn daemon_search(_index_dir: &Path, _request: &SearchRequest) -> Result<Option<Vec<SearchHit>>> {
+    Ok(None)
+}
+
+/// The socket a daemon serving `index_dir` listens on, so daemons for different configs
+/// or roots never answer for each other. It is named after a hash of the path because Unix
+/// socket paths are limited to about 100 bytes.
+fn daemon_socket_path(index_dir: &Path) -> Result<PathBuf> {
+    let proj_dirs = get_project_dirs()?;
+    let hash = blake3::hash(index_dir.as_os_str().as_encoded_bytes());
+    Ok(proj_dirs.data_local_dir().join(format!(
+        "{DAEMON_SOCKET_PREFIX}{}.sock",
+        &hash.to_hex()[..16]
+    )))
+}
+
+/// Sends one request line and reads the matching response line.
+fn exchange_request(
+    reader: impl Read,
+    mut writer: impl Write,
+    request: &SearchRequest,
+) -> Result<Vec<SearchHit>> {
+    serde_json::to_writer(&mut writer, request).context("Failed to encode request")?;
+    writer.write_all(b"\n")?;
+    writer.flush()?;
+
+    let mut line = String::new();
+    BufReader::new(reader)
+        .read_line(&mut line)
+        .context("Failed to read response")?;
+    if line.is_empty() {
+        anyhow::bail!("Server closed the connection without responding");
+    }
+    match serde_json::from_str(&line).context("Server sent an invalid response")? {
+        SearchResponse::Results(results) => Ok(results),
+        SearchResponse::Error(message) => anyhow::bail!("Server error: {message}"),
+    }
+}
+
+// ---- Config helpers ----
+
+fn get_project_dirs() -> Result<ProjectDirs> {
+    ProjectDirs::from("com", "vault", "vaultsearch")
+        .context("Could not determine platform-specific config directory")
+}
+
+fn config_file_path(proj_dirs: &ProjectDirs) -> Result<PathBuf> {
+    Ok(resolve_config_path(proj_dirs).0)
+}
+
+/// The config file in effect, made absolute so a saved path survives a change of directory.
+fn absolute_config_path() -> Result<PathBuf> {
+    let (path, _) = resolve_config_path(&get_project_dirs()?);
+    std::path::absolute(&path)
+        .with_context(|| format!("Failed to resolve config path {}", path.display()))
+}
+
+/// Which setting chose the config file, in order of precedence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ConfigSource {
+    Flag,
+    Env,
+    Default,
+}
+
+impl ConfigSource {
+    fn describe(self) -> &'static str {
+        match self {
+            ConfigSource::Flag => "from --config",
+            ConfigSource::Env => "from $VAULTSEARCH_CONFIG",
+            ConfigSource::Default => "default location",
+        }
+    }
+}
+
+/// `--config`, then `$VAULTSEARCH_CONFIG`, then `config.toml` in the platform config directory.
+fn resolve_config_path(proj_dirs: &ProjectDirs) -> (PathBuf, ConfigSource) {
+    choose_config_path(
+        CONFIG_FLAG.get().map(PathBuf::as_path),
+        std::env::var_os(CONFIG_ENV_VAR),
+        proj_dirs.config_dir().join("config.toml"),
+    )
+}
+
+fn choose_config_path(
+    flag: Option<&Path>,
+    env: Option<std::ffi::OsString>,
+    default: PathBuf,
+) -> (PathBuf, ConfigSource) {
+    if let Some(path) = flag {
+        return (path.to_path_buf(), ConfigSource::Flag);
+    }
+    match env.filter(|value| !value.is_empty()) {
+        Some(path) => (PathBuf::from(path), ConfigSource::Env),
+        None => (default, ConfigSource::Default),
+    }
+}
+
+fn index_dir_path(proj_dirs: &ProjectDirs) -> Result<PathBuf> {
+    let mut path = proj_dirs.data_local_dir().to_path_buf();
+    path.push("index");
+    Ok(path)
+}
+
+/// The `init --index-dir` path (with `~` and variables expanded, made absolute), or the
+/// platform default.
+fn resolve_index_dir(proj_dirs: &ProjectDirs, explicit: Option<&str>) -> Result<PathBuf> {
+    let Some(explicit) = explicit else {
+        return index_dir_path(proj_dirs);
+    };
+    let expanded = shellexpand::full(explicit).map_err(|e| {
+        anyhow::anyhow!(
+            "--index-dir `{explicit}` references environment variable `{}`, which is not set",
+            e.var_name
+        )
+    })?;
+    let path = PathBuf::from(expanded.as_ref());
+    if path.is_absolute() {
+        return Ok(path);
+    }
+    let cwd = std::env::current_dir().context("Failed to determine current directory")?;
+    Ok(cwd.join(path))
+}
+
+fn dir_has_entries(dir: &Path) -> bool {
+    fs::read_dir(dir).is_ok_and(|mut entries| entries.next().is_some())
+}
+
+/// Fails early with a clear message when `dir` cannot be written, rather than partway
+/// through the first indexing run.
+fn ensure_dir_writable(dir: &Path) -> Result<()> {
+    let probe = dir.join(".vaultsearch-write-test");
+    fs::write(&probe, b"").with_context(|| {
+        format!(
+            "Index directory {} is not writable; choose another --index-dir",
+            dir.display()
+        )
+    })?;
+    fs::remove_file(&probe)
+        .with_context(|| format!("Failed to remove write probe {}", probe.display()))
+}
+
+/// Built-in settings for commands that can run without a config file (`search --index`).
+fn standalone_config() -> AppConfig {
+    AppConfig {
+        root: String::new(),
+        index_dir: String::new(),
+        last_indexed: None,
+        index_generation: 0,
+        max_depth: None,
+        recursive: None,
+        min_file_size: None,
+        progress_every: None,
+        default_excluded_dirs: default_excluded_dirs(),
+        size_limits: SizeLimits::default(),
+        recency_boost: RecencyBoost::default(),
+        sidecar_suffix: default_sidecar_suffix(),
+        max_recorded_skips: None,
+        synonyms: BTreeMap::new(),
+        search_fields: Vec::new(),
+        field_boosts: BTreeMap::new(),
+        default_relevance_profile: None,
+        relevance_profiles: BTreeMap::new(),
+        roots: BTreeMap::new(),
+        stop_words: StopWords::default(),
+        max_token_length: None,
+        field_routing: FieldRouting::default(),
+        store_relative_paths: false,
+        reader_reload: ReaderReload::default(),
+        cache_results: false,
+        merge_policy: MergePolicyKind::default(),
+        merge_min_segments: None,
+        analysis: ContentsAnalysis::default(),
+        raw_root: None,
+        project_config: None,
+        root_name: None,
+        project_index: false,
+        loaded_from: None,
+    }
+}
+
+/// Loads the config file chosen by `resolve_config_path` and merges the nearest
+/// project-local `.vaultsearch.toml` over it.
+fn load_config() -> Result<AppConfig> {
+    let proj_dirs = get_project_dirs()?;
+    let (config_path, source) = resolve_config_path(&proj_dirs);
+    if !config_path.exists() {
+        let hint = match source {
+            ConfigSource::Default => format!(
+                "Run `vaultsearch init --root <DIR>` to create it, or point --config or \
+                 ${CONFIG_ENV_VAR} at an existing config file."
+            ),
+            ConfigSource::Flag | ConfigSource::Env => {
+                "Check the path, or run `vaultsearch init --root <DIR>` with the same setting to create it."
+                    .to_string()
+            }
+        };
+        return Err(categorized(
+            FailureKind::Config,
+            format!(
+                "No config file at {} ({}). {hint}",
+                config_path.display(),
+                source.describe()
+            ),
+        )
+        .into());
+    }
+    let mut cfg = load_global_config(&config_path)?;
+    cfg.loaded_from = Some((config_path, source));
+
+    let cwd = std::env::current_dir().context("Failed to determine current directory")?;
+    if let Some(project_path) = find_project_config(&cwd) {
+        apply_project_config(&mut cfg, &project_path)
+            .map_err(|e| e.context(categorized(FailureKind::Config, "Invalid project config")))?;
+    }
+
+    Ok(cfg)
+}
+
+fn load_global_config(config_path: &Path) -> Result<AppConfig> {
+    let data = fs::read_to_string(config_path).with_context(|| {
+        categorized(
+            FailureKind::Config,
+            format!(
+                "Failed to read config file at {}. Did you run `vaultsearch init`?",
+                config_path.display()
+            ),
+        )
+    })?;
+
+    let mut cfg: AppConfig = toml::from_str(&data)
+        .with_context(|| categorized(FailureKind::Config, "Failed to parse config TOML"))?;
+
+    let expanded = expand_root(&cfg.root)
+        .map_err(|e| e.context(categorized(FailureKind::Config, "Invalid root in config")))?;
+    if expanded != cfg.root {
+        cfg.raw_root = Some(std::mem::replace(&mut cfg.root, expanded));
+    }
+    let stop_words = cfg.stop_words.resolve().map_err(|e| {
+        e.context(categorized(
+            FailureKind::Config,
+            "Invalid stop_words in config",
+        ))
+    })?;
+    let max_token_length = match cfg.max_token_length {
+        Some(0) => {
+            return Err(categorized(
+                FailureKind::Config,
+                "max_token_length in config must be at least 1",
+            )
+            .into())
+        }
+        Some(length) => length,
+        None => DEFAULT_MAX_TOKEN_LENGTH,
+    };
+    cfg.analysis = ContentsAnalysis {
+        stop_words,
+        max_token_length,
+        code_extensions: cfg.field_routing.code_extensions(),
+    };
+
+    Ok(cfg)
+}
+
+/// Prints where the effective config came from and the settings that decide what is
+/// indexed, for `--show-config-source`. Goes to stderr so search results stay parseable.
+fn print_config_source(cfg: &AppConfig) {
+    eprintln!("Config in effect:");
+    match &cfg.loaded_from {
+        Some((path, source)) => eprintln!(
+            "  Config file    : {} ({})",
+            path.display(),
+            source.describe()
+        ),
+        None => eprintln!("  Config file    : none"),
+    }
+    match &cfg.project_config {
+        Some(path) => eprintln!(
+            "  Project config : {} (overrides the config file)",
+            path.display()
+        ),
+        None => eprintln!("  Project config : none ({PROJECT_CONFIG_FILE} not found)"),
+    }
+    eprintln!("  Root directory : {}", cfg.root);
+    eprintln!("  Index directory: {}", cfg.index_dir);
+    let mut per_extension: Vec<String> = cfg
+        .size_limits
+        .per_extension
+        .iter()
+        .map(|(ext, limit)| format!("{ext} = {limit}"))
+        .collect();
+    per_extension.sort();
+    if per_extension.is_empty() {
+        eprintln!("  Max file size  : {} bytes", cfg.size_limits.default);
+    } else {
+        eprintln!(
+            "  Max file size  : {} bytes ({})",
+            cfg.size_limits.default,
+            per_extension.join(", ")
+        );
+    }
+    if let Some(min) = cfg.min_file_size {
+        eprintln!("  Min file size  : {min} bytes");
+    }
+    eprintln!("  Extensions     : {}", TEXT_LIKE_EXTENSIONS.join(", "));
+    if !cfg.analysis.code_extensions.is_empty() {
+        eprintln!(
+            "  Code extensions: {} ([field_routing])",
+            cfg.analysis.code_extensions.join(", ")
+        );
+    }
+}
+
+/// Returns the first `.vaultsearch.toml` found in `start` or any of its ancestors.
+fn find_project_config(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .map(|dir| dir.join(PROJECT_CONFIG_FILE))
+        .find(|candidate| candidate.is_file())
+}
+
+fn apply_project_config(cfg: &mut AppConfig, project_path: &Path) -> Result<()> {
+    let data = fs::read_to_string(project_path)
+        .with_context(|| format!("Failed to read {}", project_path.display()))?;
+    let project: ProjectConfig = toml::from_str(&data)
+        .with_context(|| format!("Failed to parse {}", project_path.display()))?;
+
+    if let Some(root) = project.root {
+        let expanded = PathBuf::from(expand_root(&root)?);
+        let project_dir = project_path.parent().unwrap_or(Path::new("."));
+        let resolved = fs::canonicalize(project_dir.join(&expanded)).with_context(|| {
+            format!(
+                "Root `{root}` from {} does not exist",
+                project_path.display()
+            )
+        })?;
+        let global_root = fs::canonicalize(&cfg.root).unwrap_or_else(|_| PathBuf::from(&cfg.root));
+        if resolved != global_root {
+            // Sharing the global index would make `index` here remove every file of the global root.
+            let index_dir = project_index_dir(&resolved)?;
+            let state = load_project_run_state(&index_dir);
+            cfg.index_dir = index_dir.to_string_lossy().to_string();
+            cfg.last_indexed = state.last_indexed;
+            cfg.index_generation = state.index_generation;
+            cfg.project_index = true;
+        }
+        cfg.root = resolved.to_string_lossy().to_string();
+        cfg.raw_root = None;
+    }
+    if project.max_depth.is_some() {
+        cfg.max_depth = project.max_depth;
+    }
+    if project.recursive.is_some() {
+        cfg.recursive = project.recursive;
+    }
+    if project.min_file_size.is_some() {
+        cfg.min_file_size = project.min_file_size;
+    }
+    if project.progress_every.is_some() {
+        cfg.progress_every = project.progress_every;
+    }
+    if let Some(dirs) = project.default_excluded_dirs {
+        cfg.default_excluded_dirs = dirs;
+    }
+    if let Some(size_limits) = project.size_limits {
+        cfg.size_limits = size_limits;
+    }
+    if let Some(suffix) = project.sidecar_suffix {
+        cfg.sidecar_suffix = suffix;
+    }
+    if let Some(synonyms) = project.synonyms {
+        cfg.synonyms = synonyms;
+    }
+    if let Some(fields) = project.search_fields {
+        cfg.search_fields = fields;
+    }
+    if let Some(boosts) = project.field_boosts {
+        cfg.field_boosts = boosts;
+    }
+    cfg.project_config = Some(project_path.to_path_buf());
+
+    Ok(())
+}
+
+/// Expands `~` and environment variables in a configured root, then canonicalizes it when possible.
+fn expand_root(root: &str) -> Result<String> {
+    let expanded = shellexpand::full(root).map_err(|e| {
+        anyhow::anyhow!(
+            "Root `{root}` references environment variable `{}`, which is not set",
+            e.var_name
+        )
+    })?;
+
+    if expanded == root {
+        return Ok(root.to_string());
+    }
+
+    Ok(fs::canonicalize(expanded.as_ref())
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or_else(|_| expanded.into_owned()))
+}
+
+fn save_config(cfg: &AppConfig) -> Result<()> {
+    let proj_dirs = get_project_dirs()?;
+    let config_path = config_file_path(&proj_dirs)?;
+    if let Some(name) = &cfg.root_name {
+        // Only the `[roots]` entry's run state changes; its root and index stay as configured.
+        let mut global = load_global_config(&config_path)?;
+        let named = global.roots.get_mut(name).with_context(|| {
+            categorized(
+                FailureKind::Config,
+                format!("Root `{name}` was removed from the config while indexing"),
+            )
+        })?;
+        named.last_indexed = cfg.last_indexed.clone();
+        named.index_generation = cfg.index_generation;
+        return write_config(&global, &config_path);
+    }
+    if cfg.project_index {
+        return save_project_run_state(
+            Path::new(&cfg.index_dir),
+            &ProjectRunState {
+                last_indexed: cfg.last_indexed.clone(),
+                index_generation: cfg.index_generation,
+            },
+        );
+    }
+    if cfg.project_config.is_some() {
+        // Keep project overrides out of the global file; only run state is saved there.
+        let mut global = load_global_config(&config_path)?;
+        global.last_indexed = cfg.last_indexed.clone();
+        global.index_generation = cfg.index_generation;
+        return write_config(&global, &config_path);
+    }
+    write_config(cfg, &config_path)
+}
+
+fn write_config(cfg: &AppConfig, config_path: &Path) -> Result<()> {
+    let cfg_toml = match &cfg.raw_root {
+        Some(raw_root) => {
+            let mut unexpanded = cfg.clone();
+            unexpanded.root = raw_root.clone();
+            toml::to_string_pretty(&unexpanded)
+        }
+        None => toml::to_string_pretty(cfg),
+    }
+    .context("Failed to serialize config to TOML")?;
+    write_atomically(config_path, cfg_toml.as_bytes())
+        .with_context(|| format!("Failed to write config file: {}", config_path.display()))?;
+    Ok(())
+}
+
+/// Writes `contents` to a temporary file next to `path`, then renames it over `path`, so
+/// an interrupted write (crash, full disk) leaves either the old file or the new one.
+fn write_atomically(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?;
+    let temp_path = path.with_file_name(format!(
+        ".{}.tmp-{}",
+        file_name.to_string_lossy(),
+        process::id()
+    ));
+
+    let result = (|| {
+        let mut file = fs::File::create(&temp_path)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+        fs::rename(&temp_path, path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result
+}
+
+// ---- Index helpers ----
+
+fn tantivy_index_exists(index_dir: &Path) -> bool {
+    index_dir.join("meta.json").exists()
+}
+
+fn create_empty_index(index_dir: &Path, analysis: &ContentsAnalysis) -> Result<()> {
+    let schema = build_schema(analysis);
+    let _index =
+        Index::create_in_dir(index_dir, schema).context("Failed to create Tantivy index")?;
+    Ok(())
+}
+
+/// Opens the index and registers the `contents` tokenizer for the configured `stop_words`
+/// and `max_token_length`, failing if the index was built with different settings.
+fn open_index(index_dir: &Path, analysis: &ContentsAnalysis) -> Result<Index> {
+    if let Some(concern) = index_filesystem_concern(index_dir) {
+        eprintln!(
+            "  [warn] The index at {} is on {concern}; index operations may fail intermittently \
+             or run slowly. Consider an `index_dir` on a local disk.",
+            index_dir.display()
+        );
+    }
+    let index = Index::open_in_dir(index_dir).context("Failed to open Tantivy index")?;
+
+    let expected_code = code_tokenizer_name(analysis);
+    if text_field_tokenizer(&index.schema(), "code") != expected_code {
+        return Err(categorized(
+            FailureKind::Config,
+            "The index was built with a different `[field_routing]` than the config. Re-run \
+             `vaultsearch init --force` to rebuild it.",
+        )
+        .into());
+    }
+    if let Some(name) = &expected_code {
+        index.tokenizers().register(name, code_analyzer(analysis));
+    }
+
+    let expected = contents_tokenizer_name(analysis);
+    if let Some(actual) = text_field_tokenizer(&index.schema(), "contents") {
+        if actual != expected {
+            return Err(categorized(
+                FailureKind::Config,
+                "The index was built with different stop words or token length than \
+                 `stop_words` and `max_token_length` in the config. Re-run \
+                 `vaultsearch init --force` to rebuild it.",
+            )
+            .into());
+        }
+    }
+    if !analysis.is_default() {
+        index
+            .tokenizers()
+            .register(&expected, contents_analyzer(analysis));
+    }
+    Ok(index)
+}
+
+/// How many times opening a reader is retried after a concurrent commit removed a file.
+const READER_OPEN_ATTEMPTS: usize = 3;
+
+/// Opens the index and a reader on its latest commit. A concurrent `vaultsearch index`
+/// can commit and garbage-collect the previous commit's segment files between reading
+/// `meta.json` and opening those segments; the open is then retried on the newer commit.
+fn open_index_reader(
+    index_dir: &Path,
+    analysis: &ContentsAnalysis,
+    reload: ReaderReload,
+) -> Result<(Index, IndexReader)> {
+    let mut attempt = 1;
+    loop {
+        let index = open_index(index_dir, analysis)?;
+        match index
+            .reader_builder()
+            .reload_policy(reload.policy())
+            .try_into()
+        {
+            Ok(reader) => return Ok((index, reader)),
+            Err(err) if attempt < READER_OPEN_ATTEMPTS && is_missing_file_error(&err) => {
+                attempt += 1;
+            }
+            Err(err) => return Err(err).context("Failed to create index reader"),
+        }
+    }
+}
+
+fn is_missing_file_error(err: &tantivy::TantivyError) -> bool {
+    matches!(
+        err,
+        tantivy::TantivyError::OpenReadError(OpenReadError::FileDoesNotExist(_))
+    )
+}
+
+/// A private copy of one index commit for `search --snapshot`, removed on drop.
+struct IndexSnapshot {
+    dir: PathBuf,
+}
+
+impl IndexSnapshot {
+    /// Creates an empty snapshot directory next to the index, so hard links stay on the
+    /// same filesystem.
+    fn create(index_dir: &Path) -> Result<Self> {
+        let name = index_dir
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "index".to_string());
+        let parent = index_dir.parent().unwrap_or_else(|| Path::new("."));
+        let dir = parent.join(format!(".{name}-snapshot-{}", process::id()));
+        if dir.exists() {
+            fs::remove_dir_all(&dir)
+                .with_context(|| format!("Failed to remove stale snapshot {}", dir.display()))?;
+        }
+        fs::create_dir(&dir)
+            .with_context(|| format!("Failed to create snapshot directory {}", dir.display()))?;
+        Ok(Self { dir })
+    }
+}
+
+impl Drop for IndexSnapshot {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Copies the latest commit of the index into a private directory and opens it. Segment
+/// files are immutable once committed, so hard-linking `meta.json` and then every file it
+/// names captures one consistent commit. If a concurrent `vaultsearch index` deletes a
+/// segment file before it is linked, the snapshot starts over from the newer commit.
+fn snapshot_index(
+    index_dir: &Path,
+    analysis: &ContentsAnalysis,
+) -> Result<(IndexSnapshot, Index, IndexReader)> {
+    let mut attempt = 1;
+    loop {
+        let snapshot = IndexSnapshot::create(index_dir)?;
+        link_or_copy(
+            &index_dir.join("meta.json"),
+            &snapshot.dir.join("meta.json"),
+        )
+        .context("Failed to snapshot meta.json")?;
+        // Opening only reads meta.json; segment files are first touched by the reader.
+        let metas = Index::open_in_dir(&snapshot.dir)
+            .context("Failed to open index snapshot")?
+            .searchable_segment_metas()
+            .context("Failed to list index segments")?;
+        for file in metas.iter().flat_map(|meta| meta.list_files()) {
+            match link_or_copy(&index_dir.join(&file), &snapshot.dir.join(&file)) {
+                // Optional segment components (such as deletes) may not exist; the reader
+                // below reports any required file that went missing.
+                Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+                result => result
+                    .with_context(|| format!("Failed to snapshot index file {}", file.display()))?,
+            }
+        }
+
+        let index = open_index(&snapshot.dir, analysis)?;
+        match index.reader() {
+            Ok(reader) => return Ok((snapshot, index, reader)),
+            Err(err) if attempt < READER_OPEN_ATTEMPTS && is_missing_file_error(&err) => {
+                attempt += 1;
+            }
+            Err(err) => return Err(err).context("Failed to create index reader"),
+        }
+    }
+}
+
+fn link_or_copy(from: &Path, to: &Path) -> io::Result<()> {
+    fs::hard_link(from, to).or_else(|_| fs::copy(from, to).map(|_| ()))
+}
+
+/// What `--check` found in a healthy index.
+#[derive(Debug)]
+struct IndexCheck {
+    documents: u64,
+    segments: usize,
+}
+
+impl std::fmt::Display for IndexCheck {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "ok ({} documents in {} segments)",
+            self.documents, self.segments
+        )
+    }
+}
+
+/// Runs `probe_index`, turning Tantivy errors and panics into one `IndexCorrupt` error
+/// that says how to recover. Errors vaultsearch already categorizes (such as a stop-words
+/// mismatch) are passed through unchanged.
+fn check_index_integrity(index_dir: &Path, analysis: &ContentsAnalysis) -> Result<IndexCheck> {
+    // Keep the default hook from printing a backtrace-style message for a handled panic.
+    let previous_hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let outcome = panic::catch_unwind(AssertUnwindSafe(|| probe_index(index_dir, analysis)));
+    panic::set_hook(previous_hook);
+
+    let problem = match outcome {
+        Ok(Ok(check)) => return Ok(check),
+        Ok(Err(err)) if err.downcast_ref::<CategorizedError>().is_some() => return Err(err),
+        Ok(Err(err)) => err,
+        Err(payload) => anyhow::anyhow!("Tantivy panicked: {}", panic_message(payload.as_ref())),
+    };
+    Err(problem.context(categorized(
+        FailureKind::IndexCorrupt,
+        format!(
+            "Index at {} appears corrupt. Re-run `vaultsearch init --force` to rebuild it.",
+            index_dir.display()
+        ),
+    )))
+}
+
+/// Opens the index, verifies file checksums, creates a reader, and loads the first
+/// document of a match-all query, which exercises every structure a search reads.
+fn probe_index(index_dir: &Path, analysis: &ContentsAnalysis) -> Result<IndexCheck> {
+    let index = open_index(index_dir, analysis)?;
+    let corrupted = index
+        .validate_checksum()
+        .context("Failed to verify index checksums")?;
+    if !corrupted.is_empty() {
+        let mut files: Vec<String> = corrupted
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect();
+        files.sort();
+        anyhow::bail!("checksum mismatch in {}", files.join(", "));
+    }
+
+    let reader = index.reader().context("Failed to create index reader")?;
+    let searcher = reader.searcher();
+    let top_docs = searcher
+        .search(&AllQuery, &TopDocs::with_limit(1))
+        .context("Match-all query failed")?;
+    for (_, doc_address) in top_docs {
+        let _: TantivyDocument = searcher
+            .doc(doc_address)
+            .context("Failed to load a stored document")?;
+    }
+
+    Ok(IndexCheck {
+        documents: searcher.num_docs(),
+        segments: searcher.segment_readers().len(),
+    })
+}
+
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> &str {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown panic")
+}
+
+/// Tokenizer for `contents`: Tantivy's `default`, or a name derived from the token length
+/// and stop-word list so that changing either changes the schema and requires a rebuild.
+fn contents_tokenizer_name(analysis: &ContentsAnalysis) -> String {
+    let mut parts = Vec::new();
+    if analysis.max_token_length != DEFAULT_MAX_TOKEN_LENGTH {
+        parts.push(format!("max_token_{}", analysis.max_token_length));
+    }
+    if !analysis.stop_words.is_empty() {
+        let hash = blake3::hash(analysis.stop_words.join("\n").as_bytes());
+        parts.push(format!("stop_words_{}", &hash.to_hex()[..12]));
+    }
+    if parts.is_empty() {
+        "default".to_string()
+    } else {
+        parts.join("_")
+    }
+}
+
+/// Tantivy's `default` tokenizer chain with the configured token length limit, followed
+/// by a filter dropping `stop_words`.
+fn contents_analyzer(analysis: &ContentsAnalysis) -> TextAnalyzer {
+    TextAnalyzer::builder(SimpleTokenizer::default())
+        .filter(RemoveLongFilter::limit(analysis.max_token_length + 1))
+        .filter(LowerCaser)
+        .filter(StopWordFilter::remove(analysis.stop_words.clone()))
+        .build()
+}
+
+/// Tokenizer for `code`, or `None` when no extensions are routed there. The name is derived
+/// from the routed extensions and token length, so changing either requires a rebuild.
+fn code_tokenizer_name(analysis: &ContentsAnalysis) -> Option<String> {
+    if analysis.code_extensions.is_empty() {
+        return None;
+    }
+    let key = format!(
+        "{}\n{}",
+        analysis.max_token_length,
+        analysis.code_extensions.join("\n")
+    );
+    let hash = blake3::hash(key.as_bytes());
+    Some(format!("code_{}", &hash.to_hex()[..12]))
+}
+
+/// The `contents` tokenizer chain without stop words, since common English words are
+/// meaningful identifiers in code.
+fn code_analyzer(analysis: &ContentsAnalysis) -> TextAnalyzer {
+    TextAnalyzer::builder(SimpleTokenizer::default())
+        .filter(RemoveLongFilter::limit(analysis.max_token_length + 1))
+        .filter(LowerCaser)
+        .build()
+}
+
+/// Tokenizer name recorded for the text field `name` in an index's schema.
+fn text_field_tokenizer(schema: &Schema, name: &str) -> Option<String> {
+    let field = schema.get_field(name).ok()?;
+    match schema.get_field_entry(field).field_type() {
+        FieldType::Str(options) => options
+            .get_indexing_options()
+            .map(|indexing| indexing.tokenizer().to_string()),
+        _ => None,
+    }
+}
+
+/// Filesystem types mounted over the network, as named in `/proc/mounts`.
+const NETWORK_FILESYSTEMS: &[&str] = &[
+    "nfs",
+    "nfs4",
+    "cifs",
+    "smb3",
+    "smbfs",
+    "9p",
+    "afs",
+    "ceph",
+    "glusterfs",
+    "davfs",
+    "fuse.sshfs",
+    "fuse.rclone",
+    "fuse.s3fs",
+];
+
+/// Best-effort description of why `index_dir`'s filesystem is a poor home for an
+/// index (network or removable), or `None` if it looks local or cannot be told.
+#[cfg(target_os = "linux")]
+fn index_filesystem_concern(index_dir: &Path) -> Option<String> {
+    let mounts = fs::read_to_string("/proc/mounts").ok()?;
+    let index_dir = fs::canonicalize(index_dir).ok()?;
+    let mount = mount_for_path(&mounts, &index_dir)?;
+
+    if NETWORK_FILESYSTEMS.contains(&mount.fs_type.as_str()) {
+        return Some(format!(
+            "a network filesystem ({} mounted at {})",
+            mount.fs_type, mount.mount_point
+        ));
+    }
+    let device = mount.device.strip_prefix("/dev/")?;
+    let sys_block = fs::canonicalize(Path::new("/sys/class/block").join(device)).ok()?;
+    // Partitions (sdb1) carry no `removable` flag of their own; their disk (sdb) does.
+    let removable = [sys_block.as_path(), sys_block.parent()?]
+        .iter()
+        .find_map(|dir| fs::read_to_string(dir.join("removable")).ok())?;
+    (removable.trim() == "1").then(|| {
+        format!(
+            "a removable drive ({} mounted at {})",
+            mount.device, mount.mount_point
+        )
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn index_filesystem_concern(_index_dir: &Path) -> Option<String> {
+    None
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct MountEntry {
+    device: String,
+    mount_point: String,
+    fs_type: String,
+}
+
+/// Finds the `/proc/mounts` entry whose mount point most specifically contains `path`.
+/// Later entries win ties, since they are mounted over earlier ones.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn mount_for_path(mounts: &str, path: &Path) -> Option<MountEntry> {
+    mounts
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            Some(MountEntry {
+                device: unescape_mount_field(fields.next()?),
+                mount_point: unescape_mount_field(fields.next()?),
+                fs_type: fields.next()?.to_string(),
+            })
+        })
+        .filter(|entry| path.starts_with(&entry.mount_point))
+        .max_by_key(|entry| Path::new(&entry.mount_point).components().count())
+}
+
+/// Decodes the octal escapes (`\040` for a space) that `/proc/mounts` uses in paths.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn unescape_mount_field(field: &str) -> String {
+    let mut out = String::with_capacity(field.len());
+    let mut rest = field;
+    while let Some(pos) = rest.find('\\') {
+        out.push_str(&rest[..pos]);
+        let escape = rest.get(pos + 1..pos + 4);
+        match escape.and_then(|digits| u8::from_str_radix(digits, 8).ok()) {
+            Some(byte) => {
+                out.push(char::from(byte));
+                rest = &rest[pos + 4..];
+            }
+            None => {
+                out.push('\\');
+                rest = &rest[pos + 1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Total size of the files directly inside the index directory (Tantivy keeps it flat).
+fn index_size_bytes(index_dir: &Path) -> Result<u64> {
+    let mut total = 0;
+    for entry in fs::read_dir(index_dir)
+        .with_context(|| format!("Failed to list index directory {}", index_dir.display()))?
+    {
+        let metadata = entry?.metadata()?;
+        if metadata.is_file() {
+            total += metadata.len();
+        }
+    }
+    Ok(total)
+}
+
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
+
+/// Path as written to the `path` fields and metadata: relative to `root` when given and
+/// the path is inside it, otherwise as is.
+fn stored_path(path: &Path, root: Option<&Path>) -> String {
+    root.and_then(|root| path.strip_prefix(root).ok())
+        .unwrap_or(path)
+        .to_string_lossy()
+        .to_string()
+}
+
+/// Absolute path for a stored `path`: relative ones (`store_relative_paths`) are joined onto
+/// the current root, so moving the root only needs a config change.
+fn resolve_stored_path(stored: &str, root: &Path) -> String {
+    if Path::new(stored).is_relative() {
+        root.join(stored).to_string_lossy().to_string()
+    } else {
+        stored.to_string()
+    }
+}
+
+fn metadata_file_path(index_dir: &Path) -> PathBuf {
+    index_dir.join(METADATA_FILE)
+}
+
+fn load_file_metadata(index_dir: &Path) -> Result<HashMap<String, FileMetadata>> {
+    let path = metadata_file_path(index_dir);
+
+    if !path.exists() {
+        return Ok(HashMap::new());
+    }
+
+    let data = fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read metadata file at {}", path.display()))?;
+
+    let entries: Vec<FileMetadata> = serde_json::from_str(&data)
+        .with_context(|| format!("Failed to parse metadata file at {}", path.display()))?;
+
+    Ok(entries
+        .into_iter()
+        .map(|entry| (entry.path.clone(), entry))
+        .collect())
+}
+
+fn save_file_metadata(index_dir: &Path, metadata: &HashMap<String, FileMetadata>) -> Result<()> {
+    let path = metadata_file_path(index_dir);
+    let entries: Vec<&FileMetadata> = metadata.values().collect();
+    let serialized =
+        serde_json::to_string_pretty(&entries).context("Failed to serialize file metadata")?;
+    fs::write(&path, serialized)
+        .with_context(|| format!("Failed to write metadata file at {}", path.display()))?;
+    Ok(())
+}
+
+/// Adds previous entries for files this run has not reached yet; they are still in the index.
+fn keep_unreached_metadata(
+    metadata: &mut HashMap<String, FileMetadata>,
+    previous: &HashMap<String, FileMetadata>,
+) {
+    for (path, entry) in previous {
+        metadata
+            .entry(path.clone())
+            .or_insert_with(|| entry.clone());
+    }
+}
+
+/// An indexing run that committed a checkpoint (or was interrupted) but did not complete.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct UnfinishedRun {
+    generation: u64,
+    started: String,
+}
+
+fn load_unfinished_run(index_dir: &Path) -> Option<UnfinishedRun> {
+    let path = index_dir.join(UNFINISHED_RUN_FILE);
+    let data = fs::read_to_string(&path).ok()?;
+    serde_json::from_str(&data)
+        .map_err(|e| eprintln!("  [warn] Ignoring unreadable {}: {e}", path.display()))
+        .ok()
+}
+
+fn save_unfinished_run(index_dir: &Path, run: &UnfinishedRun) -> Result<()> {
+    let path = index_dir.join(UNFINISHED_RUN_FILE);
+    let serialized = serde_json::to_string(run).context("Failed to serialize unfinished run")?;
+    fs::write(&path, serialized).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+fn clear_unfinished_run(index_dir: &Path) -> Result<()> {
+    let path = index_dir.join(UNFINISHED_RUN_FILE);
+    match fs::remove_file(&path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => {
+            Err(e).with_context(|| format!("Failed to remove {}", path.display()))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Run state of an index that belongs to a project config's root rather than to the config file.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ProjectRunState {
+    last_indexed: Option<String>,
+    index_generation: u64,
+}
+
+/// Where the index of a project config's `root` lives, named after a hash of the root.
+fn project_index_dir(root: &Path) -> Result<PathBuf> {
+    let proj_dirs = get_project_dirs()?;
+    let hash = blake3::hash(root.as_os_str().as_encoded_bytes());
+    Ok(proj_dirs
+        .data_local_dir()
+        .join(PROJECT_INDEXES_DIR)
+        .join(&hash.to_hex()[..16]))
+}
+
+fn load_project_run_state(index_dir: &Path) -> ProjectRunState {
+    let path = index_dir.join(PROJECT_RUN_STATE_FILE);
+    let Ok(data) = fs::read_to_string(&path) else {
+        return ProjectRunState::default();
+    };
+    serde_json::from_str(&data)
+        .map_err(|e| eprintln!("  [warn] Ignoring unreadable {}: {e}", path.display()))
+        .unwrap_or_default()
+}
+
+fn save_project_run_state(index_dir: &Path, state: &ProjectRunState) -> Result<()> {
+    let path = index_dir.join(PROJECT_RUN_STATE_FILE);
+    let serialized =
+        serde_json::to_string(state).context("Failed to serialize project run state")?;
+    write_atomically(&path, serialized.as_bytes())
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+fn build_schema(analysis: &ContentsAnalysis) -> Schema {
+    let mut schema_builder: SchemaBuilder = Schema::builder();
+
+    // Path: stored so we can print it in results, also tokenized to search by path pieces.
+    schema_builder.add_text_field("path", TEXT | STORED);
+
+    // Exact path: used for document replacement / deletion without tokenization.
+    schema_builder.add_text_field("path_exact", STRING | STORED);
+
+    // Contents: main text content we will index for full-text search, minus any stop words.
+    let mut contents_options = TEXT | STORED;
+    if let Some(indexing) = contents_options.get_indexing_options() {
+        let indexing = indexing
+            .clone()
+            .set_tokenizer(&contents_tokenizer_name(analysis));
+        contents_options = contents_options.set_indexing_options(indexing);
+    }
+    schema_builder.add_text_field("contents", contents_options);
+
+    // Code: contents of files routed by `[field_routing]`, tokenized without stop words.
+    if let Some(tokenizer) = code_tokenizer_name(analysis) {
+        let mut code_options = TEXT | STORED;
+        if let Some(indexing) = code_options.get_indexing_options() {
+            let indexing = indexing.clone().set_tokenizer(&tokenizer);
+            code_options = code_options.set_indexing_options(indexing);
+        }
+        schema_builder.add_text_field("code", code_options);
+    }
+
+    // Comments: comment lines pulled out of source files, for `search --comments`. Stored so
+    // comment-only matches still get a highlighted snippet.
+    let mut comments_options = TEXT | STORED;
+    if let Some(indexing) = comments_options.get_indexing_options() {
+        let indexing = indexing
+            .clone()
+            .set_tokenizer(&contents_tokenizer_name(analysis));
+        comments_options = comments_options.set_indexing_options(indexing);
+    }
+    schema_builder.add_text_field("comments", comments_options);
+
+    // Owner and permission bits (Unix only; absent elsewhere) for `search --owner/--mode`.
+    schema_builder.add_u64_field("uid", INDEXED | FAST | STORED);
+    schema_builder.add_u64_field("gid", INDEXED | FAST | STORED);
+    schema_builder.add_u64_field("mode", INDEXED | FAST | STORED);
+
+    // Resolved target for files reached through a symlink (only with `index --follow-symlinks`).
+    schema_builder.add_text_field("symlink_target", TEXT | STORED);
+
+    // Size and modification time for `size:` and `modified:` range queries.
+    schema_builder.add_u64_field("size", INDEXED | FAST | STORED);
+    schema_builder.add_date_field("modified", INDEXED | FAST | STORED);
+
+    // Line count of the file's text for `search --min-lines/--max-lines` and `--sort lines`.
+    schema_builder.add_u64_field("lines", INDEXED | FAST | STORED);
+
+    // Indexing run that last wrote the document, for `search --as-of-generation`.
+    schema_builder.add_u64_field("generation", INDEXED | FAST | STORED);
+
+    // Detected language of the text (ISO 639-3 or `unknown`), for `search --language`.
+    schema_builder.add_text_field("language", STRING | FAST | STORED);
+
+    schema_builder.build()
+}
+
+// ---- File helpers ----
+
+fn is_text_like(path: &Path) -> bool {
+    match path.extension().and_then(|s| s.to_str()) {
+        Some(ext) => {
+            let ext_lower = ext.to_ascii_lowercase();
+            TEXT_LIKE_EXTENSIONS.contains(&ext_lower.as_str())
+        }
+        None => false,
+    }
+}
+
+fn is_ocr_image(path: &Path) -> bool {
+    match path.extension().and_then(|s| s.to_str()) {
+        Some(ext) => {
+            let ext_lower = ext.to_ascii_lowercase();
+            OCR_IMAGE_EXTENSIONS.contains(&ext_lower.as_str())
+        }
+        None => false,
+    }
+}
+
+fn is_probably_binary(path: &Path) -> Result<bool> {
+    let mut file = fs::File::open(path)
+        .with_context(|| format!("Failed to open file {} for sniffing", path.display()))?;
+    let mut buf = [0u8; BINARY_SNIFF_BYTES];
+    let bytes_read = file
+        .read(&mut buf)
+        .with_context(|| format!("Failed to read file {}", path.display()))?;
+    let sample = &buf[..bytes_read];
+
+    if sample.contains(&0) {
+        return Ok(true);
+    }
+
+    if std::str::from_utf8(sample).is_err() {
+        return Ok(true);
+    }
+
+    Ok(false)
+}
+
+fn file_modified_timestamp(metadata: &fs::Metadata) -> Result<i64> {
+    let modified_time = metadata
+        .modified()
+        .context("Failed to read file modified timestamp")?;
+    let duration: Duration = modified_time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_else(|_| Duration::from_secs(0));
+    Ok(duration.as_secs() as i64)
+}
+
+#[derive(Debug)]
+struct FileReadResult {
+    contents: String,
+    hash: String,
+    /// Lines in the text as read (before CSV expansion or sidecar text is added)
+    lines: u64,
+}
+
+impl FileReadResult {
+    /// Empty contents for a file indexed by its path alone.
+    fn path_only() -> Self {
+        Self {
+            contents: String::new(),
+            hash: String::new(),
+            lines: 0,
+        }
+    }
+}
+
+/// Comment markers of a source language, for the `comments` field.
+#[derive(Debug, Clone, Copy)]
+struct CommentSyntax {
+    /// Markers that comment out the rest of the line
+    line: &'static [&'static str],
+    /// Start and end markers of block comments (and docstrings)
+    block: &'static [(&'static str, &'static str)],
+}
+
+const C_LIKE_COMMENTS: CommentSyntax = CommentSyntax {
+    line: &["//"],
+    block: &[("/*", "*/")],
+};
+const HASH_COMMENTS: CommentSyntax = CommentSyntax {
+    line: &["#"],
+    block: &[],
+};
+
+/// Comment syntax for a source file by extension, or `None` for prose and data files.
+fn comment_syntax(path: &Path) -> Option<CommentSyntax> {
+    let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+    let syntax = match ext.as_str() {
+        "rs" | "c" | "cpp" | "h" | "hpp" | "cs" | "java" | "go" | "js" | "ts" | "tsx" | "jsx" => {
+            C_LIKE_COMMENTS
+        }
+        "php" => CommentSyntax {
+            line: &["//", "#"],
+            block: &[("/*", "*/")],
+        },
+        "css" => CommentSyntax {
+            line: &[],
+            block: &[("/*", "*/")],
+        },
+        "py" => CommentSyntax {
+            line: &["#"],
+            block: &[("\"\"\"", "\"\"\""), ("'''", "'''")],
+        },
+        "rb" | "sh" | "bash" | "ps1" | "toml" | "yaml" | "yml" => HASH_COMMENTS,
+        "ini" | "cfg" => CommentSyntax {
+            line: &[";", "#"],
+            block: &[],
+        },
+        "html" | "htm" => CommentSyntax {
+            line: &[],
+            block: &[("<!--", "-->")],
+        },
+        "tex" => CommentSyntax {
+            line: &["%"],
+            block: &[],
+        },
+        _ => return None,
+    };
+    Some(syntax)
+}
+
+/// Text of the comments in `text`, one line per comment line. Markers are matched anywhere,
+/// without knowing about string literals, so `"http://..."` in code counts as a comment.
+fn extract_comments(text: &str, syntax: CommentSyntax) -> String {
+    fn push(comments: &mut String, piece: &str) {
+        let piece = piece.trim_start_matches(['/', '*', '!', '#']).trim();
+        if !piece.is_empty() {
+            comments.push_str(piece);
+            comments.push('\n');
+        }
+    }
+
+    let mut comments = String::new();
+    let mut block_end: Option<&str> = None;
+    for line in text.lines() {
+        let mut rest = line;
+        loop {
+            if let Some(end) = block_end {
+                match rest.find(end) {
+                    Some(at) => {
+                        push(&mut comments, &rest[..at]);
+                        rest = &rest[at + end.len()..];
+                        block_end = None;
+                        continue;
+                    }
+                    None => {
+                        push(&mut comments, rest);
+                        break;
+                    }
+                }
+            }
+            let line_start = syntax
+                .line
+                .iter()
+                .filter_map(|marker| rest.find(marker).map(|at| (at, marker.len(), None)));
+            let block_start = syntax.block.iter().filter_map(|(start, end)| {
+                rest.find(start).map(|at| (at, start.len(), Some(*end)))
+            });
+            match line_start.chain(block_start).min_by_key(|(at, _, _)| *at) {
+                Some((at, len, None)) => {
+                    push(&mut comments, &rest[at + len..]);
+                    break;
+                }
+                Some((at, len, Some(end))) => {
+                    rest = &rest[at + len..];
+                    block_end = Some(end);
+                }
+                None => break,
+            }
+        }
+    }
+    comments
+}
+
+/// ISO 639-3 code of the text's language, or `unknown` when it is too short or ambiguous to tell.
+fn detect_language(text: &str) -> &'static str {
+    let sample = match text.char_indices().nth(LANGUAGE_SAMPLE_CHARS) {
+        Some((end, _)) => &text[..end],
+        None => text,
+    };
+    whatlang::detect(sample)
+        .filter(|info| info.is_reliable())
+        .map_or(UNKNOWN_LANGUAGE, |info| info.lang().code())
+}
+
+fn read_file_streaming(path: &Path, size_hint: u64, max_bytes: u64) -> Result<FileReadResult> {
+    let file =
+        fs::File::open(path).with_context(|| format!("Failed to open file {}", path.display()))?;
+    let mut reader = BufReader::new(file);
+    let mut contents = String::new();
+    let mut line = String::new();
+    let mut total_bytes: u64 = 0;
+    let mut lines: u64 = 0;
+    let mut hasher = Hasher::new();
+
+    while reader
+        .read_line(&mut line)
+        .with_context(|| format!("Failed to read from file {}", path.display()))?
+        > 0
+    {
+        total_bytes += line.len() as u64;
+        lines += 1;
+
+        if total_bytes > max_bytes || size_hint > max_bytes {
+            anyhow::bail!("File exceeded size limit while reading (limit {max_bytes} bytes)");
+        }
+
+        hasher.update(line.as_bytes());
+        contents.push_str(&line);
+        line.clear();
+    }
+
+    let hash = hasher.finalize().to_hex().to_string();
+
+    Ok(FileReadResult {
+        contents,
+        hash,
+        lines,
+    })
+}
+
+/// Recognizes text in an image by running the `tesseract` command-line tool.
+#[cfg(feature = "ocr")]
+fn ocr_image(path: &Path) -> Result<FileReadResult> {
+    let output = process::Command::new("tesseract")
+        .arg(path)
+        .arg("stdout")
+        .stdin(process::Stdio::null())
+        .output()
+        .context("Failed to run `tesseract`; is it installed and on PATH?")?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "tesseract exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    let bytes = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let hash = blake3::hash(&bytes).to_hex().to_string();
+
+    let contents = String::from_utf8_lossy(&output.stdout).into_owned();
+    Ok(FileReadResult {
+        lines: contents.lines().count() as u64,
+        contents,
+        hash,
+    })
+}
+
+#[cfg(not(feature = "ocr"))]
+fn ocr_image(_path: &Path) -> Result<FileReadResult> {
+    anyhow::bail!("vaultsearch was built without the `ocr` feature")
+}
+
+// ---- Extractors ----
+
+/// Turns a file on disk into the text stored in `contents`.
+trait Extractor: Send + Sync {
+    fn extract(&self, path: &Path, size_hint: u64, max_bytes: u64) -> Result<FileReadResult>;
+}
+
+/// Indexes the file as-is; used for every extension without a registered extractor.
+struct PlainTextExtractor;
+
+impl Extractor for PlainTextExtractor {
+    fn extract(&self, path: &Path, size_hint: u64, max_bytes: u64) -> Result<FileReadResult> {
+        read_file_streaming(path, size_hint, max_bytes)
+    }
+}
+
+/// Recognizes text in images (`index --ocr`).
+struct OcrExtractor;
+
+impl Extractor for OcrExtractor {
+    fn extract(&self, path: &Path, _size_hint: u64, _max_bytes: u64) -> Result<FileReadResult> {
+        ocr_image(path)
+    }
+}
+
+/// Expands CSV rows into `header: value` pairs so column values can be searched,
+/// falling back to the raw text when the file does not parse.
+#[cfg(feature = "csv-index")]
+struct CsvExtractor;
+
+#[cfg(feature = "csv-index")]
+impl Extractor for CsvExtractor {
+    fn extract(&self, path: &Path, size_hint: u64, max_bytes: u64) -> Result<FileReadResult> {
+        let mut raw = read_file_streaming(path, size_hint, max_bytes)?;
+        match expand_csv_rows(&raw.contents, max_bytes) {
+            Ok(expanded) => raw.contents = expanded,
+            Err(e) => eprintln!(
+                "  [warn] Indexing {} as raw text; CSV parsing failed: {e}",
+                path.display()
+            ),
+        }
+        Ok(raw)
+    }
+}
+
+/// Appends `key.path: value` lines for every scalar in a JSON or YAML file to its raw
+/// text, so a dotted key path can be searched as a phrase. Files that do not parse are
+/// indexed as raw text only.
+#[cfg(feature = "structured-index")]
+struct StructuredExtractor {
+    format: StructuredFormat,
+}
+
+#[cfg(feature = "structured-index")]
+#[derive(Clone, Copy)]
+enum StructuredFormat {
+    Json,
+    Yaml,
+}
+
+#[cfg(feature = "structured-index")]
+impl Extractor for StructuredExtractor {
+    fn extract(&self, path: &Path, size_hint: u64, max_bytes: u64) -> Result<FileReadResult> {
+        let mut raw = read_file_streaming(path, size_hint, max_bytes)?;
+        match flatten_structured(&raw.contents, self.format, max_bytes) {
+            Ok(flattened) if !flattened.is_empty() => {
+                raw.contents.push('\n');
+                raw.contents.push_str(&flattened);
+            }
+            Ok(_) => {}
+            Err(e) => eprintln!(
+                "  [warn] Indexing {} as raw text only; parsing failed: {e}",
+                path.display()
+            ),
+        }
+        Ok(raw)
+    }
+}
+
+/// Maps lowercase file extensions to the extractor that reads them.
+struct ExtractorRegistry {
+    by_extension: HashMap<String, Box<dyn Extractor>>,
+}
+
+impl ExtractorRegistry {
+    /// The extractors built into this binary; OCR is registered only for `index --ocr` runs.
+    fn builtin(ocr: bool) -> Self {
+        let mut registry = Self {
+            by_extension: HashMap::new(),
+        };
+        #[cfg(feature = "csv-index")]
+        registry.register("csv", CsvExtractor);
+        #[cfg(feature = "structured-index")]
+        {
+            let json = StructuredFormat::Json;
+            registry.register("json", StructuredExtractor { format: json });
+            for ext in ["yaml", "yml"] {
+                let yaml = StructuredFormat::Yaml;
+                registry.register(ext, StructuredExtractor { format: yaml });
+            }
+        }
+        if ocr {
+            for ext in OCR_IMAGE_EXTENSIONS {
+                registry.register(ext, OcrExtractor);
+            }
+        }
+        registry
+    }
+
+    fn register(&mut self, extension: &str, extractor: impl Extractor + 'static) {
+        self.by_extension
+            .insert(extension.to_ascii_lowercase(), Box::new(extractor));
+    }
+
+    fn for_path(&self, path: &Path) -> &dyn Extractor {
+        path.extension()
+            .and_then(|s| s.to_str())
+            .and_then(|ext| self.by_extension.get(&ext.to_ascii_lowercase()))
+            .map_or(&PlainTextExtractor, |extractor| extractor.as_ref())
+    }
+}
+
+/// Rewrites each CSV row as `header: value` pairs, one row per line.
+#[cfg(feature = "csv-index")]
+fn expand_csv_rows(raw: &str, max_bytes: u64) -> Result<String> {
+    let mut reader = csv::Reader::from_reader(raw.as_bytes());
+    let headers = reader
+        .headers()
+        .context("Failed to read CSV header row")?
+        .clone();
+
+    let mut expanded = String::with_capacity(raw.len() * 2);
+    for record in reader.records() {
+        let record = record.context("Failed to parse CSV row")?;
+        let pairs: Vec<String> = headers
+            .iter()
+            .zip(record.iter())
+            .filter(|(_, value)| !value.is_empty())
+            .map(|(header, value)| format!("{header}: {value}"))
+            .collect();
+
+        expanded.push_str(&pairs.join("; "));
+        expanded.push('\n');
+
+        if expanded.len() as u64 > max_bytes {
+            anyhow::bail!("Expanded CSV exceeded size limit (limit {max_bytes} bytes)");
+        }
+    }
+
+    Ok(expanded)
+}
+
+/// Flattens every document in `raw` into `key.path: value` lines. Array elements use
+/// their index as a path segment (`servers.0.host`); empty containers and nulls are skipped.
+#[cfg(feature = "structured-index")]
+fn flatten_structured(raw: &str, format: StructuredFormat, max_bytes: u64) -> Result<String> {
+    let documents: Vec<serde_json::Value> = match format {
+        StructuredFormat::Json => vec![serde_json::from_str(raw).context("Invalid JSON")?],
+        StructuredFormat::Yaml => serde_yaml::Deserializer::from_str(raw)
+            .map(|document| serde_json::Value::deserialize(document).context("Invalid YAML"))
+            .collect::<Result<_>>()?,
+    };
+
+    let mut flattened = String::new();
+    for document in &documents {
+        flatten_value(document, &mut String::new(), &mut flattened);
+        if flattened.len() as u64 > max_bytes {
+            anyhow::bail!("Flattened keys exceeded size limit (limit {max_bytes} bytes)");
+        }
+    }
+    Ok(flattened)
+}
+
+#[cfg(feature = "structured-index")]
+fn flatten_value(value: &serde_json::Value, key_path: &mut String, out: &mut String) {
+    use serde_json::Value;
+
+    let mut descend = |segment: &str, child: &Value, key_path: &mut String| {
+        let parent_len = key_path.len();
+        if !key_path.is_empty() {
+            key_path.push('.');
+        }
+        key_path.push_str(segment);
+        flatten_value(child, key_path, out);
+        key_path.truncate(parent_len);
+    };
+    match value {
+        Value::Null => {}
+        Value::Object(map) => {
+            for (key, child) in map {
+                descend(key, child, key_path);
+            }
+        }
+        Value::Array(items) => {
+            for (index, child) in items.iter().enumerate() {
+                descend(&index.to_string(), child, key_path);
+            }
+        }
+        Value::String(text) => push_key_value(out, key_path, text),
+        scalar => push_key_value(out, key_path, &scalar.to_string()),
+    }
+}
+
+#[cfg(feature = "structured-index")]
+fn push_key_value(out: &mut String, key_path: &str, value: &str) {
+    if key_path.is_empty() {
+        return;
+    }
+    out.push_str(key_path);
+    out.push_str(": ");
+    out.push_str(value);
+    out.push('\n');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_is_text_like_matches_known_extensions() {
+        assert!(is_text_like(Path::new("notes.txt")));
+        assert!(is_text_like(Path::new("script.rs")));
+        assert!(!is_text_like(Path::new("archive.zip")));
+        assert!(!is_text_like(Path::new("no_extension")));
+    }
+
+    #[test]
+    fn test_is_ocr_image_matches_image_extensions() {
+        assert!(is_ocr_image(Path::new("receipt.PNG")));
+        assert!(is_ocr_image(Path::new("scan.jpeg")));
+        assert!(!is_ocr_image(Path::new("notes.txt")));
+        assert!(!is_ocr_image(Path::new("no_extension")));
+    }
+
+    #[test]
+    fn test_is_probably_binary_detects_null_bytes_and_utf8_errors() {
+        let mut text_file = NamedTempFile::new().expect("create temp file");
+        writeln!(text_file, "plain text content").expect("write text");
+
+        let mut binary_file = NamedTempFile::new().expect("create temp file");
+        binary_file
+            .write_all(b"binary\0content")
+            .expect("write binary");
+
+        assert!(!is_probably_binary(text_file.path()).unwrap());
+        assert!(is_probably_binary(binary_file.path()).unwrap());
+    }
+
+    #[test]
+    fn test_resolve_query_reads_query_file_and_rejects_empty() {
+        let mut file = NamedTempFile::new().expect("create temp file");
+        writeln!(file, "\"quoted phrase\" AND wild*").expect("write query");
+
+        let query = resolve_query(None, Some(file.path())).expect("read query file");
+        assert_eq!(query, "\"quoted phrase\" AND wild*");
+
+        assert_eq!(resolve_query(Some(" rust "), None).unwrap(), "rust");
+        assert!(resolve_query(Some("   "), None).is_err());
+    }
+
+    #[test]
+    fn test_describe_schema_lists_builtin_fields() {
+        let fields = describe_schema(&build_schema(&ContentsAnalysis::default()));
+        let names: Vec<&str> = fields.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "path",
+                "path_exact",
+                "contents",
+                "comments",
+                "uid",
+                "gid",
+                "mode",
+                "symlink_target",
+                "size",
+                "modified",
+                "lines",
+                "generation",
+                "language"
+            ]
+        );
+
+        let path_exact = &fields[1];
+        assert_eq!(path_exact.field_type, "str");
+        assert_eq!(path_exact.tokenizer.as_deref(), Some("raw"));
+        assert!(path_exact.stored && path_exact.indexed);
+    }
+
+    fn ranked(score: f32, path: &str) -> RankedDoc {
+        RankedDoc {
+            score,
+            path: path.to_string(),
+            lines: 0,
+            doc: TantivyDocument::default(),
+        }
+    }
+
+    #[test]
+    fn test_sort_hits_breaks_score_ties_by_path() {
+        let mut hits = vec![
+            ranked(1.0, "b.txt"),
+            ranked(2.0, "z.txt"),
+            ranked(1.0, "a.txt"),
+        ];
+        sort_hits(&mut hits, SortOrder::Score);
+        let paths: Vec<&str> = hits.iter().map(|h| h.path.as_str()).collect();
+        assert_eq!(paths, ["z.txt", "a.txt", "b.txt"]);
+
+        sort_hits(&mut hits, SortOrder::Path);
+        let paths: Vec<&str> = hits.iter().map(|h| h.path.as_str()).collect();
+        assert_eq!(paths, ["a.txt", "b.txt", "z.txt"]);
+
+        hits[0].lines = 10;
+        hits[2].lines = 10;
+        sort_hits(&mut hits, SortOrder::Lines);
+        let paths: Vec<&str> = hits.iter().map(|h| h.path.as_str()).collect();
+        assert_eq!(paths, ["z.txt", "a.txt", "b.txt"]);
+    }
+
+    #[test]
+    fn test_choose_config_path_prefers_flag_then_env_then_default() {
+        let default = PathBuf::from("/home/me/.config/vaultsearch/config.toml");
+        let flag = Path::new("work.toml");
+        let env = Some(std::ffi::OsString::from("/etc/vaultsearch.toml"));
+
+        assert_eq!(
+            choose_config_path(Some(flag), env.clone(), default.clone()),
+            (flag.to_path_buf(), ConfigSource::Flag)
+        );
+        assert_eq!(
+            choose_config_path(None, env, default.clone()),
+            (PathBuf::from("/etc/vaultsearch.toml"), ConfigSource::Env)
+        );
+        // An empty variable counts as unset, as with most tools.
+        assert_eq!(
+            choose_config_path(None, Some("".into()), default.clone()),
+            (default.clone(), ConfigSource::Default)
+        );
+        assert_eq!(
+            choose_config_path(None, None, default.clone()),
+            (default, ConfigSource::Default)
+        );
+    }
+
+    #[test]
+    fn test_expand_root_substitutes_variables_and_reports_unset_ones() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        std::env::set_var("VAULTSEARCH_TEST_ROOT", dir.path());
+
+        let expanded = expand_root("$VAULTSEARCH_TEST_ROOT").expect("expand root");
+        assert_eq!(
+            PathBuf::from(expanded),
+            fs::canonicalize(dir.path()).unwrap()
+        );
+        assert_eq!(expand_root("/plain/path").unwrap(), "/plain/path");
+
+        let err = expand_root("$VAULTSEARCH_TEST_UNSET_VAR/docs").unwrap_err();
+        assert!(format!("{err}").contains("VAULTSEARCH_TEST_UNSET_VAR"));
+    }
+
+    #[test]
+    fn test_snippet_fields_puts_contents_first_and_skips_raw_fields() {
+        let schema = build_schema(&ContentsAnalysis::default());
+        let contents = schema.get_field("contents").unwrap();
+        let path = schema.get_field("path").unwrap();
+        let comments = schema.get_field("comments").unwrap();
+        let symlink_target = schema.get_field("symlink_target").unwrap();
+
+        assert_eq!(
+            snippet_fields(&schema, contents),
+            vec![contents, path, comments, symlink_target]
+        );
+    }
+
+    #[test]
+    fn test_translate_field_ranges_converts_units_and_whole_days() {
+        let translate = |query: &str| translate_field_ranges(query).expect("translate");
+
+        assert_eq!(translate("report size:>10KB"), "report size:>10240");
+        assert_eq!(translate("size:[1.5mb TO *]"), "size:[1572864 TO *]");
+        assert_eq!(translate("size:500"), "size:[500 TO 500]");
+        assert_eq!(
+            translate("modified:[2023-01-01 TO 2023-12-31]"),
+            "modified:[2023-01-01T00:00:00Z TO 2024-01-01T00:00:00Z}"
+        );
+        assert_eq!(
+            translate("(modified:<=2023-12-31)"),
+            "(modified:<2024-01-01T00:00:00Z)"
+        );
+        assert_eq!(
+            translate("-modified:2024-02-29"),
+            "-modified:[2024-02-29T00:00:00Z TO 2024-03-01T00:00:00Z}"
+        );
+        assert_eq!(
+            translate("modified:>2023-06-01T12:00:00+02:00"),
+            "modified:>2023-06-01T10:00:00Z"
+        );
+        assert_eq!(
+            translate("filesize:10KB path:size:x"),
+            "filesize:10KB path:size:x"
+        );
+        assert_eq!(translate("\"the size: big\""), "\"the size: big\"");
+        assert_eq!(
+            translate("\"modified: soon\" size:1KB"),
+            "\"modified: soon\" size:[1024 TO 1024]"
+        );
+
+        assert!(translate_field_ranges("size:>10XB").is_err());
+        assert!(translate_field_ranges("modified:>yesterday").is_err());
+    }
+
+    #[test]
+    fn test_path_is_excluded_matches_absolute_and_root_relative_paths() {
+        let patterns = ["**/archive/**".to_string(), "drafts/*.md".to_string()];
+        let globs = build_exclude_set(&patterns).unwrap().unwrap();
+        let root = Path::new("/vault");
+
+        assert!(path_is_excluded(&globs, "/vault/old/archive/a.txt", root));
+        assert!(path_is_excluded(&globs, "/vault/drafts/plan.md", root));
+        assert!(!path_is_excluded(
+            &globs,
+            "/vault/notes/drafts/plan.md",
+            root
+        ));
+        assert!(!path_is_excluded(&globs, "/vault/notes/plan.md", root));
+
+        assert!(build_exclude_set(&[]).unwrap().is_none());
+        assert!(build_exclude_set(&["a[".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_mount_for_path_picks_most_specific_mount() {
+        let mounts = "\
+/dev/sda2 / ext4 rw,relatime 0 0
+server:/export /mnt/shared nfs4 rw,vers=4.2 0 0
+/dev/sdb1 /media/usb\\040stick vfat rw 0 0
+";
+        let entry = mount_for_path(mounts, Path::new("/mnt/shared/vault/index")).unwrap();
+        assert_eq!(entry.fs_type, "nfs4");
+        assert_eq!(entry.mount_point, "/mnt/shared");
+
+        let entry = mount_for_path(mounts, Path::new("/media/usb stick/index")).unwrap();
+        assert_eq!(entry.device, "/dev/sdb1");
+
+        let entry = mount_for_path(mounts, Path::new("/mnt/sharedother")).unwrap();
+        assert_eq!(entry.mount_point, "/");
+    }
+
+    #[test]
+    fn test_parse_duration_secs_accepts_units_and_rejects_bare_numbers() {
+        assert_eq!(parse_duration_secs("90m"), Ok(90 * 60));
+        assert_eq!(parse_duration_secs("24h"), Ok(24 * 60 * 60));
+        assert_eq!(parse_duration_secs("7D"), Ok(7 * 24 * 60 * 60));
+        assert_eq!(parse_duration_secs("2w"), Ok(14 * 24 * 60 * 60));
+        assert!(parse_duration_secs("7").is_err());
+        assert!(parse_duration_secs("d").is_err());
+        assert!(parse_duration_secs("1y").is_err());
+        assert!(parse_duration_secs("99999999999999999999w").is_err());
+    }
+
+    #[test]
+    fn test_expand_synonyms_rewrites_only_mapped_bare_words() {
+        let synonyms = BTreeMap::from([
+            (
+                "Car".to_string(),
+                vec!["automobile".to_string(), "motor vehicle".to_string()],
+            ),
+            ("tax".to_string(), Vec::new()),
+        ]);
+
+        assert_eq!(
+            expand_synonyms("+car repair", &synonyms),
+            "+(car OR automobile OR \"motor vehicle\") repair"
+        );
+        assert_eq!(
+            expand_synonyms("CAR AND \"car wash\" path:car", &synonyms),
+            "(CAR OR automobile OR \"motor vehicle\") AND \"car wash\" path:car"
+        );
+        assert_eq!(expand_synonyms("automobile", &synonyms), "automobile");
+        assert_eq!(expand_synonyms("-tax", &synonyms), "-tax");
+    }
+
+    #[test]
+    fn test_highlight_snippet_wraps_matches_after_decoding_entities() {
+        let html = "a &lt;b&gt; tag &amp; <b>match</b> and <b>more</b>";
+        let markers = HighlightMarkers {
+            open: "[[",
+            close: "]]",
+        };
+        assert_eq!(
+            highlight_snippet(html, Some(markers)),
+            "a <b> tag & [[match]] and [[more]]"
+        );
+        assert_eq!(plain_snippet(html), "a <b> tag & match and more");
+        assert_eq!(
+            highlight_snippet("<b>x</b>", Some(ANSI_HIGHLIGHT)),
+            "\x1b[1mx\x1b[0m"
+        );
+    }
+
+    #[test]
+    fn test_drop_short_terms_keeps_phrases_fields_and_operators() {
+        let (kept, dropped) = drop_short_terms("io AND +fs \"a b\" x:y parser -ab", 3);
+        assert_eq!(kept, "AND \"a b\" x:y parser");
+        assert_eq!(dropped, ["io", "+fs", "-ab"]);
+
+        let (kept, dropped) = drop_short_terms("io fs", 1);
+        assert_eq!(kept, "io fs");
+        assert!(dropped.is_empty());
+    }
+
+    #[test]
+    fn test_highlight_terms_mark_tokenized_words_in_a_line() {
+        let terms = query_highlight_terms("+Ledger -draft \"year end\" path:ledger size:>1KB");
+        let expected: HashSet<String> = ["ledger", "draft", "year", "end"]
+            .into_iter()
+            .map(str::to_string)
+            .collect();
+        assert_eq!(terms, expected);
+
+        let mut analyzer = TokenizerManager::default().get("default").unwrap();
+        let line = "The LEDGER, at year-end.";
+        let ranges = matching_term_ranges(&mut analyzer, line, &terms);
+        let words: Vec<&str> = ranges.iter().map(|r| &line[r.clone()]).collect();
+        assert_eq!(words, ["LEDGER", "year", "end"]);
+    }
+
+    #[test]
+    fn test_tracked_file_included_applies_depth_and_excluded_dirs() {
+        let excluded: HashSet<&str> = ["node_modules"].into_iter().collect();
+
+        assert!(tracked_file_included(
+            Path::new("README.md"),
+            Some(0),
+            &excluded
+        ));
+        assert!(!tracked_file_included(
+            Path::new("docs/a.md"),
+            Some(0),
+            &excluded
+        ));
+        assert!(tracked_file_included(
+            Path::new("docs/a.md"),
+            Some(1),
+            &excluded
+        ));
+        assert!(tracked_file_included(
+            Path::new("a/b/c/d.md"),
+            None,
+            &excluded
+        ));
+        assert!(!tracked_file_included(
+            Path::new("web/node_modules/x/index.js"),
+            None,
+            &excluded
+        ));
+        assert!(tracked_file_included(
+            Path::new("node_modules"),
+            None,
+            &excluded
+        ));
+    }
+
+    #[test]
+    fn test_resolve_query_fields_prefers_request_over_config_and_skips_unknown() {
+        let schema = build_schema(&ContentsAnalysis::default());
+        let field = |name: &str| schema.get_field(name).unwrap();
+        let cfg: AppConfig = toml::from_str(
+            r#"
+            root = "/vault"
+            index_dir = "/index"
+            search_fields = ["contents", "title", "size"]
+
+            [field_boosts]
+            path = 3.0
+            title = 2.0
+            "#,
+        )
+        .unwrap();
+        let mut request: SearchRequest = serde_json::from_str(r#"{"query": "x"}"#).unwrap();
+
+        let (fields, boosts) = resolve_query_fields(&schema, &cfg, &request);
+        assert_eq!(fields, [field("contents")]);
+        assert_eq!(boosts, [(field("path"), 3.0)]);
+
+        request.search_fields = vec!["path".to_string(), "contents".to_string()];
+        request.field_boosts = BTreeMap::from([("path".to_string(), 1.5)]);
+        let (fields, boosts) = resolve_query_fields(&schema, &cfg, &request);
+        assert_eq!(fields, [field("path"), field("contents")]);
+        assert_eq!(boosts, [(field("path"), 1.5)]);
+
+        assert_eq!(parse_field_boost("path=3"), Ok(("path".to_string(), 3.0)));
+        assert!(parse_field_boost("path").is_err());
+        assert!(parse_field_boost("path=-1").is_err());
+        assert!(parse_field_boost("path=NaN").is_err());
+    }
+
+    #[test]
+    fn test_apply_relevance_profile_fills_only_unset_settings() {
+        let cfg: AppConfig = toml::from_str(
+            r#"
+            root = "/vault"
+            index_dir = "/index"
+            default_relevance_profile = "notes"
+
+            [relevance_profiles.notes]
+            field_boosts = { contents = 2.0, path = 0.5 }
+            recency_boost = true
+            recency_half_life_days = 14.0
+
+            [relevance_profiles.code]
+            search_fields = ["path", "code"]
+            "#,
+        )
+        .unwrap();
+        let mut request: SearchRequest = serde_json::from_str(r#"{"query": "x"}"#).unwrap();
+        request.field_boosts = BTreeMap::from([("path".to_string(), 3.0)]);
+
+        let notes = apply_relevance_profile(&cfg, &request).unwrap();
+        assert!(notes.search_fields.is_empty());
+        assert_eq!(
+            notes.field_boosts,
+            BTreeMap::from([("contents".to_string(), 2.0), ("path".to_string(), 3.0)])
+        );
+        assert!(notes.recency_boost);
+        assert_eq!(notes.recency_half_life, Some(14.0));
+
+        request.relevance = Some("code".to_string());
+        let code = apply_relevance_profile(&cfg, &request).unwrap();
+        assert_eq!(code.search_fields, ["path", "code"]);
+        assert!(!code.recency_boost);
+
+        request.relevance = Some("prose".to_string());
+        let err = apply_relevance_profile(&cfg, &request).unwrap_err();
+        assert!(format!("{err}").contains("configured profiles: code, notes"));
+    }
+
+    #[test]
+    fn test_describe_query_walks_boolean_structure() {
+        let schema = build_schema(&ContentsAnalysis::default());
+        let index = Index::create_in_ram(schema.clone());
+        let fields = vec![
+            schema.get_field("path").unwrap(),
+            schema.get_field("contents").unwrap(),
+        ];
+        let parser = QueryParser::for_index(&index, fields);
+        let query = parser
+            .parse_query("+tax -\"draft copy\" size:>1024")
+            .expect("parse");
+
+        let mut lines = Vec::new();
+        describe_query(query.as_ref(), &schema, 0, &mut lines);
+        assert_eq!(
+            lines,
+            [
+                "boolean",
+                "  must: boolean",
+                "    should: term path:\"tax\"",
+                "    should: term contents:\"tax\"",
+                "  must not: boolean",
+                "    should: phrase path:\"draft copy\"",
+                "    should: phrase contents:\"draft copy\"",
+                "  should: range size: > 1024",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_batch_queries_skips_comments_blanks_and_repeats() {
+        let text = "# daily report\ninvoice 2024\n\n  tax report  \ninvoice 2024\n";
+        assert_eq!(
+            parse_batch_queries(text),
+            vec![
+                (2, "invoice 2024".to_string()),
+                (4, "tax report".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_normalize_scores_divides_by_top_score() {
+        let hit = |rank: usize, score: f32| SearchHit {
+            rank,
+            score,
+            path: format!("/root/{rank}.txt"),
+            relative_path: format!("{rank}.txt"),
+            snippet_html: String::new(),
+            symlink_target: None,
+            fields: None,
+            size: None,
+            modified: None,
+            match_spans: None,
+            language: None,
+            index: None,
+            line: None,
+        };
+
+        // `--sort path` can put the best score anywhere in the list.
+        let mut results = vec![hit(1, 1.0), hit(2, 4.0), hit(3, 2.0)];
+        normalize_scores(&mut results);
+        let scores: Vec<f32> = results.iter().map(|r| r.score).collect();
+        assert_eq!(scores, vec![0.25, 1.0, 0.5]);
+
+        let mut unscored = vec![hit(1, 0.0)];
+        normalize_scores(&mut unscored);
+        assert_eq!(unscored[0].score, 0.0);
+    }
+
+    #[test]
+    fn test_truncate_middle_keeps_both_ends() {
+        assert_eq!(truncate_middle("notes.txt", 20), "notes.txt");
+        assert_eq!(
+            truncate_middle("projects/2024/report.txt", 11),
+            "proje…t.txt"
+        );
+        assert_eq!(truncate_middle("abc", 1), "…");
+        assert_eq!(truncate_middle("abc", 0), "");
+    }
+
+    #[test]
+    fn test_write_table_results_fits_paths_to_width() {
+        let results = [SearchHit {
+            rank: 3,
+            score: 1.5,
+            path: "/root/archive/2024/quarterly/report.txt".to_string(),
+            relative_path: "archive/2024/quarterly/report.txt".to_string(),
+            snippet_html: "<b>report</b>".to_string(),
+            symlink_target: None,
+            fields: None,
+            size: Some(2048),
+            modified: None,
+            match_spans: None,
+            language: None,
+            index: None,
+            line: None,
+        }];
+
+        let mut out = Vec::new();
+        write_table_results(&mut out, &results, true, Some(50)).expect("write table");
+        let table = String::from_utf8(out).unwrap();
+        assert!(
+            table.lines().all(|line| line.chars().count() <= 50),
+            "{table}"
+        );
+        assert!(table.contains("Modified"));
+        assert!(table.contains("2.0 KB"));
+        assert!(table.contains("archiv…ort.txt"), "{table}");
+        assert!(!table.contains("<b>"));
+
+        let mut out = Vec::new();
+        write_table_results(&mut out, &results, false, None).expect("write table");
+        let table = String::from_utf8(out).unwrap();
+        assert!(table.contains("archive/2024/quarterly/report.txt"));
+        assert!(!table.contains("Modified"));
+    }
+
+    #[test]
+    fn test_write_csv_results_quotes_snippets_and_strips_highlights() {
+        let results = [SearchHit {
+            rank: 1,
+            score: 1.5,
+            path: "/root/a.txt".to_string(),
+            relative_path: "a.txt".to_string(),
+            snippet_html: "<b>alpha</b>, beta\ngamma &amp; delta".to_string(),
+            symlink_target: None,
+            fields: None,
+            size: None,
+            modified: None,
+            match_spans: None,
+            language: None,
+            index: None,
+            line: None,
+        }];
+
+        let mut out = Vec::new();
+        write_csv_results(&mut out, &results, None).expect("write csv");
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "rank,score,path,relative_path,snippet\n\
+             1,1.500,/root/a.txt,a.txt,\"alpha, beta\ngamma & delta\"\n"
+        );
+
+        let mut empty = Vec::new();
+        write_csv_results(&mut empty, &[], None).expect("write empty csv");
+        assert_eq!(
+            String::from_utf8(empty).unwrap(),
+            "rank,score,path,relative_path,snippet\n"
+        );
+    }
+
+    #[test]
+    fn test_size_limits_fall_back_to_default() {
+        let limits = SizeLimits::default();
+
+        assert_eq!(
+            limits.limit_for(Path::new("notes.txt")),
+            MAX_FILE_SIZE_BYTES
+        );
+        assert_eq!(
+            limits.limit_for(Path::new("no_extension")),
+            MAX_FILE_SIZE_BYTES
+        );
+    }
+
+    #[test]
+    fn test_size_limits_prefer_extension_override() {
+        let limits: SizeLimits =
+            toml::from_str("default = 1000\n[per_extension]\nlog = 50000\njson = 10\n")
+                .expect("parse size limits");
+
+        assert_eq!(limits.limit_for(Path::new("server.LOG")), 50_000);
+        assert_eq!(limits.limit_for(Path::new("data.json")), 10);
+        assert_eq!(limits.limit_for(Path::new("notes.md")), 1_000);
     }
-}
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct FileMetadata {
-    path: String,
-    modified: i64,
-    size: u64,
-    hash: String,
-}
+    #[test]
+    fn test_stop_words_resolve_and_filter_contents_tokens() {
+        let inline = StopWords::List(vec![" System ".into(), "data".into(), "system".into()]);
+        let words = inline.resolve().expect("resolve list");
+        assert_eq!(words, ["data", "system"]);
 
-#[derive(Default)]
-struct IndexingStats {
-    indexed: usize,
-    updated: usize,
-    unchanged: usize,
-    removed: usize,
-}
+        let dir = tempfile::tempdir().expect("temp dir");
+        let file = dir.path().join("stop.txt");
+        fs::write(&file, "# domain noise\ndata\n\nSYSTEM\n").expect("write stop words");
+        let from_file = StopWords::File(file.to_string_lossy().to_string());
+        assert_eq!(from_file.resolve().expect("resolve file"), words);
 
-// ---- Config helpers ----
+        // Equal lists share a tokenizer; an empty list keeps Tantivy's default.
+        let analysis = ContentsAnalysis {
+            stop_words: words.clone(),
+            ..ContentsAnalysis::default()
+        };
+        let same_words = ContentsAnalysis {
+            stop_words: inline.resolve().unwrap(),
+            ..ContentsAnalysis::default()
+        };
+        assert_eq!(
+            contents_tokenizer_name(&analysis),
+            contents_tokenizer_name(&same_words)
+        );
+        assert_eq!(
+            contents_tokenizer_name(&ContentsAnalysis::default()),
+            "default"
+        );
+        assert_eq!(
+            text_field_tokenizer(&build_schema(&analysis), "contents"),
+            Some(contents_tokenizer_name(&analysis))
+        );
 
-fn get_project_dirs() -> Result<ProjectDirs> {
-    ProjectDirs::from("com", "vault", "vaultsearch")
-        .context("Could not determine platform-specific config directory")
-}
+        let mut analyzer = contents_analyzer(&analysis);
+        let mut stream = analyzer.token_stream("System data report");
+        let mut tokens = Vec::new();
+        while stream.advance() {
+            tokens.push(stream.token().text.clone());
+        }
+        assert_eq!(tokens, ["report"]);
+    }
 
-fn config_file_path(proj_dirs: &ProjectDirs) -> Result<PathBuf> {
-    let mut path = proj_dirs.config_dir().to_path_buf();
-    path.push("config.toml");
-    Ok(path)
-}
+    #[test]
+    fn test_field_routing_adds_a_code_field_for_normalized_extensions() {
+        let routing = FieldRouting {
+            code: vec![
+                ".RS".to_string(),
+                "py".to_string(),
+                "rs".to_string(),
+                " ".to_string(),
+            ],
+        };
+        let analysis = ContentsAnalysis {
+            code_extensions: routing.code_extensions(),
+            ..ContentsAnalysis::default()
+        };
+        assert_eq!(analysis.code_extensions, ["py", "rs"]);
+        assert!(analysis.routes_to_code(Path::new("/src/Main.RS")));
+        assert!(!analysis.routes_to_code(Path::new("/docs/readme.md")));
+        assert!(analysis.is_default());
 
-fn index_dir_path(proj_dirs: &ProjectDirs) -> Result<PathBuf> {
-    let mut path = proj_dirs.data_local_dir().to_path_buf();
-    path.push("index");
-    Ok(path)
-}
+        let schema = build_schema(&analysis);
+        assert_eq!(
+            text_field_tokenizer(&schema, "code"),
+            code_tokenizer_name(&analysis)
+        );
+        assert_eq!(
+            text_field_tokenizer(&schema, "contents").as_deref(),
+            Some("default")
+        );
+        assert!(build_schema(&ContentsAnalysis::default())
+            .get_field("code")
+            .is_err());
+    }
 
-fn load_config() -> Result<AppConfig> {
-    let proj_dirs = get_project_dirs()?;
-    let config_path = config_file_path(&proj_dirs)?;
+    #[test]
+    fn test_max_token_length_drops_longer_contents_tokens() {
+        let analysis = ContentsAnalysis {
+            max_token_length: 5,
+            ..ContentsAnalysis::default()
+        };
+        assert_eq!(contents_tokenizer_name(&analysis), "max_token_5");
 
-    let data = fs::read_to_string(&config_path).with_context(|| {
-        format!(
-            "Failed to read config file at {}. Did you run `vaultsearch init`?",
-            config_path.display()
-        )
-    })?;
+        let mut analyzer = contents_analyzer(&analysis);
+        let mut stream = analyzer.token_stream("short lengthy QUITE aGVsbG8gd29ybGQ=");
+        let mut tokens = Vec::new();
+        while stream.advance() {
+            tokens.push(stream.token().text.clone());
+        }
+        assert_eq!(tokens, ["short", "quite"]);
 
-    let cfg: AppConfig = toml::from_str(&data).with_context(|| "Failed to parse config TOML")?;
-    Ok(cfg)
-}
+        // The default limit matches Tantivy's `default` tokenizer, so existing indexes keep working.
+        let mut default_tokens = 0;
+        let mut analyzer = contents_analyzer(&ContentsAnalysis::default());
+        let text = format!("{} {}", "a".repeat(39), "b".repeat(40));
+        let mut stream = analyzer.token_stream(&text);
+        while stream.advance() {
+            default_tokens += 1;
+        }
+        assert_eq!(default_tokens, 1);
+    }
 
-fn save_config(cfg: &AppConfig) -> Result<()> {
-    let proj_dirs = get_project_dirs()?;
-    let config_path = config_file_path(&proj_dirs)?;
-    write_config(cfg, &config_path)
-}
+    #[test]
+    fn test_recency_boost_weight_halves_per_half_life_down_to_min_weight() {
+        let day = 86_400;
+        let boost = RecencyBoost::default();
+        assert_eq!(boost.weight(0), 1.0);
+        assert_eq!(boost.weight(-day), 1.0);
+        assert!((boost.weight(30 * day) - 0.5).abs() < 1e-6);
+        assert!((boost.weight(60 * day) - 0.25).abs() < 1e-6);
 
-fn write_config(cfg: &AppConfig, config_path: &Path) -> Result<()> {
-    let cfg_toml = toml::to_string_pretty(cfg).context("Failed to serialize config to TOML")?;
-    fs::write(config_path, cfg_toml)
-        .with_context(|| format!("Failed to write config file: {}", config_path.display()))?;
-    Ok(())
-}
+        let floored = RecencyBoost {
+            half_life_days: 7.0,
+            min_weight: 0.2,
+        };
+        assert!((floored.weight(7 * day) - 0.6).abs() < 1e-6);
+        assert!((floored.weight(10_000 * day) - 0.2).abs() < 1e-6);
 
-// ---- Index helpers ----
+        assert!(RecencyBoost {
+            half_life_days: 0.0,
+            min_weight: 0.0
+        }
+        .validate()
+        .is_err());
+        assert!(RecencyBoost {
+            half_life_days: 7.0,
+            min_weight: 1.5
+        }
+        .validate()
+        .is_err());
+    }
 
-fn tantivy_index_exists(index_dir: &Path) -> bool {
-    index_dir.join("meta.json").exists()
-}
+    #[test]
+    fn test_write_atomically_replaces_file_and_cleans_up_on_failure() {
+        let dir = tempfile::tempdir().expect("temp dir");
+        let config = dir.path().join("config.toml");
+        fs::write(&config, "root = \"/old\"\n").unwrap();
 
-fn create_empty_index(index_dir: &Path) -> Result<()> {
-    let schema = build_schema();
-    let _index =
-        Index::create_in_dir(index_dir, schema).context("Failed to create Tantivy index")?;
-    Ok(())
-}
+        write_atomically(&config, b"root = \"/new\"\n").expect("atomic write");
+        assert_eq!(fs::read_to_string(&config).unwrap(), "root = \"/new\"\n");
 
-fn open_index(index_dir: &Path) -> Result<Index> {
-    Index::open_in_dir(index_dir).context("Failed to open Tantivy index")
-}
+        // Renaming over a non-empty directory fails after the temp file was written.
+        let blocked = dir.path().join("blocked");
+        fs::create_dir(&blocked).unwrap();
+        fs::write(blocked.join("keep"), "x").unwrap();
+        assert!(write_atomically(&blocked, b"data").is_err());
+        assert!(blocked.join("keep").exists());
 
-fn metadata_file_path(index_dir: &Path) -> PathBuf {
-    index_dir.join(METADATA_FILE)
-}
+        let mut names: Vec<String> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["blocked", "config.toml"]);
+    }
 
-fn load_file_metadata(index_dir: &Path) -> Result<HashMap<String, FileMetadata>> {
-    let path = metadata_file_path(index_dir);
+    #[test]
+    fn test_read_tail_lines_returns_last_lines() {
+        let mut file = NamedTempFile::new().expect("create temp file");
+        for i in 1..=5 {
+            writeln!(file, "line {i}").expect("write line");
+        }
 
-    if !path.exists() {
-        return Ok(HashMap::new());
+        assert_eq!(
+            read_tail_lines(file.path(), 2).unwrap(),
+            ["line 4", "line 5"]
+        );
+        assert_eq!(read_tail_lines(file.path(), 10).unwrap().len(), 5);
+        assert!(read_tail_lines(Path::new("/definitely/missing.log"), 2).is_err());
     }
 
-    let data = fs::read_to_string(&path)
-        .with_context(|| format!("Failed to read metadata file at {}", path.display()))?;
+    #[test]
+    fn test_split_wildcard_terms_extracts_bare_wildcards_only() {
+        let (rest, patterns) =
+            split_wildcard_terms("Config* report \"draft *\" path:a* *log.v2 size:[10 TO *]");
 
-    let entries: Vec<FileMetadata> = serde_json::from_str(&data)
-        .with_context(|| format!("Failed to parse metadata file at {}", path.display()))?;
+        assert_eq!(rest, "report \"draft *\" path:a* size:[10 TO *]");
+        assert_eq!(patterns, ["config*", "*log.v2"]);
+    }
 
-    Ok(entries
+    #[test]
+    fn test_split_stemmable_words_and_stem_word() {
+        let words: Vec<Option<String>> = split_stemmable_words(
+            "+Häuser AND \"alte häuser\" path:haus report.txt modified:[a TO b]",
+        )
         .into_iter()
-        .map(|entry| (entry.path.clone(), entry))
-        .collect())
-}
+        .map(|(_, word)| word)
+        .collect();
+        assert_eq!(
+            words,
+            vec![
+                Some("häuser".to_string()),
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None
+            ]
+        );
 
-fn save_file_metadata(index_dir: &Path, metadata: &HashMap<String, FileMetadata>) -> Result<()> {
-    let path = metadata_file_path(index_dir);
-    let entries: Vec<&FileMetadata> = metadata.values().collect();
-    let serialized =
-        serde_json::to_string_pretty(&entries).context("Failed to serialize file metadata")?;
-    fs::write(&path, serialized)
-        .with_context(|| format!("Failed to write metadata file at {}", path.display()))?;
-    Ok(())
-}
+        let mut german = stem_analyzer(StemLanguage::German);
+        assert_eq!(
+            stem_word(&mut german, "häuser"),
+            stem_word(&mut german, "hauses")
+        );
+        let mut english = stem_analyzer(StemLanguage::English);
+        assert_eq!(stem_word(&mut english, "reports"), "report");
+    }
 
-fn build_schema() -> Schema {
-    let mut schema_builder: SchemaBuilder = Schema::builder();
+    #[test]
+    fn test_wildcard_matches_prefix_suffix_and_infix() {
+        assert!(wildcard_matches("config*", "configuration"));
+        assert!(wildcard_matches("config*", "config"));
+        assert!(wildcard_matches("*port", "report"));
+        assert!(wildcard_matches("c*f*g", "config"));
+        assert!(!wildcard_matches("config*", "reconfigure"));
+        assert!(!wildcard_matches("*port", "portal"));
+        assert!(!wildcard_matches("ab*ba", "aba"));
+    }
 
-    // Path: stored so we can print it in results, also tokenized to search by path pieces.
-    schema_builder.add_text_field("path", TEXT | STORED);
+    #[test]
+    fn test_snippet_to_html_trims_partial_words_and_adds_ellipses() {
+        let source = "intro text; the quarterly invoice total was high, see appendix";
+        // A fragment that starts and ends mid-word, highlighting "invoice".
+        let offset = source.find("e quarterly").unwrap();
+        let fragment = &source[offset..offset + 33];
+        assert_eq!(fragment, "e quarterly invoice total was hig");
+        let start = fragment.find("invoice").unwrap();
+        let highlight = start..start + 7;
 
-    // Exact path: used for document replacement / deletion without tokenization.
-    schema_builder.add_text_field("path_exact", STRING | STORED);
+        assert_eq!(
+            snippet_to_html(fragment, std::slice::from_ref(&highlight), source),
+            "…quarterly <b>invoice</b> total was…"
+        );
+    }
 
-    // Contents: main text content we will index for full-text search.
-    schema_builder.add_text_field("contents", TEXT | STORED);
+    #[test]
+    fn test_snippet_to_html_keeps_complete_text_without_ellipses() {
+        let source = "a <short> note";
 
-    schema_builder.build()
-}
+        assert_eq!(
+            snippet_to_html(source, std::slice::from_ref(&(3..8)), source),
+            "a &lt;<b>short</b>&gt; note"
+        );
+        assert_eq!(snippet_to_html("", &[], source), "");
+    }
 
-// ---- File helpers ----
+    #[test]
+    fn test_stored_fields_json_truncates_long_text() {
+        let schema = build_schema(&ContentsAnalysis::default());
+        let path_field = schema.get_field("path").unwrap();
+        let contents_field = schema.get_field("contents").unwrap();
+        let long_contents = "x".repeat(FIELDS_JSON_MAX_CHARS + 10);
+        let doc = doc!(path_field => "/docs/a.txt", contents_field => long_contents);
 
-fn is_text_like(path: &Path) -> bool {
-    match path.extension().and_then(|s| s.to_str()) {
-        Some(ext) => {
-            let ext_lower = ext.to_ascii_lowercase();
-            TEXT_LIKE_EXTENSIONS.contains(&ext_lower.as_str())
-        }
-        None => false,
+        let fields = stored_fields_json(&schema, &doc);
+        assert_eq!(fields["path"], "/docs/a.txt");
+        let contents = fields["contents"].as_str().unwrap();
+        assert_eq!(contents.chars().count(), FIELDS_JSON_MAX_CHARS + 1);
+        assert!(contents.ends_with('…'));
+        assert!(!fields.contains_key("path_exact"));
     }
-}
 
-fn is_probably_binary(path: &Path) -> Result<bool> {
-    let mut file = fs::File::open(path)
-        .with_context(|| format!("Failed to open file {} for sniffing", path.display()))?;
-    let mut buf = [0u8; BINARY_SNIFF_BYTES];
-    let bytes_read = file
-        .read(&mut buf)
-        .with_context(|| format!("Failed to read file {}", path.display()))?;
-    let sample = &buf[..bytes_read];
+    #[test]
+    fn test_leading_excerpt_cuts_at_word_boundary() {
+        assert_eq!(leading_excerpt("short\nnote", 20), "short note");
+        assert_eq!(leading_excerpt("alpha beta gamma", 8), "alpha…");
+        assert_eq!(leading_excerpt("alpha beta gamma", 10), "alpha beta…");
+        assert_eq!(leading_excerpt("", 10), "");
+    }
 
-    if sample.iter().any(|&b| b == 0) {
-        return Ok(true);
+    #[test]
+    fn test_format_size_picks_unit() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KB");
+        assert_eq!(format_size(5 * 1024 * 1024), "5.0 MB");
     }
 
-    if std::str::from_utf8(sample).is_err() {
-        return Ok(true);
+    #[test]
+    fn test_parse_mode_filter() {
+        assert_eq!(parse_mode_filter("644").unwrap(), ModeFilter::Exact(0o644));
+        assert_eq!(parse_mode_filter("-002").unwrap(), ModeFilter::AllOf(0o002));
+        assert!(parse_mode_filter("9").is_err());
+        assert!(parse_mode_filter("17777").is_err());
+
+        assert!(ModeFilter::AllOf(0o002).matches(0o666));
+        assert!(!ModeFilter::AllOf(0o002).matches(0o644));
+        assert!(!ModeFilter::Exact(0o644).matches(0o664));
     }
 
-    Ok(false)
-}
+    #[test]
+    fn test_exchange_request_round_trips_results_and_errors() {
+        let request = SearchRequest {
+            query: "invoice".to_string(),
+            limit: 5,
+            offset: 0,
+            wildcard: false,
+            stem_language: Some(StemLanguage::German),
+            synonyms: false,
+            whole_word: false,
+            search_fields: Vec::new(),
+            field_boosts: BTreeMap::new(),
+            relevance: None,
+            sort: SortOrder::Score,
+            recency_boost: false,
+            recency_half_life: None,
+            owner: None,
+            mode: None,
+            exclude_path: Vec::new(),
+            limit_per_dir: None,
+            language: None,
+            min_lines: None,
+            max_lines: None,
+            modified_within: None,
+            since: None,
+            as_of_generation: None,
+            no_snippet: false,
+            fields_json: false,
+            file_details: false,
+            match_spans: false,
+            near_file: None,
+        };
 
-fn file_modified_timestamp(metadata: &fs::Metadata) -> Result<i64> {
-    let modified_time = metadata
-        .modified()
-        .context("Failed to read file modified timestamp")?;
-    let duration: Duration = modified_time
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_else(|_| Duration::from_secs(0));
-    Ok(duration.as_secs() as i64)
-}
+        let response = concat!(
+            r#"{"results":[{"rank":1,"score":2.0,"path":"/d/a.txt","#,
+            r#""relative_path":"a.txt","snippet":"a < b"}]}"#,
+            "\n"
+        );
+        let mut sent = Vec::new();
+        let results = exchange_request(response.as_bytes(), &mut sent, &request).unwrap();
+        assert_eq!(results[0].relative_path, "a.txt");
+        assert_eq!(results[0].snippet_html, "a &lt; b");
 
-#[derive(Debug)]
-struct FileReadResult {
-    contents: String,
-    hash: String,
-}
+        let sent: SearchRequest = serde_json::from_slice(&sent).unwrap();
+        assert_eq!(sent.query, "invoice");
+        assert_eq!(sent.limit, 5);
 
-fn read_file_streaming(path: &Path, size_hint: u64) -> Result<FileReadResult> {
-    let file =
-        fs::File::open(path).with_context(|| format!("Failed to open file {}", path.display()))?;
-    let mut reader = BufReader::new(file);
-    let mut contents = String::new();
-    let mut line = String::new();
-    let mut total_bytes: u64 = 0;
-    let mut hasher = Hasher::new();
+        let error = exchange_request(&b"{\"error\":\"bad query\"}\n"[..], Vec::new(), &request)
+            .unwrap_err();
+        assert!(error.to_string().contains("bad query"));
+    }
 
-    while reader
-        .read_line(&mut line)
-        .with_context(|| format!("Failed to read from file {}", path.display()))?
-        > 0
-    {
-        total_bytes += line.as_bytes().len() as u64;
+    #[test]
+    fn test_exit_code_for_finds_category_anywhere_in_chain() {
+        let plain = anyhow::anyhow!("boom");
+        assert_eq!(exit_code_for(&plain), 1);
 
-        if total_bytes > MAX_FILE_SIZE_BYTES || size_hint > MAX_FILE_SIZE_BYTES {
-            anyhow::bail!(
-                "File exceeded size limit while reading (limit {} bytes)",
-                MAX_FILE_SIZE_BYTES
-            );
-        }
+        let wrapped = anyhow::Error::new(categorized(FailureKind::QueryParse, "bad query"))
+            .context("outer context");
+        assert_eq!(exit_code_for(&wrapped), 5);
 
-        hasher.update(line.as_bytes());
-        contents.push_str(&line);
-        line.clear();
+        let as_context = Err::<(), _>(anyhow::anyhow!("io"))
+            .context(categorized(FailureKind::Config, "no config"))
+            .unwrap_err();
+        assert_eq!(exit_code_for(&as_context), 3);
     }
 
-    let hash = hasher.finalize().to_hex().to_string();
+    #[test]
+    fn test_check_index_integrity_reports_unreadable_index_as_corrupt() {
+        let dir = tempfile::tempdir().expect("temp dir");
+        create_empty_index(dir.path(), &ContentsAnalysis::default()).expect("create index");
+        let check =
+            check_index_integrity(dir.path(), &ContentsAnalysis::default()).expect("healthy index");
+        assert_eq!(check.documents, 0);
 
-    Ok(FileReadResult { contents, hash })
-}
+        fs::write(dir.path().join("meta.json"), "{ not json").expect("corrupt meta.json");
+        let err = check_index_integrity(dir.path(), &ContentsAnalysis::default()).unwrap_err();
+        assert_eq!(exit_code_for(&err), 6);
+        assert!(err.to_string().contains("appears corrupt"), "{err}");
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::io::Write;
-    use tempfile::NamedTempFile;
+        let payload: Box<dyn std::any::Any + Send> = Box::new(String::from("bad block"));
+        assert_eq!(panic_message(payload.as_ref()), "bad block");
+        let payload: Box<dyn std::any::Any + Send> = Box::new("static message");
+        assert_eq!(panic_message(payload.as_ref()), "static message");
+    }
 
     #[test]
-    fn test_is_text_like_matches_known_extensions() {
-        assert!(is_text_like(Path::new("notes.txt")));
-        assert!(is_text_like(Path::new("script.rs")));
-        assert!(!is_text_like(Path::new("archive.zip")));
-        assert!(!is_text_like(Path::new("no_extension")));
+    fn test_top_weighted_terms_prefers_rare_frequent_terms() {
+        let term_freqs = HashMap::from([
+            ("the".to_string(), 10),
+            ("invoice".to_string(), 3),
+            ("zebra".to_string(), 1),
+            ("unindexed".to_string(), 50),
+        ]);
+        let doc_freq = |term: &str| match term {
+            "the" => 100,
+            "invoice" => 2,
+            "zebra" => 1,
+            _ => 0,
+        };
+
+        let terms: Vec<String> = top_weighted_terms(term_freqs, doc_freq, 100, 2)
+            .into_iter()
+            .map(|(term, _)| term)
+            .collect();
+        assert_eq!(terms, ["invoice", "the"]);
     }
 
     #[test]
-    fn test_is_probably_binary_detects_null_bytes_and_utf8_errors() {
-        let mut text_file = NamedTempFile::new().expect("create temp file");
-        writeln!(text_file, "plain text content").expect("write text");
-
-        let mut binary_file = NamedTempFile::new().expect("create temp file");
-        binary_file
-            .write_all(b"binary\0content")
-            .expect("write binary");
+    fn test_parse_selection_handles_quit_range_and_garbage() {
+        assert_eq!(parse_selection("\n", 1, 3), Selection::Quit);
+        assert_eq!(parse_selection(" q \n", 1, 3), Selection::Quit);
+        assert_eq!(parse_selection("1\n", 1, 3), Selection::Open(0));
+        assert_eq!(parse_selection("3", 1, 3), Selection::Open(2));
+        assert_eq!(parse_selection("0", 1, 3), Selection::Invalid);
+        assert_eq!(parse_selection("4", 1, 3), Selection::Invalid);
+        assert_eq!(parse_selection("two", 1, 3), Selection::Invalid);
+    }
 
-        assert_eq!(is_probably_binary(text_file.path()).unwrap(), false);
-        assert_eq!(is_probably_binary(binary_file.path()).unwrap(), true);
+    #[test]
+    fn test_parse_selection_accepts_offset_ranks() {
+        assert_eq!(parse_selection("11", 11, 3), Selection::Open(0));
+        assert_eq!(parse_selection("13", 11, 3), Selection::Open(2));
+        assert_eq!(parse_selection("1", 11, 3), Selection::Invalid);
+        assert_eq!(parse_selection("14", 11, 3), Selection::Invalid);
     }
 
     #[test]
@@ -717,22 +9871,153 @@ mod tests {
         writeln!(file, "line two").expect("write line two");
 
         let metadata = file.as_file().metadata().expect("metadata");
-        let file_data = read_file_streaming(file.path(), metadata.len()).expect("read contents");
+        let file_data = read_file_streaming(file.path(), metadata.len(), MAX_FILE_SIZE_BYTES)
+            .expect("read contents");
 
         assert!(file_data.contents.contains("line one"));
         assert!(file_data.contents.contains("line two"));
         assert!(!file_data.hash.is_empty());
     }
 
+    #[cfg(feature = "csv-index")]
+    #[test]
+    fn test_expand_csv_rows_pairs_headers_with_values() {
+        let expanded = expand_csv_rows(
+            "name,email\nAda,ada@example.com\nBob,\n",
+            MAX_FILE_SIZE_BYTES,
+        )
+        .unwrap();
+
+        assert_eq!(expanded, "name: Ada; email: ada@example.com\nname: Bob\n");
+    }
+
+    #[cfg(feature = "csv-index")]
+    #[test]
+    fn test_csv_extractor_falls_back_to_raw_text_for_bad_csv() {
+        let registry = ExtractorRegistry::builtin(false);
+        let mut file = tempfile::Builder::new()
+            .suffix(".CSV")
+            .tempfile()
+            .expect("create temp file");
+        write!(file, "a,b\n1,2,3\n").expect("write csv");
+
+        let extracted = registry
+            .for_path(file.path())
+            .extract(file.path(), 10, MAX_FILE_SIZE_BYTES)
+            .unwrap();
+        assert_eq!(extracted.contents, "a,b\n1,2,3\n");
+        assert_eq!(extracted.lines, 2);
+    }
+
+    #[cfg(feature = "structured-index")]
+    #[test]
+    fn test_flatten_structured_writes_dotted_key_paths() {
+        let json = r#"{"database": {"host": "db.local", "port": 5432, "replica": null},
+                       "servers": [{"name": "a"}, {"name": "b"}], "debug": true}"#;
+        let flattened =
+            flatten_structured(json, StructuredFormat::Json, MAX_FILE_SIZE_BYTES).unwrap();
+        assert_eq!(
+            flattened,
+            "database.host: db.local\ndatabase.port: 5432\ndebug: true\n\
+             servers.0.name: a\nservers.1.name: b\n"
+        );
+
+        let yaml = "database:\n  host: db.local\n---\ncache:\n  ttl: 60\n";
+        let flattened =
+            flatten_structured(yaml, StructuredFormat::Yaml, MAX_FILE_SIZE_BYTES).unwrap();
+        assert_eq!(flattened, "database.host: db.local\ncache.ttl: 60\n");
+
+        assert!(flatten_structured("{oops", StructuredFormat::Json, MAX_FILE_SIZE_BYTES).is_err());
+    }
+
+    #[test]
+    fn test_extractor_registry_falls_back_to_plain_text() {
+        struct Shouting;
+        impl Extractor for Shouting {
+            fn extract(
+                &self,
+                path: &Path,
+                size_hint: u64,
+                max_bytes: u64,
+            ) -> Result<FileReadResult> {
+                let mut raw = read_file_streaming(path, size_hint, max_bytes)?;
+                raw.contents = raw.contents.to_uppercase();
+                Ok(raw)
+            }
+        }
+
+        let mut registry = ExtractorRegistry::builtin(false);
+        registry.register("LOUD", Shouting);
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let loud = dir.path().join("note.loud");
+        let plain = dir.path().join("note.txt");
+        fs::write(&loud, "hello").unwrap();
+        fs::write(&plain, "hello").unwrap();
+
+        let read = |path: &Path| {
+            registry
+                .for_path(path)
+                .extract(path, 5, MAX_FILE_SIZE_BYTES)
+                .unwrap()
+                .contents
+        };
+        assert_eq!(read(&loud), "HELLO");
+        assert_eq!(read(&plain), "hello");
+    }
+
+    #[test]
+    fn test_detect_language_marks_short_text_unknown() {
+        let french = "Le chat dort sur le canapé pendant que les enfants jouent dans le jardin.";
+        let spanish = "El gato duerme en el sofá mientras los niños juegan en el jardín.";
+        assert_eq!(detect_language(french), "fra");
+        assert_eq!(detect_language(spanish), "spa");
+        assert_eq!(detect_language("ok"), UNKNOWN_LANGUAGE);
+        assert_eq!(detect_language(""), UNKNOWN_LANGUAGE);
+
+        assert_eq!(parse_language_code("FRA").as_deref(), Ok("fra"));
+        assert_eq!(parse_language_code("unknown").as_deref(), Ok("unknown"));
+        assert!(parse_language_code("fr").is_err());
+    }
+
+    #[test]
+    fn test_extract_comments_keeps_line_and_block_comments() {
+        let rust = comment_syntax(Path::new("lib.RS")).expect("rust syntax");
+        let source = "/// Loads the manifest.\nfn load() {\n    let x = 1; // tweak\n}\n\
+                      /* spans\n   two lines */ fn after() {}\n";
+        assert_eq!(
+            extract_comments(source, rust),
+            "Loads the manifest.\ntweak\nspans\ntwo lines\n"
+        );
+
+        let python = comment_syntax(Path::new("app.py")).expect("python syntax");
+        let source = "def run():\n    \"\"\"Runs it.\"\"\"\n    return 1  # done\n";
+        assert_eq!(extract_comments(source, python), "Runs it.\ndone\n");
+
+        assert!(comment_syntax(Path::new("notes.md")).is_none());
+    }
+
     #[test]
     fn test_read_file_streaming_errors_when_size_hint_exceeds_limit() {
         let mut file = NamedTempFile::new().expect("create temp file");
         writeln!(file, "small").expect("write content");
 
         let oversized_hint = MAX_FILE_SIZE_BYTES + 1;
-        let err = read_file_streaming(file.path(), oversized_hint).unwrap_err();
+        let err =
+            read_file_streaming(file.path(), oversized_hint, MAX_FILE_SIZE_BYTES).unwrap_err();
 
         let message = format!("{err}");
         assert!(message.contains("size limit"));
     }
+
+    #[test]
+    fn test_use_color_auto_requires_terminal_and_no_no_color() {
+        let set = Some(std::ffi::OsStr::new("1"));
+        let empty = Some(std::ffi::OsStr::new(""));
+        assert!(use_color(ColorChoice::Auto, None, true));
+        assert!(use_color(ColorChoice::Auto, empty, true));
+        assert!(!use_color(ColorChoice::Auto, None, false));
+        assert!(!use_color(ColorChoice::Auto, set, true));
+        assert!(use_color(ColorChoice::Always, set, false));
+        assert!(!use_color(ColorChoice::Never, None, true));
+    }
 }
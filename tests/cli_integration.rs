@@ -1,8 +1,9 @@
 use assert_cmd::{cargo::cargo_bin_cmd, Command};
+use predicates::prelude::*;
 use predicates::str::contains;
 use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use tempfile::TempDir;
 
 fn test_environment(base: &TempDir) -> HashMap<&'static str, String> {
@@ -83,3 +84,3716 @@ fn init_index_and_search_flow() {
         .success()
         .stdout(contains("updates.txt"));
 }
+
+#[test]
+fn max_depth_zero_skips_nested_files() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    create_sample_files(&root);
+    let nested = root.join("vendor").join("deep");
+    fs::create_dir_all(&nested).expect("create nested dir");
+    fs::write(nested.join("buried.txt"), "vendored dependency").expect("write buried.txt");
+
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap(), "--max-depth", "0"])
+        .assert()
+        .success();
+
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    search_cmd
+        .args(["search", "vendored"])
+        .assert()
+        .success()
+        .stdout(contains("No results found"));
+
+    // A one-off override on `index` descends further without touching the config.
+    let mut index_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut index_cmd, &envs);
+    index_cmd
+        .args(["index", "--max-depth", "2"])
+        .assert()
+        .success();
+
+    let mut search_deep_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_deep_cmd, &envs);
+    search_deep_cmd
+        .args(["search", "vendored"])
+        .assert()
+        .success()
+        .stdout(contains("buried.txt"));
+}
+
+#[test]
+fn no_recursive_indexes_only_top_level_files() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    fs::create_dir_all(root.join("sub")).expect("create sub dir");
+    fs::write(root.join("top.txt"), "shallow reef").expect("write file");
+    fs::write(root.join("sub").join("nested.txt"), "deep reef").expect("write file");
+
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success();
+
+    let mut index_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut index_cmd, &envs);
+    index_cmd
+        .args(["index", "--no-recursive"])
+        .assert()
+        .success()
+        .stdout(contains("Max depth      : 0"))
+        .stdout(contains("Removed files  : 1"));
+
+    // `recursive = false` in the config does the same on every run.
+    let config_path = PathBuf::from(&envs["XDG_CONFIG_HOME"])
+        .join("vaultsearch")
+        .join("config.toml");
+    let config = fs::read_to_string(&config_path).expect("read config");
+    fs::write(&config_path, format!("recursive = false\n{config}")).expect("write config");
+
+    let mut index_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut index_cmd, &envs);
+    index_cmd
+        .arg("index")
+        .assert()
+        .success()
+        .stdout(contains("Max depth      : 0"));
+
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    search_cmd
+        .args(["search", "reef"])
+        .assert()
+        .success()
+        .stdout(contains("top.txt"))
+        .stdout(contains("nested.txt").not());
+}
+
+#[test]
+fn store_relative_paths_survives_moving_the_root() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    create_sample_files(&root);
+
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success();
+
+    let config_path = PathBuf::from(&envs["XDG_CONFIG_HOME"])
+        .join("vaultsearch")
+        .join("config.toml");
+    let config = fs::read_to_string(&config_path).expect("read config");
+    fs::write(
+        &config_path,
+        format!("store_relative_paths = true\n{config}"),
+    )
+    .expect("write config");
+
+    // Switching re-adds every file under its relative path and drops the absolute ones.
+    let mut index_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut index_cmd, &envs);
+    index_cmd
+        .arg("index")
+        .assert()
+        .success()
+        .stdout(contains("Added files    : 2"))
+        .stdout(contains("Removed files  : 2"));
+
+    let old_root = fs::canonicalize(&root).expect("canonical root");
+    let moved = temp_dir.path().join("moved");
+    fs::rename(&root, &moved).expect("move root");
+    let new_root = fs::canonicalize(&moved).expect("canonical moved root");
+    let config = fs::read_to_string(&config_path).expect("read config");
+    fs::write(
+        &config_path,
+        config.replace(old_root.to_str().unwrap(), new_root.to_str().unwrap()),
+    )
+    .expect("write config");
+
+    let mut index_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut index_cmd, &envs);
+    index_cmd
+        .arg("index")
+        .assert()
+        .success()
+        .stdout(contains("Unchanged files: 2"))
+        .stdout(contains("Removed files  : 0"));
+
+    let expected = new_root.join("notes.txt");
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    search_cmd
+        .args(["search", "rust", "--format", "json"])
+        .assert()
+        .success()
+        .stdout(contains(format!(
+            r#""path": "{}""#,
+            expected.to_str().unwrap()
+        )));
+
+    // Federation keeps relative paths as stored rather than joining them onto this root.
+    let index_dir = PathBuf::from(&envs["XDG_DATA_HOME"])
+        .join("vaultsearch")
+        .join("index");
+    let mut federated_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut federated_cmd, &envs);
+    federated_cmd
+        .args(["search", "rust", "--format", "json", "--index"])
+        .arg(&index_dir)
+        .assert()
+        .success()
+        .stdout(contains(r#""path": "notes.txt""#));
+}
+
+#[test]
+fn show_config_source_reports_the_effective_config() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    create_sample_files(&root);
+
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success();
+
+    let mut index_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut index_cmd, &envs);
+    index_cmd
+        .args(["index", "--show-config-source"])
+        .assert()
+        .success()
+        .stderr(contains("config.toml (default location)"))
+        .stderr(contains("Project config : none"))
+        .stderr(contains("Max file size  : 5000000 bytes"));
+
+    fs::write(
+        root.join(".vaultsearch.toml"),
+        "[size_limits]\ndefault = 1000\n",
+    )
+    .expect("write project config");
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    search_cmd
+        .current_dir(&root)
+        .args(["search", "rust", "--show-config-source", "--format", "json"])
+        .assert()
+        .success()
+        .stdout(predicates::str::starts_with("["))
+        .stderr(contains(".vaultsearch.toml (overrides the config file)"))
+        .stderr(contains("Max file size  : 1000 bytes"));
+}
+
+#[cfg(unix)]
+#[test]
+fn symlinked_file_is_indexed_once() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    create_sample_files(&root);
+    std::os::unix::fs::symlink(root.join("notes.txt"), root.join("notes-link.txt"))
+        .expect("create symlink");
+
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success()
+        .stdout(contains("Added files    : 2"))
+        .stdout(contains("Duplicate paths       : 1"));
+}
+
+#[test]
+fn default_excluded_dirs_are_pruned_unless_disabled() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    create_sample_files(&root);
+    let modules = root.join("node_modules").join("left-pad");
+    fs::create_dir_all(&modules).expect("create node_modules");
+    fs::write(modules.join("README.md"), "padding helper").expect("write module readme");
+
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success()
+        .stdout(contains("Pruned dirs    : 1"));
+
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    search_cmd
+        .args(["search", "padding"])
+        .assert()
+        .success()
+        .stdout(contains("No results found"));
+
+    let mut index_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut index_cmd, &envs);
+    index_cmd
+        .args(["index", "--no-default-excludes"])
+        .assert()
+        .success()
+        .stdout(contains("Added files    : 1"));
+}
+
+#[test]
+fn failures_use_category_exit_codes() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    // No config yet.
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    search_cmd
+        .args(["search", "rust"])
+        .assert()
+        .code(3)
+        .stderr(contains("vaultsearch init"));
+
+    let root = temp_dir.path().join("workspace");
+    create_sample_files(&root);
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success();
+
+    let mut bad_query_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut bad_query_cmd, &envs);
+    bad_query_cmd
+        .args(["search", "nosuchfield:rust"])
+        .assert()
+        .code(5);
+}
+
+#[test]
+fn project_config_overrides_global_root() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    create_sample_files(&root);
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success();
+
+    let project = temp_dir.path().join("project");
+    let docs = project.join("docs");
+    fs::create_dir_all(project.join("src")).expect("create project src");
+    fs::create_dir_all(&docs).expect("create project docs");
+    fs::write(docs.join("design.md"), "quantum flux design").expect("write design.md");
+    fs::write(project.join(".vaultsearch.toml"), "root = \"docs\"\n")
+        .expect("write project config");
+
+    let mut index_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut index_cmd, &envs);
+    index_cmd
+        .current_dir(project.join("src"))
+        .arg("index")
+        .assert()
+        .success()
+        .stdout(contains("Project config : "))
+        .stdout(contains("Created index for project root"))
+        .stdout(contains("Added files    : 1"))
+        .stdout(contains("Removed files  : 0"));
+
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    search_cmd
+        .current_dir(&project)
+        .args(["search", "quantum"])
+        .assert()
+        .success()
+        .stdout(contains("design.md"));
+
+    let config_path = PathBuf::from(&envs["XDG_CONFIG_HOME"])
+        .join("vaultsearch")
+        .join("config.toml");
+    let global = fs::read_to_string(config_path).expect("read global config");
+    assert!(global.contains("workspace"));
+    assert!(!global.contains("docs"));
+
+    // The project root has its own index, so the global root's files are all still there.
+    let mut global_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut global_cmd, &envs);
+    global_cmd
+        .current_dir(temp_dir.path())
+        .args(["search", "rust"])
+        .assert()
+        .success()
+        .stdout(contains("notes.txt"))
+        .stdout(contains("design.md").not());
+}
+
+#[test]
+fn min_file_size_skips_stub_files() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    fs::create_dir_all(&root).expect("create root");
+    fs::write(root.join("stub.md"), "todo").expect("write stub.md");
+    fs::write(root.join("essay.md"), "todo: ".repeat(200)).expect("write essay.md");
+
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success();
+
+    let mut index_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut index_cmd, &envs);
+    index_cmd
+        .args(["index", "--min-file-size", "1KB"])
+        .assert()
+        .success()
+        .stdout(contains("Min file size  : 1024 bytes"))
+        .stdout(contains("Too small             : 1"))
+        .stdout(contains("Removed files  : 1"));
+
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    search_cmd
+        .args(["search", "todo", "--no-snippet"])
+        .assert()
+        .success()
+        .stdout(contains("essay.md"))
+        .stdout(contains("stub.md").not());
+
+    let mut errors_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut errors_cmd, &envs);
+    errors_cmd
+        .args(["last-errors", "--reason", "too-small"])
+        .assert()
+        .success()
+        .stdout(contains("stub.md"));
+
+    let mut bad_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut bad_cmd, &envs);
+    bad_cmd
+        .args(["index", "--min-file-size", "tiny"])
+        .assert()
+        .code(2)
+        .stderr(contains("invalid size"));
+}
+
+#[test]
+fn fail_if_skip_ratio_fails_after_summary() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    create_sample_files(&root);
+    fs::write(root.join("photo.raw"), "not indexed").expect("write photo.raw");
+
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success();
+
+    let mut strict_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut strict_cmd, &envs);
+    strict_cmd
+        .args(["index", "--fail-if-skip-ratio", "20"])
+        .assert()
+        .code(1)
+        .stdout(contains("Skipped files  : 1"))
+        .stderr(contains("Skipped 33.3% of files"));
+
+    let mut lenient_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut lenient_cmd, &envs);
+    lenient_cmd
+        .args(["index", "--fail-if-skip-ratio", "50"])
+        .assert()
+        .success();
+}
+
+#[test]
+fn fail_on_empty_index_fails_when_nothing_was_indexed() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    fs::create_dir_all(root.join("node_modules")).expect("create root");
+    fs::write(root.join("node_modules").join("lib.js"), "excluded").expect("write file");
+
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success();
+
+    let mut strict_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut strict_cmd, &envs);
+    strict_cmd
+        .args(["index", "--fail-on-empty-index"])
+        .assert()
+        .code(1)
+        .stdout(contains("Indexing complete."))
+        .stderr(contains("The index has no documents after indexing"));
+
+    fs::write(root.join("notes.txt"), "indexed").expect("write file");
+    let mut strict_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut strict_cmd, &envs);
+    strict_cmd
+        .args(["index", "--fail-on-empty-index"])
+        .assert()
+        .success();
+}
+
+#[test]
+fn resume_counts_files_committed_by_the_unfinished_run() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    create_sample_files(&root);
+    let index_dir = temp_dir.path().join("index");
+
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args([
+            "init",
+            "--root",
+            root.to_str().unwrap(),
+            "--index-dir",
+            index_dir.to_str().unwrap(),
+        ])
+        .assert()
+        .success();
+    let marker = index_dir.join("unfinished_run.json");
+    assert!(!marker.exists());
+
+    // Pretend the first run was killed after committing both sample files.
+    fs::write(
+        &marker,
+        r#"{"generation":1,"started":"2026-01-01T00:00:00+00:00"}"#,
+    )
+    .expect("write marker");
+    fs::write(root.join("later.txt"), "not reached before the kill").expect("write later.txt");
+
+    let mut resume_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut resume_cmd, &envs);
+    resume_cmd
+        .args(["index", "--resume", "--checkpoint-every", "1"])
+        .assert()
+        .success()
+        .stdout(contains("Resuming       : generation 1"))
+        .stdout(contains("Already indexed: 2 (by the interrupted run)"))
+        .stdout(contains("Added files    : 1"))
+        .stdout(contains("Generation    : 1"));
+    assert!(!marker.exists());
+
+    let mut again_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut again_cmd, &envs);
+    again_cmd
+        .args(["index", "--resume"])
+        .assert()
+        .success()
+        .stdout(contains("nothing to resume"));
+}
+
+#[test]
+fn any_of_matches_files_mentioning_any_listed_keyword() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    fs::create_dir_all(&root).expect("create root");
+    fs::write(root.join("a.log"), "connection timeout").expect("write a.log");
+    fs::write(root.join("b.log"), "error: disk full on /var").expect("write b.log");
+    fs::write(root.join("c.log"), "all good, disk healthy").expect("write c.log");
+    let keywords = temp_dir.path().join("watchlist.txt");
+    fs::write(&keywords, "# watchlist\n\ntimeout\ndisk full\n").expect("write keywords");
+
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success();
+
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    search_cmd
+        .args([
+            "search",
+            "--any-of",
+            keywords.to_str().unwrap(),
+            "--modified-within",
+            "1d",
+            "--no-snippet",
+        ])
+        .assert()
+        .success()
+        .stdout(contains("a.log"))
+        .stdout(contains("b.log"))
+        .stdout(contains("c.log").not());
+
+    let mut narrowed_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut narrowed_cmd, &envs);
+    narrowed_cmd
+        .args(["search", "error", "--any-of", keywords.to_str().unwrap()])
+        .assert()
+        .success()
+        .stdout(contains("b.log"))
+        .stdout(contains("a.log").not());
+}
+
+#[test]
+fn language_filter_keeps_files_detected_as_that_language() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    fs::create_dir_all(&root).expect("create root");
+    fs::write(
+        root.join("chat.txt"),
+        "Le chat dort sur le canapé pendant que les enfants jouent dans le jardin avec leurs amis.",
+    )
+    .expect("write chat.txt");
+    fs::write(
+        root.join("cat.txt"),
+        "The cat sleeps on the sofa while the children play in the garden with their friends.",
+    )
+    .expect("write cat.txt");
+
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success();
+
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    search_cmd
+        .args(["search", "chat OR cat", "--language", "fra", "--verbose"])
+        .assert()
+        .success()
+        .stdout(contains("chat.txt"))
+        .stdout(contains("language: fra"))
+        .stdout(contains("cat.txt").not());
+
+    let mut bad_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut bad_cmd, &envs);
+    bad_cmd
+        .args(["search", "cat", "--language", "fr"])
+        .assert()
+        .code(2)
+        .stderr(contains("ISO 639-3"));
+}
+
+#[test]
+fn filename_only_match_shows_labeled_excerpt() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    create_sample_files(&root);
+    fs::write(root.join("quarterly.txt"), "revenue figures by region")
+        .expect("write quarterly.txt");
+
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success();
+
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    search_cmd
+        .args(["search", "quarterly", "--format", "json"])
+        .assert()
+        .success()
+        .stdout(contains("[matched filename] revenue figures by region"));
+}
+
+#[test]
+fn compact_merges_segments_and_keeps_results() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    create_sample_files(&root);
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success();
+
+    fs::write(root.join("later.txt"), "added after the first run").expect("write later.txt");
+    let mut index_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut index_cmd, &envs);
+    index_cmd.arg("index").assert().success();
+
+    let mut compact_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut compact_cmd, &envs);
+    compact_cmd
+        .arg("compact")
+        .assert()
+        .success()
+        .stdout(contains("Compaction complete."))
+        .stdout(contains("Segments       : 2 -> 1"));
+
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    search_cmd
+        .args(["search", "added"])
+        .assert()
+        .success()
+        .stdout(contains("later.txt"));
+}
+
+#[test]
+fn no_snippet_prints_only_ranked_paths() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    create_sample_files(&root);
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success();
+
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    search_cmd
+        .args(["search", "rust", "--no-snippet"])
+        .assert()
+        .success()
+        .stdout(contains("notes.txt"))
+        .stdout(contains("search tools").not());
+}
+
+#[cfg(unix)]
+#[test]
+fn mode_filter_finds_world_writable_files() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    create_sample_files(&root);
+    fs::write(root.join("shared.txt"), "rust shared notes").expect("write shared.txt");
+    fs::set_permissions(root.join("notes.txt"), fs::Permissions::from_mode(0o644))
+        .expect("chmod notes.txt");
+    fs::set_permissions(root.join("shared.txt"), fs::Permissions::from_mode(0o666))
+        .expect("chmod shared.txt");
+
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success();
+
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    search_cmd
+        .args(["search", "rust", "--mode", "-002"])
+        .assert()
+        .success()
+        .stdout(contains("shared.txt"))
+        .stdout(contains("notes.txt").not());
+}
+
+#[test]
+fn cwd_relative_paths_fall_back_to_absolute() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    create_sample_files(&root);
+    let nested = root.join("projects");
+    fs::create_dir_all(&nested).expect("create projects dir");
+    fs::write(nested.join("plan.txt"), "rust roadmap").expect("write plan.txt");
+
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success();
+
+    let canonical_root = fs::canonicalize(&root).expect("canonicalize root");
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    search_cmd
+        .current_dir(&nested)
+        .args(["search", "rust", "--cwd-relative", "--format", "csv"])
+        .assert()
+        .success()
+        .stdout(contains(",plan.txt,"))
+        .stdout(contains(format!(
+            ",{},",
+            canonical_root.join("notes.txt").display()
+        )));
+}
+
+#[cfg(unix)]
+#[test]
+fn follow_symlinks_reports_link_targets() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    create_sample_files(&root);
+    let outside = temp_dir.path().join("archive");
+    fs::create_dir_all(&outside).expect("create archive dir");
+    fs::write(outside.join("ledger.txt"), "archived ledger entries").expect("write ledger.txt");
+    std::os::unix::fs::symlink(&outside, root.join("archive-link")).expect("create symlink");
+
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success()
+        .stdout(contains("Added files    : 2"));
+
+    let mut index_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut index_cmd, &envs);
+    index_cmd
+        .args(["index", "--follow-symlinks"])
+        .assert()
+        .success()
+        .stdout(contains("Added files    : 1"));
+
+    let target = fs::canonicalize(outside.join("ledger.txt")).expect("canonicalize target");
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    search_cmd
+        .args(["search", "ledger"])
+        .assert()
+        .success()
+        .stdout(contains(format!(
+            "archive-link/ledger.txt (via symlink → {})",
+            target.display()
+        )));
+}
+
+#[test]
+fn separator_and_no_header_shape_text_output() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    create_sample_files(&root);
+    fs::write(root.join("more.txt"), "rust again").expect("write more.txt");
+
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success();
+
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    search_cmd
+        .args([
+            "search",
+            "rust",
+            "--no-header",
+            "--no-snippet",
+            "--separator",
+            "--",
+        ])
+        .assert()
+        .success()
+        .stdout(predicates::str::starts_with(" 1. "))
+        .stdout(contains("\n--\n 2. "))
+        .stdout(predicates::str::ends_with("--\n").not());
+}
+
+#[test]
+fn search_server_answers_remote_queries() {
+    use std::io::{BufRead, BufReader};
+    use std::process::{Command as StdCommand, Stdio};
+
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    create_sample_files(&root);
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success();
+
+    let mut server = StdCommand::new(env!("CARGO_BIN_EXE_vaultsearch"))
+        .args(["serve", "--listen", "127.0.0.1:0"])
+        .envs(&envs)
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("start server");
+    let mut banner = String::new();
+    BufReader::new(server.stdout.take().expect("server stdout"))
+        .read_line(&mut banner)
+        .expect("read server banner");
+    let addr = banner
+        .trim()
+        .rsplit(' ')
+        .next()
+        .expect("listen address")
+        .to_string();
+
+    // The client needs no local config at all.
+    let client_dir = TempDir::new().expect("create client dir");
+    let client_envs = test_environment(&client_dir);
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &client_envs);
+    let assert = search_cmd
+        .args(["search", "rust", "--server", &addr, "--format", "json"])
+        .assert();
+    server.kill().expect("stop server");
+    server.wait().expect("reap server");
+
+    assert
+        .success()
+        .stdout(contains("\"relative_path\": \"notes.txt\""));
+}
+
+#[test]
+fn search_server_rejects_oversized_requests() {
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpStream;
+    use std::process::{Command as StdCommand, Stdio};
+
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    create_sample_files(&root);
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success();
+
+    let mut server = StdCommand::new(env!("CARGO_BIN_EXE_vaultsearch"))
+        .args(["serve", "--listen", "127.0.0.1:0"])
+        .envs(&envs)
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("start server");
+    let mut banner = String::new();
+    BufReader::new(server.stdout.take().expect("server stdout"))
+        .read_line(&mut banner)
+        .expect("read server banner");
+    let addr = banner.trim().rsplit(' ').next().expect("listen address");
+
+    let mut stream = TcpStream::connect(addr).expect("connect to server");
+    let mut responses = BufReader::new(stream.try_clone().expect("clone stream"));
+    let mut exchange = |request: &[u8]| {
+        stream.write_all(request).expect("send request");
+        let mut response = String::new();
+        responses.read_line(&mut response).expect("read response");
+        response
+    };
+    let huge_limit = exchange(b"{\"query\":\"rust\",\"limit\":100000000000}\n");
+    let huge_offset = exchange(b"{\"query\":\"rust\",\"offset\":18446744073709551000}\n");
+    let still_serving = exchange(b"{\"query\":\"rust\"}\n");
+    // One byte past the cap with no newline, so the server has nothing left unread.
+    stream
+        .write_all(&vec![b' '; 64 * 1024 + 1])
+        .expect("send long line");
+    stream
+        .shutdown(std::net::Shutdown::Write)
+        .expect("close request side");
+    let mut oversized = String::new();
+    responses.read_line(&mut oversized).expect("read response");
+    server.kill().expect("stop server");
+    server.wait().expect("reap server");
+
+    assert!(
+        huge_limit.contains("offset + limit must be at most"),
+        "{huge_limit}"
+    );
+    assert!(
+        huge_offset.contains("offset + limit must be at most"),
+        "{huge_offset}"
+    );
+    assert!(still_serving.contains("notes.txt"), "{still_serving}");
+    assert!(oversized.contains("line exceeds"), "{oversized}");
+}
+
+#[test]
+fn manual_reader_reload_sees_commits_on_the_next_request() {
+    use std::io::{BufRead, BufReader};
+    use std::process::{Command as StdCommand, Stdio};
+
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    create_sample_files(&root);
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success();
+    let config_path = PathBuf::from(&envs["XDG_CONFIG_HOME"])
+        .join("vaultsearch")
+        .join("config.toml");
+    let config = fs::read_to_string(&config_path).expect("read config");
+    fs::write(
+        &config_path,
+        format!("reader_reload = \"manual\"\n{config}"),
+    )
+    .expect("write config");
+
+    let mut server = StdCommand::new(env!("CARGO_BIN_EXE_vaultsearch"))
+        .args(["serve", "--listen", "127.0.0.1:0"])
+        .envs(&envs)
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("start server");
+    let mut banner = String::new();
+    BufReader::new(server.stdout.take().expect("server stdout"))
+        .read_line(&mut banner)
+        .expect("read server banner");
+    let addr = banner
+        .trim()
+        .rsplit(' ')
+        .next()
+        .expect("listen address")
+        .to_string();
+
+    fs::write(root.join("zebra.txt"), "zebra crossing").expect("write zebra.txt");
+    let mut index_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut index_cmd, &envs);
+    index_cmd.arg("index").assert().success();
+
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    let assert = search_cmd
+        .args(["search", "zebra", "--server", &addr, "--no-snippet"])
+        .assert();
+    server.kill().expect("stop server");
+    server.wait().expect("reap server");
+
+    assert.success().stdout(contains("zebra.txt"));
+}
+
+#[cfg(unix)]
+#[test]
+fn print0_paths_round_trip_through_xargs() {
+    use std::io::Write;
+    use std::process::{Command as StdCommand, Stdio};
+
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    create_sample_files(&root);
+    fs::write(root.join("my notes.txt"), "spaced rust notes").expect("write spaced file");
+
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success();
+
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    let output = search_cmd
+        .args(["search", "spaced", "--print0"])
+        .output()
+        .expect("run search");
+    assert!(output.status.success());
+    assert!(output.stdout.ends_with(b"my notes.txt\0"));
+
+    let mut xargs = StdCommand::new("xargs")
+        .args(["-0", "echo"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("run xargs");
+    xargs
+        .stdin
+        .take()
+        .expect("xargs stdin")
+        .write_all(&output.stdout)
+        .expect("feed xargs");
+    let echoed = xargs.wait_with_output().expect("wait for xargs");
+
+    let expected = fs::canonicalize(&root).unwrap().join("my notes.txt");
+    assert_eq!(
+        String::from_utf8_lossy(&echoed.stdout).trim_end(),
+        expected.to_string_lossy()
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn daemon_answers_searches_and_client_falls_back_without_it() {
+    use std::io::{BufRead, BufReader};
+    use std::process::{Command as StdCommand, Stdio};
+
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    create_sample_files(&root);
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success();
+
+    // Without a daemon the client opens the index itself (and highlights locally).
+    let mut direct_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut direct_cmd, &envs);
+    direct_cmd
+        .args(["search", "rust", "--daemon", "--color", "always"])
+        .assert()
+        .success()
+        .stdout(contains("notes.txt"))
+        .stdout(contains("\u{1b}[1m"));
+
+    let mut daemon = StdCommand::new(env!("CARGO_BIN_EXE_vaultsearch"))
+        .arg("daemon")
+        .envs(&envs)
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("start daemon");
+    let mut banner = String::new();
+    BufReader::new(daemon.stdout.take().expect("daemon stdout"))
+        .read_line(&mut banner)
+        .expect("read daemon banner");
+
+    // Another config has its own index, so the daemon must not answer for it.
+    let other_root = temp_dir.path().join("other");
+    fs::create_dir_all(&other_root).expect("create other root");
+    fs::write(other_root.join("other.txt"), "rust elsewhere").expect("write other.txt");
+    let other_config = temp_dir.path().join("other.toml");
+    let mut other_init = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut other_init, &envs);
+    other_init
+        .args([
+            "init",
+            "--root",
+            other_root.to_str().unwrap(),
+            "--index-dir",
+        ])
+        .arg(temp_dir.path().join("other-index"))
+        .arg("--config")
+        .arg(&other_config)
+        .assert()
+        .success();
+
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    let assert = search_cmd
+        .args(["search", "rust", "--daemon", "--color", "always"])
+        .assert();
+    let mut other_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut other_cmd, &envs);
+    let other_assert = other_cmd
+        .arg("--config")
+        .arg(&other_config)
+        .args(["search", "rust", "--daemon", "--color", "always"])
+        .assert();
+    daemon.kill().expect("stop daemon");
+    daemon.wait().expect("reap daemon");
+
+    assert!(banner.contains(".sock"), "{banner}");
+    // Daemon responses carry plain snippets, so there are no highlight escapes.
+    assert
+        .success()
+        .stdout(contains("notes.txt"))
+        .stdout(contains("\u{1b}[1m").not());
+    // Searched directly, hence highlighted.
+    other_assert
+        .success()
+        .stdout(contains("other.txt"))
+        .stdout(contains("notes.txt").not())
+        .stdout(contains("\u{1b}[1m"));
+}
+
+#[test]
+fn stem_language_matches_other_word_forms() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    fs::create_dir_all(&root).expect("create root");
+    fs::write(root.join("alt.txt"), "Die alten Häuser stehen noch").expect("write alt");
+    fs::write(root.join("garten.txt"), "Garten ohne Zaun").expect("write garten");
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success();
+
+    let mut plain_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut plain_cmd, &envs);
+    plain_cmd
+        .args(["search", "haus", "--no-snippet"])
+        .assert()
+        .success()
+        .stdout(contains("alt.txt").not());
+
+    let mut stemmed_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut stemmed_cmd, &envs);
+    stemmed_cmd
+        .args([
+            "search",
+            "haus",
+            "--stem-language",
+            "german",
+            "--no-snippet",
+        ])
+        .assert()
+        .success()
+        .stdout(contains("alt.txt"))
+        .stdout(contains("garten.txt").not());
+}
+
+#[test]
+fn sidecar_text_is_indexed_with_its_file() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    fs::create_dir_all(&root).expect("create root");
+    fs::write(root.join("beach.png"), b"\x89PNG\0\0pixels").expect("write image");
+    fs::write(
+        root.join("beach.png.meta.json"),
+        r#"{"description": "Sunset over Lisbon harbour"}"#,
+    )
+    .expect("write sidecar");
+    fs::write(root.join("notes.txt"), "packing list").expect("write notes");
+    fs::write(root.join("notes.txt.meta.json"), r#"{"tags": ["holiday"]}"#).expect("write sidecar");
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success()
+        .stdout(contains("Sidecars read  : 2"));
+
+    let mut image_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut image_cmd, &envs);
+    image_cmd
+        .args(["search", "lisbon", "--no-snippet"])
+        .assert()
+        .success()
+        .stdout(contains("beach.png\n"));
+
+    let mut appended_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut appended_cmd, &envs);
+    appended_cmd
+        .args(["search", "+packing +holiday", "--no-snippet"])
+        .assert()
+        .success()
+        .stdout(contains("notes.txt\n"));
+}
+
+#[test]
+fn init_print_only_creates_nothing() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+    let root = temp_dir.path().join("workspace");
+    create_sample_files(&root);
+
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--print-only", "--root", root.to_str().unwrap()])
+        .assert()
+        .success()
+        .stdout(contains("config.toml (not created yet)"))
+        .stdout(contains("index (not created yet)"));
+
+    let data_dir = PathBuf::from(&envs["XDG_DATA_HOME"]);
+    let config_dir = PathBuf::from(&envs["XDG_CONFIG_HOME"]);
+    assert!(!config_dir.join("vaultsearch").exists());
+    assert!(!data_dir.join("vaultsearch").exists());
+}
+
+#[test]
+fn explicit_thread_counts_index_every_file() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+    let root = temp_dir.path().join("workspace");
+    create_sample_files(&root);
+    for i in 0..20 {
+        fs::write(
+            root.join(format!("extra{i}.txt")),
+            format!("batch file {i}"),
+        )
+        .expect("write");
+    }
+
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success();
+    fs::write(root.join("late.txt"), "added after init").expect("write late.txt");
+
+    let mut index_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut index_cmd, &envs);
+    index_cmd
+        .args(["index", "--read-threads", "3", "--index-threads", "1"])
+        .assert()
+        .success()
+        .stdout(contains("Threads        : 3 read, 1 index"))
+        .stdout(contains("Added files    : 1"))
+        .stdout(contains("Unchanged files: 22"));
+
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    search_cmd
+        .args(["search", "batch", "--no-snippet", "--limit", "50"])
+        .assert()
+        .success()
+        .stdout(contains("extra19.txt"));
+}
+
+#[test]
+fn exclude_path_hides_matches_and_still_fills_limit() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    let archive = root.join("archive");
+    fs::create_dir_all(&archive).expect("create archive");
+    for name in ["2019.txt", "2020.txt", "2021.txt"] {
+        fs::write(archive.join(name), "ledger ledger ledger totals").expect("write archive");
+    }
+    fs::write(root.join("current.txt"), "ledger for this year, with notes").expect("write current");
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success();
+
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    search_cmd
+        .args([
+            "search",
+            "ledger",
+            "--limit",
+            "1",
+            "--exclude-path",
+            "**/archive/**",
+            "--no-snippet",
+        ])
+        .assert()
+        .success()
+        .stdout(contains("current.txt"))
+        .stdout(contains("archive").not());
+}
+
+#[test]
+fn limit_per_dir_promotes_results_from_other_directories() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    let busy = root.join("busy");
+    fs::create_dir_all(&busy).expect("create busy");
+    for name in ["a.txt", "b.txt", "c.txt"] {
+        fs::write(busy.join(name), "ledger ledger ledger totals").expect("write busy");
+    }
+    fs::write(root.join("quiet.txt"), "ledger for this year, with notes").expect("write quiet");
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success();
+
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    let output = search_cmd
+        .args([
+            "search",
+            "ledger",
+            "--limit",
+            "2",
+            "--limit-per-dir",
+            "1",
+            "--format",
+            "null",
+        ])
+        .output()
+        .expect("run search");
+    assert!(output.status.success());
+    let paths: Vec<String> = output
+        .stdout
+        .split(|b| *b == 0)
+        .filter(|p| !p.is_empty())
+        .map(|p| String::from_utf8_lossy(p).to_string())
+        .collect();
+    assert_eq!(paths.len(), 2, "{paths:?}");
+    assert!(paths[0].contains("busy"), "{paths:?}");
+    assert!(paths[1].ends_with("quiet.txt"), "{paths:?}");
+}
+
+#[test]
+fn line_filters_and_sort_use_indexed_line_counts() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    fs::create_dir_all(&root).expect("create root");
+    fs::write(root.join("one.rs"), "fn main() {}\n").expect("write one");
+    fs::write(root.join("five.rs"), "fn main() {\n\n\n\n}\n").expect("write five");
+    fs::write(root.join("forty.rs"), "fn main() {\n".repeat(40)).expect("write forty");
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success();
+
+    let mut filter_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut filter_cmd, &envs);
+    filter_cmd
+        .args([
+            "search",
+            "main",
+            "--min-lines",
+            "2",
+            "--max-lines",
+            "10",
+            "--no-snippet",
+        ])
+        .assert()
+        .success()
+        .stdout(contains("five.rs"))
+        .stdout(contains("one.rs").not())
+        .stdout(contains("forty.rs").not());
+
+    let mut sort_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut sort_cmd, &envs);
+    let output = sort_cmd
+        .args(["search", "main", "--sort", "lines", "--format", "null"])
+        .output()
+        .expect("run search");
+    assert!(output.status.success());
+    let names: Vec<String> = output
+        .stdout
+        .split(|b| *b == 0)
+        .filter(|p| !p.is_empty())
+        .map(|p| {
+            String::from_utf8_lossy(p)
+                .rsplit('/')
+                .next()
+                .unwrap()
+                .to_string()
+        })
+        .collect();
+    assert_eq!(names, ["forty.rs", "five.rs", "one.rs"]);
+}
+
+#[test]
+fn modified_within_keeps_only_recent_files() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    fs::create_dir_all(&root).expect("create root");
+    fs::write(root.join("fresh.txt"), "budget draft").expect("write fresh");
+    let stale = root.join("stale.txt");
+    fs::write(&stale, "budget final").expect("write stale");
+    let ten_days_ago = std::time::SystemTime::now() - std::time::Duration::from_secs(10 * 86_400);
+    fs::File::options()
+        .write(true)
+        .open(&stale)
+        .and_then(|file| file.set_modified(ten_days_ago))
+        .expect("backdate stale");
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success();
+
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    search_cmd
+        .args([
+            "search",
+            "budget",
+            "--modified-within",
+            "7d",
+            "--no-snippet",
+        ])
+        .assert()
+        .success()
+        .stdout(contains("fresh.txt"))
+        .stdout(contains("stale.txt").not());
+
+    let mut invalid_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut invalid_cmd, &envs);
+    invalid_cmd
+        .args(["search", "budget", "--modified-within", "7"])
+        .assert()
+        .failure()
+        .stderr(contains("invalid duration"));
+}
+
+#[test]
+fn init_index_dir_stores_index_in_explicit_location() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    fs::create_dir_all(&root).expect("create root");
+    fs::write(root.join("notes.txt"), "scratch disk index").expect("write notes");
+    let index_dir = temp_dir.path().join("scratch").join("index");
+
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args([
+            "init",
+            "--root",
+            root.to_str().unwrap(),
+            "--index-dir",
+            index_dir.to_str().unwrap(),
+        ])
+        .assert()
+        .success()
+        .stdout(contains(index_dir.to_str().unwrap()));
+    assert!(index_dir.join("meta.json").is_file());
+
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    search_cmd
+        .args(["search", "scratch", "--no-snippet"])
+        .assert()
+        .success()
+        .stdout(contains("notes.txt"));
+
+    let occupied = temp_dir.path().join("occupied");
+    fs::create_dir_all(&occupied).expect("create occupied");
+    fs::write(occupied.join("keep.txt"), "not an index").expect("write keep");
+    let mut occupied_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut occupied_cmd, &envs);
+    occupied_cmd
+        .args([
+            "init",
+            "--root",
+            root.to_str().unwrap(),
+            "--index-dir",
+            occupied.to_str().unwrap(),
+            "--force",
+        ])
+        .assert()
+        .failure()
+        .stderr(contains("is not empty"));
+    assert!(occupied.join("keep.txt").is_file());
+}
+
+#[test]
+fn synonyms_expand_configured_words_only_when_requested() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    fs::create_dir_all(&root).expect("create root");
+    fs::write(root.join("garage.txt"), "the automobile needs new tyres").expect("write garage");
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success();
+
+    let project = temp_dir.path().join("project");
+    fs::create_dir_all(&project).expect("create project dir");
+    fs::write(
+        project.join(".vaultsearch.toml"),
+        "[synonyms]\ncar = [\"automobile\", \"vehicle\"]\n",
+    )
+    .expect("write project config");
+
+    let mut plain_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut plain_cmd, &envs);
+    plain_cmd
+        .current_dir(&project)
+        .args(["search", "car", "--no-snippet"])
+        .assert()
+        .success()
+        .stdout(contains("garage.txt").not());
+
+    let mut synonyms_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut synonyms_cmd, &envs);
+    synonyms_cmd
+        .current_dir(&project)
+        .args(["search", "car", "--synonyms", "--no-snippet"])
+        .assert()
+        .success()
+        .stdout(contains("garage.txt"));
+}
+
+#[test]
+fn highlight_only_prints_numbered_matching_lines() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    fs::create_dir_all(&root).expect("create root");
+    fs::write(
+        root.join("notes.txt"),
+        "intro\nthe ledger total\nunrelated\nLedger again\nledger three\n",
+    )
+    .expect("write notes");
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success();
+
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    search_cmd
+        .args([
+            "search",
+            "ledger",
+            "--highlight-only",
+            "--max-matches-per-file",
+            "2",
+            "--color",
+            "always",
+        ])
+        .assert()
+        .success()
+        .stdout(contains("   2: the \x1b[1mledger\x1b[0m total"))
+        .stdout(contains("   4: \x1b[1mLedger\x1b[0m again"))
+        .stdout(contains("1 more matching line(s)"))
+        .stdout(contains("intro").not())
+        .stdout(contains("unrelated").not());
+}
+
+#[test]
+fn git_tracked_only_indexes_tracked_files_and_falls_back_outside_repos() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    fs::create_dir_all(&root).expect("create root");
+    fs::write(root.join("tracked.txt"), "quarterly plan").expect("write tracked");
+    fs::write(root.join("scratch.txt"), "quarterly scratch").expect("write scratch");
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success();
+
+    // Outside a repository the flag warns and indexes everything.
+    let mut fallback_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut fallback_cmd, &envs);
+    fallback_cmd
+        .args(["index", "--git-tracked-only"])
+        .assert()
+        .success()
+        .stderr(contains("indexing every file under the root instead"));
+
+    for args in [&["init", "-q"][..], &["add", "tracked.txt"][..]] {
+        let status = std::process::Command::new("git")
+            .args(args)
+            .current_dir(&root)
+            .status()
+            .expect("run git");
+        assert!(status.success());
+    }
+
+    let mut index_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut index_cmd, &envs);
+    index_cmd
+        .args(["index", "--git-tracked-only"])
+        .assert()
+        .success()
+        .stdout(contains("Git-tracked    : 1 files"));
+
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    search_cmd
+        .args(["search", "quarterly", "--no-snippet"])
+        .assert()
+        .success()
+        .stdout(contains("tracked.txt"))
+        .stdout(contains("scratch.txt").not());
+}
+
+#[test]
+fn search_field_flags_restrict_fields_and_warn_about_unknown_ones() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    fs::create_dir_all(&root).expect("create root");
+    fs::write(root.join("budget.txt"), "numbers for next year").expect("write budget");
+    fs::write(root.join("notes.txt"), "the budget is tight").expect("write notes");
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success();
+
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    search_cmd
+        .args([
+            "search",
+            "budget",
+            "--search-field",
+            "path",
+            "--field-boost",
+            "title=2",
+            "--no-snippet",
+        ])
+        .assert()
+        .success()
+        .stdout(contains("budget.txt"))
+        .stdout(contains("notes.txt").not())
+        .stderr(contains("Ignoring field boost `title`"));
+}
+
+#[test]
+fn relevance_profile_applies_its_search_fields() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    fs::create_dir_all(&root).expect("create root");
+    fs::write(root.join("budget.txt"), "numbers for next year").expect("write budget");
+    fs::write(root.join("notes.txt"), "the budget is tight").expect("write notes");
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success();
+
+    let config_path = PathBuf::from(&envs["XDG_CONFIG_HOME"])
+        .join("vaultsearch")
+        .join("config.toml");
+    let config = fs::read_to_string(&config_path).expect("read config");
+    fs::write(
+        &config_path,
+        format!("{config}\n[relevance_profiles.filenames]\nsearch_fields = [\"path\"]\n"),
+    )
+    .expect("write config");
+
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    search_cmd
+        .args([
+            "search",
+            "budget",
+            "--relevance",
+            "filenames",
+            "--no-snippet",
+        ])
+        .assert()
+        .success()
+        .stdout(contains("budget.txt"))
+        .stdout(contains("notes.txt").not());
+
+    let mut missing_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut missing_cmd, &envs);
+    missing_cmd
+        .args(["search", "budget", "--relevance", "code"])
+        .assert()
+        .code(3)
+        .stderr(contains("configured profiles: filenames"));
+}
+
+#[test]
+fn index_binary_paths_makes_binary_files_findable_by_name() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    fs::create_dir_all(&root).expect("create root");
+    fs::write(root.join("photo_2023.jpg"), [0xFF, 0xD8, 0xFF, 0x00]).expect("write photo");
+    fs::write(root.join("firmware.txt"), b"\x00\x01firmware\x00").expect("write blob");
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success();
+
+    let mut index_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut index_cmd, &envs);
+    index_cmd
+        .args(["index", "--index-binary-paths"])
+        .assert()
+        .success()
+        .stdout(contains("Paths only     : 2"));
+
+    for (query, name) in [
+        ("photo_2023", "photo_2023.jpg"),
+        ("firmware", "firmware.txt"),
+    ] {
+        let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+        apply_env(&mut search_cmd, &envs);
+        search_cmd
+            .args(["search", query, "--no-snippet"])
+            .assert()
+            .success()
+            .stdout(contains(name));
+    }
+
+    // A run without the flag drops them again.
+    let mut plain_index_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut plain_index_cmd, &envs);
+    plain_index_cmd.arg("index").assert().success();
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    search_cmd
+        .args(["search", "firmware", "--no-snippet"])
+        .assert()
+        .success()
+        .stdout(contains("firmware.txt").not());
+}
+
+#[test]
+fn next_and_prev_page_through_the_last_search() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let mut prev_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut prev_cmd, &envs);
+    prev_cmd
+        .arg("prev")
+        .assert()
+        .failure()
+        .stderr(contains("No previous search"));
+
+    let root = temp_dir.path().join("workspace");
+    fs::create_dir_all(&root).expect("create root");
+    fs::write(root.join("a.txt"), "ledger ledger ledger note").expect("write a.txt");
+    fs::write(root.join("b.txt"), "ledger ledger note note").expect("write b.txt");
+    fs::write(root.join("c.txt"), "ledger note note note").expect("write c.txt");
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success();
+
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    search_cmd
+        .args(["search", "ledger", "--limit", "1"])
+        .assert()
+        .success()
+        .stdout(contains(" 1. [score:").and(contains("a.txt")));
+
+    let mut next_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut next_cmd, &envs);
+    next_cmd
+        .arg("next")
+        .assert()
+        .success()
+        .stdout(contains(" 2. [score:").and(contains("b.txt")))
+        .stdout(contains("a.txt").not());
+
+    let mut next_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut next_cmd, &envs);
+    next_cmd
+        .arg("next")
+        .assert()
+        .success()
+        .stdout(contains(" 3. [score:").and(contains("c.txt")));
+
+    let mut prev_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut prev_cmd, &envs);
+    prev_cmd
+        .arg("prev")
+        .assert()
+        .success()
+        .stdout(contains(" 2. [score:").and(contains("b.txt")));
+
+    // A new explicit search starts again from the first page.
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    search_cmd
+        .args(["search", "note", "--limit", "2"])
+        .assert()
+        .success();
+    let mut next_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut next_cmd, &envs);
+    next_cmd
+        .arg("next")
+        .assert()
+        .success()
+        .stdout(contains(" 3. [score:"));
+
+    // That page was short, so there is nothing further to move on to.
+    let mut next_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut next_cmd, &envs);
+    next_cmd
+        .arg("next")
+        .assert()
+        .success()
+        .stderr(contains("Already on the last page."))
+        .stdout(contains(" 3. [score:"));
+
+    // Later pages are normalized against rank 1, not against their own best hit.
+    let mut normalized_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut normalized_cmd, &envs);
+    let output = normalized_cmd
+        .args(["search", "ledger", "--limit", "1", "--offset", "1"])
+        .args(["--score-normalize", "--format", "json"])
+        .output()
+        .expect("run search");
+    assert!(output.status.success());
+    let results: serde_json::Value =
+        serde_json::from_slice(&output.stdout).expect("parse json results");
+    assert_eq!(results[0]["rank"], 2);
+    assert_eq!(results[0]["relative_path"], "b.txt");
+    let score = results[0]["score"].as_f64().expect("score");
+    assert!(score > 0.0 && score < 1.0, "{score}");
+}
+
+#[test]
+fn search_rejects_limits_past_the_result_window() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    create_sample_files(&root);
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success();
+
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    search_cmd
+        .args(["search", "rust", "--limit", "100000000000"])
+        .assert()
+        .failure()
+        .code(1)
+        .stderr(contains("--limit must be at most 10000"));
+
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    search_cmd
+        .args(["search", "rust", "--offset", "18446744073709551000"])
+        .assert()
+        .failure()
+        .code(1)
+        .stderr(contains("--offset plus --limit must be at most 10000"));
+
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    search_cmd
+        .args(["search", "rust", "--limit", "10000"])
+        .assert()
+        .success()
+        .stdout(contains("notes.txt"));
+}
+
+#[test]
+fn stop_words_file_drops_words_and_config_changes_require_rebuild() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    fs::create_dir_all(&root).expect("create root");
+    fs::write(root.join("notes.txt"), "system ledger").expect("write notes");
+    let stop_words = temp_dir.path().join("stop.txt");
+    fs::write(&stop_words, "# noise in this corpus\nSystem\n").expect("write stop words");
+
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args([
+            "init",
+            "--root",
+            root.to_str().unwrap(),
+            "--stop-words-file",
+        ])
+        .arg(&stop_words)
+        .assert()
+        .success()
+        .stdout(contains("Stop words     : 1 from"));
+
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    search_cmd
+        .args(["search", "system"])
+        .assert()
+        .success()
+        .stdout(contains("No results found"));
+
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    search_cmd
+        .args(["search", "ledger"])
+        .assert()
+        .success()
+        .stdout(contains("notes.txt"));
+
+    // Editing the list without rebuilding is caught when the index is opened.
+    let config_path = PathBuf::from(&envs["XDG_CONFIG_HOME"])
+        .join("vaultsearch")
+        .join("config.toml");
+    let config = fs::read_to_string(&config_path).expect("read config");
+    let stop_words_line = config
+        .lines()
+        .find(|line| line.starts_with("stop_words"))
+        .expect("stop_words saved to config")
+        .to_string();
+    fs::write(
+        &config_path,
+        config.replace(&stop_words_line, r#"stop_words = ["ledger"]"#),
+    )
+    .expect("write config");
+
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    search_cmd
+        .args(["search", "ledger"])
+        .assert()
+        .code(3)
+        .stderr(contains("different stop words"));
+
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap(), "--force"])
+        .assert()
+        .success();
+
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    search_cmd
+        .args(["search", "system"])
+        .assert()
+        .success()
+        .stdout(contains("notes.txt"));
+}
+
+#[test]
+fn check_flag_reports_corrupt_index_with_exit_code() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    create_sample_files(&root);
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success();
+
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    search_cmd
+        .args(["search", "rust", "--check"])
+        .assert()
+        .success()
+        .stderr(contains("Index check: ok (2 documents"))
+        .stdout(contains("notes.txt"));
+
+    // Overwrite the segment term dictionaries, as an interrupted write might leave them.
+    let index_dir = PathBuf::from(&envs["XDG_DATA_HOME"])
+        .join("vaultsearch")
+        .join("index");
+    for entry in fs::read_dir(&index_dir).expect("list index dir") {
+        let path = entry.expect("dir entry").path();
+        if path.extension().is_some_and(|ext| ext == "term") {
+            fs::write(&path, "garbage").expect("corrupt segment file");
+        }
+    }
+
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    search_cmd
+        .args(["search", "rust", "--check"])
+        .assert()
+        .code(6)
+        .stderr(contains("appears corrupt"))
+        .stderr(contains("vaultsearch init --force"));
+
+    let mut index_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut index_cmd, &envs);
+    index_cmd.args(["index", "--check"]).assert().code(6);
+}
+
+#[test]
+fn whole_word_matches_only_complete_words() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    fs::create_dir_all(&root).expect("create root");
+    fs::write(root.join("pets.txt"), "a cat sat").expect("write pets.txt");
+    fs::write(root.join("words.txt"), "category and concatenate").expect("write words.txt");
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success();
+
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    search_cmd
+        .args(["search", "cat", "--whole-word", "--no-snippet"])
+        .assert()
+        .success()
+        .stdout(contains("pets.txt"))
+        .stdout(contains("words.txt").not());
+
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    search_cmd
+        .args(["search", "\"a ca\"*", "--match-whole-word"])
+        .assert()
+        .code(5)
+        .stderr(contains("match partial words"));
+
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    search_cmd
+        .args(["search", "cat*", "--whole-word", "--wildcard"])
+        .assert()
+        .code(2);
+}
+
+#[test]
+fn config_flag_and_env_choose_the_config_file() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    create_sample_files(&root);
+    let alt_config = temp_dir.path().join("alt.toml");
+
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap(), "--config"])
+        .arg(&alt_config)
+        .assert()
+        .success();
+    assert!(alt_config.is_file());
+
+    // The default location was never written, so a plain search has no config.
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    search_cmd
+        .args(["search", "rust"])
+        .assert()
+        .code(3)
+        .stderr(contains("No config file at"))
+        .stderr(contains("VAULTSEARCH_CONFIG"));
+
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    search_cmd
+        .env("VAULTSEARCH_CONFIG", &alt_config)
+        .args(["search", "rust"])
+        .assert()
+        .success()
+        .stdout(contains("notes.txt"));
+
+    // `--config` wins over the environment variable.
+    let mut index_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut index_cmd, &envs);
+    index_cmd
+        .env("VAULTSEARCH_CONFIG", temp_dir.path().join("missing.toml"))
+        .arg("index")
+        .arg("--config")
+        .arg(&alt_config)
+        .assert()
+        .success()
+        .stdout(contains("alt.toml (from --config)"));
+
+    // Paging searches the config the last search used, and refuses a different one.
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    search_cmd
+        .args(["search", "rust", "--limit", "1", "--config"])
+        .arg(&alt_config)
+        .assert()
+        .success();
+    let mut prev_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut prev_cmd, &envs);
+    prev_cmd
+        .arg("prev")
+        .assert()
+        .success()
+        .stdout(contains(" 1. [score:"));
+    let mut next_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut next_cmd, &envs);
+    next_cmd
+        .args(["next", "--config"])
+        .arg(temp_dir.path().join("missing.toml"))
+        .assert()
+        .code(3)
+        .stderr(contains("The last search used config"));
+}
+
+#[test]
+fn tags_counts_extensions_and_field_terms_of_live_documents() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    fs::create_dir_all(&root).expect("create root");
+    for name in ["a.md", "b.md", "c.MD", "d.txt"] {
+        fs::write(root.join(name), "alpha beta").expect("write file");
+    }
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success();
+
+    // Deleted files must not be counted even before segments are merged.
+    fs::remove_file(root.join("a.md")).expect("remove a.md");
+    let mut index_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut index_cmd, &envs);
+    index_cmd.arg("index").assert().success();
+
+    let mut tags_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut tags_cmd, &envs);
+    tags_cmd
+        .arg("tags")
+        .assert()
+        .success()
+        .stdout(contains("EXTENSION  FILES"))
+        .stdout(contains("md             2"))
+        .stdout(contains("txt            1"))
+        .stdout(contains("2 distinct values"));
+
+    let mut tags_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut tags_cmd, &envs);
+    tags_cmd
+        .args(["tags", "--by", "contents", "--limit", "1"])
+        .assert()
+        .success()
+        .stdout(contains("alpha         3"))
+        .stdout(contains("Showing 1 of 2 distinct values"));
+
+    let mut tags_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut tags_cmd, &envs);
+    tags_cmd
+        .args(["tags", "--by", "size"])
+        .assert()
+        .failure()
+        .stderr(contains("not an indexed text field"));
+}
+
+#[test]
+fn snapshot_search_queries_a_private_copy_and_removes_it() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    create_sample_files(&root);
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success();
+
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    search_cmd
+        .args(["search", "rust", "--snapshot"])
+        .assert()
+        .success()
+        .stdout(contains("notes.txt"));
+
+    let data_dir = PathBuf::from(&envs["XDG_DATA_HOME"]).join("vaultsearch");
+    let leftovers: Vec<_> = fs::read_dir(&data_dir)
+        .expect("list data dir")
+        .map(|entry| entry.expect("dir entry").file_name())
+        .filter(|name| name.to_string_lossy().contains("snapshot"))
+        .collect();
+    assert!(leftovers.is_empty(), "snapshot left behind: {leftovers:?}");
+}
+
+#[test]
+fn color_flag_controls_snippet_highlighting() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    create_sample_files(&root);
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success();
+
+    // Output to a pipe is not a terminal, so `auto` leaves it plain.
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    search_cmd
+        .args(["search", "rust"])
+        .assert()
+        .success()
+        .stdout(contains("notes.txt"))
+        .stdout(contains("\x1b[").not());
+
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    search_cmd
+        .env("NO_COLOR", "1")
+        .args(["search", "rust", "--color", "always"])
+        .assert()
+        .success()
+        .stdout(contains("\x1b[1m"));
+}
+
+#[test]
+fn now_env_var_fixes_last_indexed() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    create_sample_files(&root);
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .env("VAULTSEARCH_NOW", "2024-01-02T03:04:05Z")
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success();
+
+    let config_path = PathBuf::from(&envs["XDG_CONFIG_HOME"])
+        .join("vaultsearch")
+        .join("config.toml");
+    let config = fs::read_to_string(&config_path).expect("read config");
+    assert!(
+        config.contains(r#"last_indexed = "2024-01-02T03:04:05+00:00""#),
+        "{config}"
+    );
+
+    let mut index_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut index_cmd, &envs);
+    index_cmd
+        .env("VAULTSEARCH_NOW", "yesterday")
+        .arg("index")
+        .assert()
+        .code(3)
+        .stderr(contains("VAULTSEARCH_NOW is not an RFC 3339 timestamp"));
+}
+
+#[test]
+fn output_flag_writes_results_to_a_file_without_color() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    create_sample_files(&root);
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success();
+
+    let text_path = temp_dir
+        .path()
+        .join("exports")
+        .join("nested")
+        .join("rust.txt");
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    search_cmd
+        .args(["search", "rust", "--color", "always", "--output"])
+        .arg(&text_path)
+        .assert()
+        .success()
+        .stdout("")
+        .stderr(contains("Wrote 1 result(s) to"));
+    let text = fs::read_to_string(&text_path).expect("read text export");
+    assert!(text.contains("notes.txt"), "{text}");
+    assert!(!text.contains('\x1b'), "{text}");
+
+    let json_path = temp_dir.path().join("rust.json");
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    search_cmd
+        .args(["search", "rust", "--format", "json", "--output"])
+        .arg(&json_path)
+        .assert()
+        .success();
+    let json: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&json_path).expect("read json export"))
+            .expect("valid JSON");
+    assert_eq!(json.as_array().map(Vec::len), Some(1));
+}
+
+#[test]
+fn forget_removes_documents_matching_a_glob() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    fs::create_dir_all(root.join("archive").join("2019")).expect("create archive");
+    fs::write(root.join("archive").join("old.txt"), "quarterly ledger").expect("write file");
+    fs::write(
+        root.join("archive").join("2019").join("older.txt"),
+        "annual ledger",
+    )
+    .expect("write file");
+    fs::write(root.join("current.txt"), "monthly ledger").expect("write file");
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success();
+
+    let mut forget_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut forget_cmd, &envs);
+    forget_cmd
+        .args(["forget", "archive/**"])
+        .assert()
+        .success()
+        .stdout(contains("Forgot 2 document(s) matching `archive/**`."));
+
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    search_cmd
+        .args(["search", "ledger"])
+        .assert()
+        .success()
+        .stdout(contains("current.txt"))
+        .stdout(contains("old.txt").not());
+
+    let mut forget_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut forget_cmd, &envs);
+    forget_cmd
+        .args(["forget", "**"])
+        .assert()
+        .failure()
+        .stderr(contains("matches all 1 indexed documents"));
+
+    // Forgotten files that still exist are indexed again by the next run.
+    let mut index_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut index_cmd, &envs);
+    index_cmd
+        .arg("index")
+        .assert()
+        .success()
+        .stdout(contains("Added files    : 2"));
+}
+
+#[test]
+fn max_token_length_skips_long_words_and_is_saved_to_config() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    fs::create_dir_all(&root).expect("create root");
+    fs::write(root.join("blob.txt"), "short aGVsbG8gd29ybGQ").expect("write file");
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args([
+            "init",
+            "--root",
+            root.to_str().unwrap(),
+            "--max-token-length",
+            "8",
+        ])
+        .assert()
+        .success()
+        .stdout(contains("Max token size : 8 bytes"));
+
+    let config_path = PathBuf::from(&envs["XDG_CONFIG_HOME"])
+        .join("vaultsearch")
+        .join("config.toml");
+    let config = fs::read_to_string(&config_path).expect("read config");
+    assert!(config.contains("max_token_length = 8"), "{config}");
+
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    search_cmd
+        .args(["search", "short"])
+        .assert()
+        .success()
+        .stdout(contains("blob.txt"));
+
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    search_cmd
+        .args(["search", "aGVsbG8gd29ybGQ"])
+        .assert()
+        .success()
+        .stdout(contains("No results found"));
+}
+
+#[test]
+fn index_profile_stats_prints_phase_timings() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    create_sample_files(&root);
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success()
+        .stdout(contains("Profile (wall-clock)").not());
+
+    fs::write(root.join("extra.txt"), "one more file").expect("write file");
+    let mut index_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut index_cmd, &envs);
+    index_cmd
+        .args(["index", "--profile-stats", "--read-threads", "2"])
+        .assert()
+        .success()
+        .stdout(contains("Profile (wall-clock):"))
+        .stdout(contains("- Scan"))
+        .stdout(contains("(summed over 2 read threads)"))
+        .stdout(contains("- Tokenize and commit"))
+        .stdout(contains("- Total"));
+}
+
+#[test]
+fn recency_boost_ranks_recently_modified_files_first() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    fs::create_dir_all(&root).expect("create root");
+    // The older file mentions the term twice, so it wins on relevance alone.
+    fs::write(root.join("old.txt"), "roadmap roadmap draft").expect("write file");
+    fs::write(root.join("new.txt"), "roadmap draft notes").expect("write file");
+    let a_year_ago = std::time::SystemTime::now() - std::time::Duration::from_secs(365 * 86_400);
+    fs::File::options()
+        .write(true)
+        .open(root.join("old.txt"))
+        .and_then(|file| file.set_modified(a_year_ago))
+        .expect("backdate old.txt");
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success();
+
+    let first_hit = |extra: &[&str]| {
+        let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+        apply_env(&mut search_cmd, &envs);
+        let output = search_cmd
+            .args(["search", "roadmap", "--no-snippet"])
+            .args(extra)
+            .output()
+            .expect("run search");
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout).expect("utf8 stdout");
+        stdout
+            .lines()
+            .find(|line| line.starts_with(" 1."))
+            .map(str::to_string)
+            .expect("first result")
+    };
+    assert!(first_hit(&[]).contains("old.txt"));
+    assert!(first_hit(&["--recency-boost"]).contains("new.txt"));
+    // With an enormous half-life the year-old file barely decays.
+    assert!(first_hit(&["--recency-boost", "--recency-half-life", "100000"]).contains("old.txt"));
+}
+
+#[test]
+fn as_of_generation_filters_to_documents_from_earlier_runs() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    fs::create_dir_all(&root).expect("create root");
+    fs::write(root.join("first.txt"), "budget review").expect("write file");
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success()
+        .stdout(contains("Generation    : 1"));
+
+    fs::write(root.join("second.txt"), "budget forecast").expect("write file");
+    let mut index_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut index_cmd, &envs);
+    index_cmd
+        .arg("index")
+        .assert()
+        .success()
+        .stdout(contains("Generation    : 2"));
+
+    let config_path = PathBuf::from(&envs["XDG_CONFIG_HOME"])
+        .join("vaultsearch")
+        .join("config.toml");
+    let config = fs::read_to_string(&config_path).expect("read config");
+    assert!(config.contains("index_generation = 2"), "{config}");
+
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    search_cmd
+        .args(["search", "budget", "--as-of-generation", "1"])
+        .assert()
+        .success()
+        .stdout(contains("first.txt"))
+        .stdout(contains("second.txt").not());
+
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    search_cmd
+        .args(["search", "budget", "--as-of-generation", "2"])
+        .assert()
+        .success()
+        .stdout(contains("first.txt"))
+        .stdout(contains("second.txt"));
+}
+
+#[test]
+fn ignore_file_skips_matching_paths_during_indexing() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    fs::create_dir_all(root.join("drafts").join("nested")).expect("create drafts");
+    fs::create_dir_all(root.join("notes")).expect("create notes");
+    fs::write(root.join("notes").join("keep.txt"), "harbor beacon").expect("write file");
+    fs::write(root.join("notes").join("scratch.tmp"), "harbor scratch").expect("write file");
+    fs::write(
+        root.join("drafts").join("nested").join("draft.txt"),
+        "harbor draft",
+    )
+    .expect("write file");
+    let ignore_path = temp_dir.path().join("vault.ignore");
+    fs::write(&ignore_path, "# local rules\n*.tmp\ndrafts/\n").expect("write ignore file");
+
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success();
+
+    let mut index_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut index_cmd, &envs);
+    index_cmd
+        .args(["index", "--ignore-file", ignore_path.to_str().unwrap()])
+        .assert()
+        .success()
+        .stdout(contains("Ignored paths  : 2 (--ignore-file)"));
+
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    search_cmd
+        .args(["search", "harbor"])
+        .assert()
+        .success()
+        .stdout(contains("keep.txt"))
+        .stdout(contains("scratch.tmp").not())
+        .stdout(contains("draft.txt").not());
+
+    fs::write(&ignore_path, "!keep.txt\n").expect("write ignore file");
+    let mut index_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut index_cmd, &envs);
+    index_cmd
+        .args(["index", "--ignore-file", ignore_path.to_str().unwrap()])
+        .assert()
+        .failure()
+        .stderr(contains("negated patterns"));
+}
+
+#[test]
+fn json_results_include_match_spans_into_indexed_contents() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    fs::create_dir_all(&root).expect("create root");
+    let contents = "alpha beta\nthe Beta gamma beta\n";
+    fs::write(root.join("spans.txt"), contents).expect("write file");
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success();
+
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    let output = search_cmd
+        .args(["search", "beta", "--format", "json"])
+        .output()
+        .expect("run search");
+    assert!(output.status.success());
+    let results: serde_json::Value =
+        serde_json::from_slice(&output.stdout).expect("parse json results");
+    let spans = results[0]["match_spans"].as_array().expect("match_spans");
+    let words: Vec<&str> = spans
+        .iter()
+        .map(|span| {
+            let start = span[0].as_u64().unwrap() as usize;
+            let end = span[1].as_u64().unwrap() as usize;
+            &contents[start..end]
+        })
+        .collect();
+    assert_eq!(words, ["beta", "Beta", "beta"]);
+}
+
+#[test]
+fn min_term_length_drops_short_terms_and_rejects_empty_queries() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    fs::create_dir_all(&root).expect("create root");
+    fs::write(root.join("parser.rs"), "fn parse() -> io::Result<()>").expect("write file");
+    fs::write(root.join("other.rs"), "use std::io;").expect("write file");
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success();
+
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    search_cmd
+        .args([
+            "search",
+            "io parse",
+            "--min-term-length",
+            "3",
+            "--no-snippet",
+        ])
+        .assert()
+        .success()
+        .stderr(contains(
+            "Dropped query term(s) shorter than 3 characters: io",
+        ))
+        .stdout(contains("parser.rs"))
+        .stdout(contains("other.rs").not());
+
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    search_cmd
+        .args(["search", "io fn", "--min-term-length", "3"])
+        .assert()
+        .code(5)
+        .stderr(contains("Query too short"));
+}
+
+#[test]
+fn field_routing_indexes_code_extensions_into_the_code_field() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    fs::create_dir_all(&root).expect("create root");
+    fs::write(root.join("loader.rs"), "fn load(data: &[u8]) {}").expect("write file");
+    fs::write(root.join("notes.md"), "The data loader is slow.").expect("write file");
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success();
+
+    let config_path = PathBuf::from(&envs["XDG_CONFIG_HOME"])
+        .join("vaultsearch")
+        .join("config.toml");
+    let mut config = fs::read_to_string(&config_path).expect("read config");
+    config.push_str("stop_words = [\"data\"]\n\n[field_routing]\ncode = [\".RS\"]\n");
+    fs::write(&config_path, config).expect("write config");
+
+    let mut index_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut index_cmd, &envs);
+    index_cmd
+        .arg("index")
+        .assert()
+        .code(3)
+        .stderr(contains("different `[field_routing]`"));
+
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap(), "--force"])
+        .assert()
+        .success()
+        .stdout(contains("Routed to code : 1"));
+
+    // `data` is a stop word for prose only, and both fields are searched by default.
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    search_cmd
+        .args(["search", "data"])
+        .assert()
+        .success()
+        .stdout(contains("loader.rs"))
+        .stdout(contains("notes.md").not());
+
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    search_cmd
+        .args(["search", "loader"])
+        .assert()
+        .success()
+        .stdout(contains("notes.md"));
+}
+
+#[test]
+fn last_errors_lists_files_skipped_by_the_last_run() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    fs::create_dir_all(&root).expect("create root");
+    fs::write(root.join("notes.txt"), "plain text").expect("write file");
+    fs::write(root.join("blob.txt"), [0u8, 159, 146, 150, 0, 1]).expect("write file");
+    fs::write(root.join("a.bin"), "x").expect("write file");
+    fs::write(root.join("b.bin"), "x").expect("write file");
+
+    let mut last_errors_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut last_errors_cmd, &envs);
+    last_errors_cmd
+        .arg("last-errors")
+        .assert()
+        .success()
+        .stdout(contains("No skipped files recorded yet"));
+
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success()
+        .stdout(contains("(3 recorded; see `vaultsearch last-errors`)"));
+
+    let mut last_errors_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut last_errors_cmd, &envs);
+    last_errors_cmd
+        .args(["last-errors", "--reason", "binary"])
+        .assert()
+        .success()
+        .stdout(contains(
+            "Files skipped by the last indexing run (binary): 1",
+        ))
+        .stdout(contains("[binary] Detected binary content:"))
+        .stdout(contains("blob.txt"))
+        .stdout(contains("a.bin").not());
+
+    let config_path = PathBuf::from(&envs["XDG_CONFIG_HOME"])
+        .join("vaultsearch")
+        .join("config.toml");
+    let mut config = fs::read_to_string(&config_path).expect("read config");
+    config.push_str("max_recorded_skips = 1\n");
+    fs::write(&config_path, config).expect("write config");
+
+    let mut index_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut index_cmd, &envs);
+    index_cmd
+        .arg("index")
+        .assert()
+        .success()
+        .stdout(contains("(1 recorded, 2 over max_recorded_skips;"));
+
+    let mut last_errors_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut last_errors_cmd, &envs);
+    last_errors_cmd
+        .arg("last-errors")
+        .assert()
+        .success()
+        .stdout(contains("Files skipped by the last indexing run: 1"));
+}
+
+#[test]
+fn search_index_merges_results_from_several_index_directories() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let mut index_dirs = Vec::new();
+    for (machine, contents, stop_words) in [
+        ("laptop", "harbor beacon", None),
+        ("desktop", "harbor harbor tide", None),
+        ("server", "harbor logs", Some("tide\n")),
+    ] {
+        let base = temp_dir.path().join(machine);
+        let root = base.join("files");
+        fs::create_dir_all(&root).expect("create root");
+        fs::write(root.join(format!("{machine}.txt")), contents).expect("write file");
+        let index_dir = base.join("index");
+
+        let env_dir = TempDir::new().expect("create env dir");
+        let envs = test_environment(&env_dir);
+        let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+        apply_env(&mut init_cmd, &envs);
+        init_cmd.args([
+            "init",
+            "--root",
+            root.to_str().unwrap(),
+            "--index-dir",
+            index_dir.to_str().unwrap(),
+        ]);
+        if let Some(words) = stop_words {
+            let file = base.join("stop.txt");
+            fs::write(&file, words).expect("write stop words");
+            init_cmd.args(["--stop-words-file", file.to_str().unwrap()]);
+        }
+        init_cmd.assert().success();
+        index_dirs.push(index_dir);
+    }
+
+    // A machine without any config of its own.
+    let envs = test_environment(&temp_dir);
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    search_cmd.args(["search", "harbor", "--format", "json"]);
+    for dir in &index_dirs {
+        search_cmd.args(["--index", dir.to_str().unwrap()]);
+    }
+    let output = search_cmd.output().expect("run search");
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Skipping index"), "{stderr}");
+    assert!(stderr.contains("custom stop_words"), "{stderr}");
+
+    let results: serde_json::Value =
+        serde_json::from_slice(&output.stdout).expect("parse json results");
+    let paths: Vec<&str> = results
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|hit| hit["relative_path"].as_str().unwrap())
+        .collect();
+    assert_eq!(paths.len(), 2);
+    assert!(paths[0].ends_with("desktop.txt"), "{paths:?}");
+    assert!(paths[1].ends_with("laptop.txt"), "{paths:?}");
+    assert!(PathBuf::from(paths[0]).is_absolute());
+    assert_eq!(
+        results[0]["index"].as_str(),
+        Some(index_dirs[1].to_str().unwrap())
+    );
+}
+
+/// Builds an index of `base/files` holding `notes.txt` with `contents`, storing paths
+/// relative to that root, and returns the index directory.
+fn build_relative_path_index(base: &Path, contents: &str) -> PathBuf {
+    let root = base.join("files");
+    fs::create_dir_all(&root).expect("create root");
+    fs::write(root.join("notes.txt"), contents).expect("write notes.txt");
+    let index_dir = base.join("index");
+
+    let env_dir = TempDir::new().expect("create env dir");
+    let envs = test_environment(&env_dir);
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap(), "--index-dir"])
+        .arg(&index_dir)
+        .assert()
+        .success();
+    let config_path = PathBuf::from(&envs["XDG_CONFIG_HOME"])
+        .join("vaultsearch")
+        .join("config.toml");
+    let config = fs::read_to_string(&config_path).expect("read config");
+    fs::write(
+        &config_path,
+        format!("store_relative_paths = true\n{config}"),
+    )
+    .expect("write config");
+    let mut index_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut index_cmd, &envs);
+    index_cmd.arg("index").assert().success();
+    index_dir
+}
+
+#[test]
+fn search_index_keeps_same_named_files_from_relative_path_indexes() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let index_a = build_relative_path_index(&temp_dir.path().join("a"), "invoice from alpha");
+    let index_b = build_relative_path_index(&temp_dir.path().join("b"), "invoice from beta");
+
+    let envs = test_environment(&temp_dir);
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    let output = search_cmd
+        .args(["search", "invoice", "--format", "json", "--index"])
+        .arg(&index_a)
+        .arg("--index")
+        .arg(&index_b)
+        .output()
+        .expect("run search");
+    assert!(output.status.success());
+    let results: serde_json::Value =
+        serde_json::from_slice(&output.stdout).expect("parse json results");
+    let mut indexes: Vec<&str> = results
+        .as_array()
+        .expect("result array")
+        .iter()
+        .map(|hit| {
+            assert_eq!(hit["path"], "notes.txt");
+            hit["index"].as_str().unwrap()
+        })
+        .collect();
+    indexes.sort();
+    assert_eq!(
+        indexes,
+        vec![index_a.to_str().unwrap(), index_b.to_str().unwrap()]
+    );
+}
+
+#[test]
+fn since_fails_on_relative_paths_from_search_index() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let index_a = build_relative_path_index(&temp_dir.path().join("a"), "invoice from alpha");
+
+    let envs = test_environment(&temp_dir);
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    search_cmd
+        .args(["search", "invoice", "--since", "2000-01-01", "--index"])
+        .arg(&index_a)
+        .assert()
+        .failure()
+        .stderr(contains("--since needs each file on disk"))
+        .stdout(contains("No results found").not());
+}
+
+#[test]
+fn search_index_does_not_read_relative_paths_from_the_working_directory() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let index_a = build_relative_path_index(&temp_dir.path().join("a"), "invoice from alpha");
+    // An unrelated file that a relative path would resolve to.
+    let cwd = temp_dir.path().join("elsewhere");
+    fs::create_dir_all(&cwd).expect("create cwd");
+    fs::write(cwd.join("notes.txt"), "unrelated\nlines\ninvoice here\n").expect("write file");
+
+    let envs = test_environment(&temp_dir);
+    let search = |extra: &[&str]| {
+        let mut cmd = cargo_bin_cmd!("vaultsearch");
+        apply_env(&mut cmd, &envs);
+        cmd.current_dir(&cwd)
+            .args(["search", "invoice", "--index"])
+            .arg(&index_a)
+            .args(extra)
+            .assert()
+            .success()
+    };
+    search(&["--line-numbers"])
+        .stdout(contains("notes.txt"))
+        .stdout(contains("notes.txt:").not());
+    search(&["--highlight-only"])
+        .stdout(contains(
+            "lines unavailable: path is stored relative to an unknown root",
+        ))
+        .stdout(contains("unrelated").not());
+    search(&["--preview-lines", "2"])
+        .stdout(contains(
+            "preview unavailable: path is stored relative to an unknown root",
+        ))
+        .stdout(contains("invoice here").not());
+}
+
+#[test]
+fn highlight_markers_wrap_matches_in_text_and_json_output() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    fs::create_dir_all(&root).expect("create root");
+    fs::write(
+        root.join("page.html"),
+        "<b>bold</b> & the lighthouse keeper",
+    )
+    .expect("write file");
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success();
+
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    search_cmd
+        .args([
+            "search",
+            "lighthouse",
+            "--highlight-open",
+            "[[",
+            "--highlight-close",
+            "]]",
+        ])
+        .assert()
+        .success()
+        .stdout(contains("<b>bold</b> & the [[lighthouse]] keeper"))
+        .stdout(contains("\x1b[").not());
+
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    search_cmd
+        .args([
+            "search",
+            "lighthouse",
+            "--format",
+            "json",
+            "--highlight-open",
+            "{",
+            "--highlight-close",
+            "}",
+        ])
+        .assert()
+        .success()
+        .stdout(contains(
+            r#""snippet": "<b>bold</b> & the {lighthouse} keeper""#,
+        ));
+}
+
+#[test]
+fn preview_lines_highlight_query_terms() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    fs::create_dir_all(&root).expect("create root");
+    fs::write(
+        root.join("app.log"),
+        "started\nconnection timeout on port 8080\nretrying\n",
+    )
+    .expect("write file");
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success();
+
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    search_cmd
+        .args([
+            "search",
+            "timeout",
+            "--preview-lines",
+            "2",
+            "--highlight-open",
+            "[[",
+            "--highlight-close",
+            "]]",
+        ])
+        .assert()
+        .success()
+        .stdout(contains("--- last 2 line(s) ---"))
+        .stdout(contains(
+            "      connection [[timeout]] on port 8080\n      retrying\n",
+        ));
+}
+
+#[test]
+fn line_numbers_point_at_first_matching_line() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    fs::create_dir_all(&root).expect("create root");
+    fs::write(
+        root.join("app.log"),
+        "started\nlistening\nconnection timeout on port 8080\nretrying\n",
+    )
+    .expect("write file");
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success();
+
+    let mut search_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut search_cmd, &envs);
+    search_cmd
+        .args(["search", "timeout", "--line-numbers"])
+        .assert()
+        .success()
+        .stdout(contains("app.log:3"));
+
+    let mut json_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut json_cmd, &envs);
+    json_cmd
+        .args(["search", "timeout", "--line-numbers", "--format", "json"])
+        .assert()
+        .success()
+        .stdout(contains("\"line\": 3"));
+
+    fs::remove_file(root.join("app.log")).expect("remove file");
+    let mut deleted_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut deleted_cmd, &envs);
+    let output = deleted_cmd
+        .args(["search", "timeout", "--line-numbers", "--format", "json"])
+        .output()
+        .expect("run search");
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("\"line\""));
+}
+
+#[test]
+fn no_merge_policy_keeps_segments_until_compact() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    create_sample_files(&root);
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success();
+
+    for (i, word) in ["alpha", "beta"].iter().enumerate() {
+        fs::write(root.join(format!("bulk{i}.txt")), format!("{word} batch")).expect("write file");
+        let mut index_cmd = cargo_bin_cmd!("vaultsearch");
+        apply_env(&mut index_cmd, &envs);
+        index_cmd
+            .args(["index", "--merge-policy", "no-merge"])
+            .assert()
+            .success()
+            .stdout(contains("Merge policy   : no-merge"))
+            .stdout(contains(format!("Segments       : {}", i + 2)));
+    }
+
+    let mut compact_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut compact_cmd, &envs);
+    compact_cmd
+        .arg("compact")
+        .assert()
+        .success()
+        .stdout(contains("Segments       : 3 -> 1"));
+
+    let mut invalid_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut invalid_cmd, &envs);
+    invalid_cmd
+        .args([
+            "index",
+            "--merge-policy",
+            "no-merge",
+            "--merge-min-segments",
+            "4",
+        ])
+        .assert()
+        .failure()
+        .stderr(contains("only applies to the `log` merge policy"));
+}
+
+#[test]
+fn validate_query_reports_parse_errors_without_searching() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    create_sample_files(&root);
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success();
+
+    let mut valid_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut valid_cmd, &envs);
+    valid_cmd
+        .args(["validate-query", "rust AND search"])
+        .assert()
+        .success()
+        .stdout("");
+
+    let mut verbose_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut verbose_cmd, &envs);
+    verbose_cmd
+        .args(["validate-query", "rust", "--verbose"])
+        .assert()
+        .success()
+        .stdout(contains("Query is valid: rust"));
+
+    let mut invalid_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut invalid_cmd, &envs);
+    invalid_cmd
+        .args(["validate-query", "rust AND ("])
+        .assert()
+        .code(5)
+        .stderr(contains("Failed to parse query"));
+}
+
+#[test]
+fn comments_flag_searches_only_source_comments() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    fs::create_dir_all(&root).expect("create root");
+    fs::write(
+        root.join("lib.rs"),
+        "/// Parses the widget manifest.\nfn load() {\n    let frobnicate = 1;\n}\n",
+    )
+    .expect("write file");
+    fs::write(root.join("notes.md"), "# widget ideas\n").expect("write file");
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success();
+
+    let mut comment_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut comment_cmd, &envs);
+    comment_cmd
+        .args(["search", "widget", "--comments", "--format", "null"])
+        .assert()
+        .success()
+        .stdout(contains("lib.rs"))
+        .stdout(contains("notes.md").not());
+
+    let mut code_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut code_cmd, &envs);
+    code_cmd
+        .args(["search", "frobnicate", "--comments"])
+        .assert()
+        .success()
+        .stdout(contains("No results found"));
+}
+
+#[test]
+fn result_cache_serves_repeat_searches_until_next_index_run() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    create_sample_files(&root);
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success();
+    let config_path = PathBuf::from(&envs["XDG_CONFIG_HOME"])
+        .join("vaultsearch")
+        .join("config.toml");
+    let config = fs::read_to_string(&config_path).expect("read config");
+    fs::write(&config_path, format!("cache_results = true\n{config}")).expect("write config");
+
+    let search = |extra: &[&str]| {
+        let mut cmd = cargo_bin_cmd!("vaultsearch");
+        apply_env(&mut cmd, &envs);
+        cmd.args(["search", "rust", "--verbose"])
+            .args(extra)
+            .assert()
+            .success()
+    };
+    search(&[]).stderr(contains("result cache").not());
+    search(&[])
+        .stderr(contains("Results served from the result cache"))
+        .stdout(contains("notes.txt"));
+    search(&["--no-cache"]).stderr(contains("result cache").not());
+    // Options measured against the current time are searched afresh every time.
+    for extra in [&["--modified-within", "1d"][..], &["--recency-boost"][..]] {
+        search(extra).stderr(contains("result cache").not());
+        search(extra).stderr(contains("result cache").not());
+    }
+
+    let mut index_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut index_cmd, &envs);
+    index_cmd.arg("index").assert().success();
+    search(&[]).stderr(contains("result cache").not());
+
+    let mut clear_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut clear_cmd, &envs);
+    clear_cmd
+        .args(["cache", "clear"])
+        .assert()
+        .success()
+        .stdout(contains("Result cache cleared."));
+    search(&[]).stderr(contains("result cache").not());
+}
+
+#[test]
+fn result_cache_is_kept_per_root() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    create_sample_files(&root);
+    let media = temp_dir.path().join("media");
+    fs::create_dir_all(&media).expect("create media root");
+    fs::write(media.join("clip.txt"), "rust conference talk").expect("write file");
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success();
+    let config_path = PathBuf::from(&envs["XDG_CONFIG_HOME"])
+        .join("vaultsearch")
+        .join("config.toml");
+    let config = fs::read_to_string(&config_path).expect("read config");
+    fs::write(
+        &config_path,
+        format!(
+            "cache_results = true\n{config}\n[roots.media]\nroot = {:?}\nindex_dir = {:?}\n",
+            media.to_str().unwrap(),
+            temp_dir.path().join("media-index").to_str().unwrap()
+        ),
+    )
+    .expect("write config");
+    let mut index_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut index_cmd, &envs);
+    index_cmd.args(["index", "--all-roots"]).assert().success();
+
+    let search = |extra: &[&str]| {
+        let mut cmd = cargo_bin_cmd!("vaultsearch");
+        apply_env(&mut cmd, &envs);
+        cmd.args(["search", "rust", "--verbose"])
+            .args(extra)
+            .assert()
+            .success()
+    };
+    search(&[]).stderr(contains("result cache").not());
+    search(&["--root-name", "media"]).stderr(contains("result cache").not());
+    // Searching the other root did not throw away this one's entries.
+    search(&[])
+        .stderr(contains("Results served from the result cache"))
+        .stdout(contains("notes.txt"));
+    search(&["--root-name", "media"])
+        .stderr(contains("Results served from the result cache"))
+        .stdout(contains("clip.txt"));
+}
+
+#[test]
+fn explain_file_reports_each_indexing_check() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    create_sample_files(&root);
+    fs::write(root.join("image.bin"), [0u8, 159, 146, 150]).expect("write file");
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success();
+
+    let mut text_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut text_cmd, &envs);
+    text_cmd
+        .args(["index", "--explain-file"])
+        .arg(root.join("notes.txt"))
+        .assert()
+        .success()
+        .stdout(contains(
+            "[pass] extension     : `.txt` is a supported text extension",
+        ))
+        .stdout(contains("Result: would be indexed."))
+        .stdout(contains("Indexing...").not());
+
+    let mut binary_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut binary_cmd, &envs);
+    binary_cmd
+        .args(["index", "--min-file-size", "10", "--explain-file"])
+        .arg(root.join("image.bin"))
+        .assert()
+        .success()
+        .stdout(contains("[fail] extension"))
+        .stdout(contains("[fail] min size      : 4 bytes, minimum 10"))
+        .stdout(contains("[fail] binary sniff  : content looks binary"))
+        .stdout(contains(
+            "Result: would be skipped (extension, min size, binary sniff).",
+        ));
+}
+
+#[test]
+fn named_roots_have_their_own_index_and_search_together() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    create_sample_files(&root);
+    let media = temp_dir.path().join("media");
+    fs::create_dir_all(&media).expect("create media root");
+    fs::write(media.join("clip.txt"), "rust conference talk").expect("write file");
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success();
+    let config_path = PathBuf::from(&envs["XDG_CONFIG_HOME"])
+        .join("vaultsearch")
+        .join("config.toml");
+    let media_index = temp_dir.path().join("media-index");
+    let mut config = fs::read_to_string(&config_path).expect("read config");
+    config.push_str(&format!(
+        "\n[roots.media]\nroot = {:?}\nindex_dir = {:?}\n",
+        media.to_str().unwrap(),
+        media_index.to_str().unwrap()
+    ));
+    fs::write(&config_path, config).expect("write config");
+
+    let mut index_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut index_cmd, &envs);
+    index_cmd
+        .args(["index", "--all-roots"])
+        .assert()
+        .success()
+        .stdout(contains("Created index for root `media`"))
+        .stderr(contains("Ctrl-C").not());
+    let config = fs::read_to_string(&config_path).expect("read config");
+    assert!(config.contains("index_generation = 1"), "{config}");
+
+    let mut named_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut named_cmd, &envs);
+    named_cmd
+        .args(["search", "rust", "--root-name", "media", "--format", "null"])
+        .assert()
+        .success()
+        .stdout(contains("clip.txt"))
+        .stdout(contains("notes.txt").not());
+
+    let mut all_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut all_cmd, &envs);
+    all_cmd
+        .args(["search", "rust", "--all-roots", "--format", "json"])
+        .assert()
+        .success()
+        .stdout(contains("\"index\": \"media\""))
+        .stdout(contains("\"index\": \"main\""));
+
+    let mut unknown_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut unknown_cmd, &envs);
+    unknown_cmd
+        .args(["search", "rust", "--root-name", "photos"])
+        .assert()
+        .code(3)
+        .stderr(contains("configured roots: media"));
+}
+
+#[test]
+fn overlapping_roots_warn_and_merge_each_file_once() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    create_sample_files(&root);
+    let nested = root.join("talks");
+    fs::create_dir_all(&nested).expect("create nested root");
+    fs::write(nested.join("clip.txt"), "rust conference talk").expect("write file");
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success();
+    let config_path = PathBuf::from(&envs["XDG_CONFIG_HOME"])
+        .join("vaultsearch")
+        .join("config.toml");
+    let mut config = fs::read_to_string(&config_path).expect("read config");
+    config.push_str(&format!(
+        "\n[roots.talks]\nroot = {:?}\nindex_dir = {:?}\n",
+        nested.to_str().unwrap(),
+        temp_dir.path().join("talks-index").to_str().unwrap()
+    ));
+    fs::write(&config_path, config).expect("write config");
+
+    let mut index_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut index_cmd, &envs);
+    index_cmd
+        .args(["index", "--all-roots"])
+        .assert()
+        .success()
+        .stderr(contains("Root `talks`").and(contains("is inside root `main`")));
+
+    let mut all_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut all_cmd, &envs);
+    let output = all_cmd
+        .args(["search", "conference", "--all-roots", "--format", "json"])
+        .output()
+        .expect("run search");
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("is inside root `main`"));
+    let results: serde_json::Value =
+        serde_json::from_slice(&output.stdout).expect("parse json results");
+    let results = results.as_array().expect("result array");
+    assert_eq!(results.len(), 1, "{results:?}");
+}
+
+#[test]
+fn since_filters_on_live_modification_time() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    fs::create_dir_all(&root).expect("create root");
+    let fresh = root.join("fresh.txt");
+    fs::write(&fresh, "budget draft").expect("write fresh");
+    let stale = root.join("stale.txt");
+    fs::write(&stale, "budget final").expect("write stale");
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success();
+
+    // Backdated after indexing, so only the file on disk knows it is old.
+    let in_2001 = std::time::UNIX_EPOCH + std::time::Duration::from_secs(978_307_200);
+    fs::File::options()
+        .write(true)
+        .open(&stale)
+        .and_then(|file| file.set_modified(in_2001))
+        .expect("backdate stale");
+
+    let mut since_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut since_cmd, &envs);
+    since_cmd
+        .args([
+            "search",
+            "budget",
+            "--since",
+            "2010-01-01",
+            "--format",
+            "null",
+        ])
+        .assert()
+        .success()
+        .stdout(contains("fresh.txt"))
+        .stdout(contains("stale.txt").not());
+
+    fs::remove_file(&fresh).expect("remove fresh");
+    let mut deleted_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut deleted_cmd, &envs);
+    deleted_cmd
+        .args(["search", "budget", "--since", "2000-06-01T00:00:00Z"])
+        .assert()
+        .success()
+        .stdout(contains("stale.txt"))
+        .stdout(contains("fresh.txt").not());
+
+    let mut invalid_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut invalid_cmd, &envs);
+    invalid_cmd
+        .args(["search", "budget", "--since", "last week"])
+        .assert()
+        .failure()
+        .stderr(contains("RFC 3339"));
+}
+
+#[test]
+fn manifest_round_trips_and_detects_changed_index_files() {
+    let temp_dir = TempDir::new().expect("create temp dir");
+    let envs = test_environment(&temp_dir);
+
+    let root = temp_dir.path().join("workspace");
+    create_sample_files(&root);
+    let mut init_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut init_cmd, &envs);
+    init_cmd
+        .args(["init", "--root", root.to_str().unwrap()])
+        .assert()
+        .success();
+
+    let mut manifest_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut manifest_cmd, &envs);
+    let output = manifest_cmd.arg("manifest").output().expect("run manifest");
+    assert!(output.status.success());
+    let manifest: serde_json::Value =
+        serde_json::from_slice(&output.stdout).expect("manifest is JSON");
+    let files = manifest["files"].as_array().expect("files array");
+    assert!(files.iter().any(|file| file["path"] == "meta.json"));
+    assert!(files
+        .iter()
+        .all(|file| file["blake3"].as_str().unwrap().len() == 64));
+    let manifest_path = temp_dir.path().join("manifest.json");
+    fs::write(&manifest_path, &output.stdout).expect("save manifest");
+
+    let mut verify_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut verify_cmd, &envs);
+    verify_cmd
+        .args(["manifest", "--verify"])
+        .arg(&manifest_path)
+        .assert()
+        .success()
+        .stdout(contains("all match"));
+
+    let index_dir = PathBuf::from(manifest["index_dir"].as_str().unwrap());
+    let meta = fs::read_to_string(index_dir.join("meta.json")).expect("read meta.json");
+    fs::write(index_dir.join("meta.json"), meta.replacen('{', "{ ", 1)).expect("corrupt meta.json");
+    let mut corrupt_cmd = cargo_bin_cmd!("vaultsearch");
+    apply_env(&mut corrupt_cmd, &envs);
+    corrupt_cmd
+        .args(["manifest", "--verify"])
+        .arg(&manifest_path)
+        .assert()
+        .code(6)
+        .stdout(contains("[changed] meta.json"));
+}